@@ -4,7 +4,7 @@
 //!
 //! Enable with the `areamy` feature flag.
 
-use crate::error::AccessError;
+use crate::error::{AccessError, BlobfigError, ParseError};
 use areamy::any_err;
 use areamy::error::{AnyErr, Error};
 
@@ -22,6 +22,34 @@ impl From<AccessError> for Error {
     }
 }
 
+impl AnyErr for ParseError {}
+
+impl From<ParseError> for Box<dyn AnyErr> {
+    fn from(value: ParseError) -> Self {
+        Box::new(value)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(value: ParseError) -> Self {
+        any_err!(value)
+    }
+}
+
+impl AnyErr for BlobfigError {}
+
+impl From<BlobfigError> for Box<dyn AnyErr> {
+    fn from(value: BlobfigError) -> Self {
+        Box::new(value)
+    }
+}
+
+impl From<BlobfigError> for Error {
+    fn from(value: BlobfigError) -> Self {
+        any_err!(value)
+    }
+}
+
 // NdarrayError integration (when both areamy and ndarray features are enabled)
 #[cfg(feature = "ndarray")]
 mod ndarray_errors {
@@ -72,6 +100,15 @@ mod tests {
         assert!(boxed.to_string().contains("int"));
     }
 
+    #[test]
+    fn parse_error_to_anyerr() {
+        let bytes = crate::writer::to_bytes(crate::types::Value::Int(1)).unwrap();
+        let err = crate::parse(&bytes[..bytes.len() - 1]).unwrap_err();
+
+        let boxed: Box<dyn AnyErr> = err.into();
+        assert!(boxed.to_string().contains("Unexpected end of input"));
+    }
+
     #[cfg(feature = "ndarray")]
     #[test]
     fn ndarray_error_to_anyerr() {
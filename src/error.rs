@@ -1,14 +1,14 @@
 //! Error types for blobfig
 
-use crate::types::ValueTag;
+use crate::types::{Cipher, Compression, DType, KeyId, ValueTag};
 use parsicomb::{CodeLoc, ErrorLeaf, ErrorNode, ParsicombError};
 use std::borrow::Cow;
 use std::error::Error;
 use std::fmt;
 
-/// Blobfig-specific error type
-#[derive(Debug)]
-pub enum BlobfigError<'a> {
+/// Kind of malformed input found while parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlobfigError {
     /// Invalid magic bytes
     InvalidMagic,
     /// Unsupported version
@@ -19,18 +19,89 @@ pub enum BlobfigError<'a> {
     InvalidDType(u8),
     /// Invalid UTF-8 in string
     InvalidUtf8,
+    /// String array end offset that is out of order or past its data
+    InvalidStringOffset(u32),
+    /// Timestamp whose nanosecond count is a second or more
+    InvalidTimestamp(u32),
+    /// Bool array element that is neither 0 nor 1
+    InvalidBool(u8),
+    /// Invalid sparse tensor format byte
+    InvalidSparseFormat(u8),
+    /// Sparse tensor whose arrays do not match its shape, or whose indices
+    /// are out of bounds
+    InvalidSparse(&'static str),
+    /// Chunked array whose chunks do not match its grid, or whose chunk
+    /// offset table does not match the chunks
+    InvalidChunkedArray(&'static str),
+    /// Array layout whose strides or axis names do not match its shape
+    InvalidArrayLayout(&'static str),
+    /// Array layout byte with unknown flags set
+    InvalidArrayLayoutFlags(u8),
+    /// Dequantization of an array whose dtype is not quantized
+    NotQuantized(DType),
     /// Data size mismatch
     DataSizeMismatch { expected: u64, actual: u64 },
-    /// Wrapped parsicomb error
-    Parse(ParsicombError<'a>),
-    /// Generic error with message and location
-    Custom {
-        message: Cow<'static, str>,
-        loc: CodeLoc<'a>,
-    },
+    /// Array shape whose element count or byte size overflows u64
+    ShapeOverflow,
+    /// Input ended in the middle of a value
+    UnexpectedEof,
+    /// Container length prefix that runs past the end of the input
+    ContainerPastEnd(u64),
+    /// Container body whose length differs from its length prefix
+    InvalidContainerLength { declared: u64, actual: u64 },
+    /// A [`ParseOptions`](crate::parser::ParseOptions) limit was exceeded
+    LimitExceeded { limit: Limit, max: usize },
+    /// An encoded size does not fit in `usize` on this platform
+    SizeOverflow(u64),
+    /// Invalid compression codec byte
+    InvalidCompression(u8),
+    /// Filter byte with unknown filters set
+    InvalidFilters(u8),
+    /// Payload compressed with a codec whose cargo feature is not enabled
+    UnsupportedCompression(Compression),
+    /// Compressed payload does not decompress to its recorded size
+    InvalidCompressedData,
+    /// Invalid cipher byte
+    InvalidCipher(u8),
+    /// Payload encrypted with a cipher whose cargo feature is not enabled
+    UnsupportedCipher(Cipher),
+    /// Payload is encrypted and has to be decrypted with a key
+    Encrypted,
+    /// The key provider has no key with this name
+    UnknownKey(String),
+    /// Wrong key, or the payload or its metadata were tampered with
+    DecryptionFailed,
+    /// Payload too large for the cipher to encrypt
+    PayloadTooLarge,
+    /// Invalid payload storage byte
+    InvalidStorage(u8),
+    /// Payload reference that does not point to earlier bytes of the file
+    InvalidReference(u64),
+    /// Payload or document bytes do not match their recorded checksum
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// Verification was requested but no checksum was recorded
+    MissingChecksum,
+    /// A path index was requested but the file has none
+    MissingPathIndex,
+    /// Path index table or record that does not fit in its trailer
+    MalformedPathIndex,
+    /// Trailer whose recorded start is out of range
+    InvalidTrailerStart(u64),
+    /// Trailer whose size in bytes does not match its kind
+    InvalidTrailerSize(u64),
+    /// Header flags with a log2 payload alignment above the supported maximum
+    UnsupportedAlignment(u8),
+    /// Signature verification was requested but the file is not signed
+    MissingSignature,
+    /// The file was signed with a key that is not trusted
+    UntrustedKey(KeyId),
+    /// The signature does not match the signed bytes
+    InvalidSignature,
+    /// Generic error with message
+    Custom { message: Cow<'static, str> },
 }
 
-impl<'a> fmt::Display for BlobfigError<'a> {
+impl fmt::Display for BlobfigError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             BlobfigError::InvalidMagic => write!(f, "Invalid magic bytes, not a blobfig file"),
@@ -38,6 +109,27 @@ impl<'a> fmt::Display for BlobfigError<'a> {
             BlobfigError::InvalidValueTag(tag) => write!(f, "Invalid value tag: 0x{:02X}", tag),
             BlobfigError::InvalidDType(tag) => write!(f, "Invalid dtype tag: 0x{:02X}", tag),
             BlobfigError::InvalidUtf8 => write!(f, "Invalid UTF-8 in string"),
+            BlobfigError::InvalidStringOffset(end) => {
+                write!(f, "Invalid string array offset: {}", end)
+            }
+            BlobfigError::InvalidTimestamp(nanos) => {
+                write!(f, "Invalid timestamp: {} nanoseconds", nanos)
+            }
+            BlobfigError::InvalidSparseFormat(tag) => {
+                write!(f, "Invalid sparse format: 0x{:02X}", tag)
+            }
+            BlobfigError::InvalidSparse(reason) => write!(f, "Invalid sparse tensor: {}", reason),
+            BlobfigError::InvalidChunkedArray(reason) => {
+                write!(f, "Invalid chunked array: {}", reason)
+            }
+            BlobfigError::InvalidArrayLayout(reason) => {
+                write!(f, "Invalid array layout: {}", reason)
+            }
+            BlobfigError::InvalidArrayLayoutFlags(flags) => {
+                write!(f, "Invalid array layout flags: 0x{:02X}", flags)
+            }
+            BlobfigError::NotQuantized(dtype) => write!(f, "Not a quantized dtype: {:?}", dtype),
+            BlobfigError::InvalidBool(byte) => write!(f, "Invalid bool element: 0x{:02X}", byte),
             BlobfigError::DataSizeMismatch { expected, actual } => {
                 write!(
                     f,
@@ -45,36 +137,186 @@ impl<'a> fmt::Display for BlobfigError<'a> {
                     expected, actual
                 )
             }
-            BlobfigError::Parse(e) => write!(f, "{}", e),
-            BlobfigError::Custom { message, loc } => {
-                write!(f, "{} at position {}", message, loc.position())
+            BlobfigError::ShapeOverflow => write!(f, "Array shape overflows u64"),
+            BlobfigError::UnexpectedEof => write!(f, "Unexpected end of input"),
+            BlobfigError::ContainerPastEnd(len) => {
+                write!(f, "Container length {} runs past the end of input", len)
+            }
+            BlobfigError::InvalidContainerLength { declared, actual } => write!(
+                f,
+                "Container body is {} bytes, length prefix says {}",
+                actual, declared
+            ),
+            BlobfigError::LimitExceeded { limit, max } => {
+                write!(f, "Limit exceeded: {} is at most {}", limit, max)
+            }
+            BlobfigError::SizeOverflow(size) => {
+                write!(f, "Size {} does not fit in memory on this platform", size)
+            }
+            BlobfigError::InvalidCompression(codec) => {
+                write!(f, "Invalid compression codec: 0x{:02X}", codec)
+            }
+            BlobfigError::InvalidFilters(filters) => {
+                write!(f, "Invalid payload filters: 0x{:02X}", filters)
+            }
+            BlobfigError::UnsupportedCompression(codec) => {
+                write!(f, "Compression codec {} is not enabled", codec)
+            }
+            BlobfigError::InvalidCompressedData => write!(f, "Corrupt compressed payload"),
+            BlobfigError::InvalidCipher(cipher) => {
+                write!(f, "Invalid cipher: 0x{:02X}", cipher)
             }
+            BlobfigError::UnsupportedCipher(cipher) => {
+                write!(f, "Cipher {} is not enabled", cipher)
+            }
+            BlobfigError::Encrypted => write!(f, "Payload is encrypted"),
+            BlobfigError::UnknownKey(key_id) => write!(f, "Unknown encryption key: {}", key_id),
+            BlobfigError::DecryptionFailed => {
+                write!(f, "Decryption failed: wrong key or tampered payload")
+            }
+            BlobfigError::PayloadTooLarge => write!(f, "Payload too large to encrypt"),
+            BlobfigError::InvalidStorage(storage) => {
+                write!(f, "Invalid payload storage: 0x{:02X}", storage)
+            }
+            BlobfigError::InvalidReference(offset) => {
+                write!(f, "Payload reference to offset {} is out of range", offset)
+            }
+            BlobfigError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Checksum mismatch: expected 0x{:08X}, got 0x{:08X}",
+                expected, actual
+            ),
+            BlobfigError::MissingChecksum => write!(f, "No checksum recorded"),
+            BlobfigError::MissingPathIndex => write!(f, "File has no path index"),
+            BlobfigError::MalformedPathIndex => write!(f, "Malformed path index"),
+            BlobfigError::InvalidTrailerStart(start) => {
+                write!(f, "Trailer start {} out of range", start)
+            }
+            BlobfigError::InvalidTrailerSize(size) => {
+                write!(f, "Trailer has the wrong size: {} bytes", size)
+            }
+            BlobfigError::UnsupportedAlignment(log2) => {
+                write!(f, "Unsupported payload alignment: 2^{}", log2)
+            }
+            BlobfigError::MissingSignature => write!(f, "File is not signed"),
+            BlobfigError::UntrustedKey(key_id) => write!(f, "Untrusted signing key: {}", key_id),
+            BlobfigError::InvalidSignature => write!(f, "Invalid signature"),
+            BlobfigError::Custom { message } => write!(f, "{}", message),
         }
     }
 }
 
-impl<'a> Error for BlobfigError<'a> {}
+impl Error for BlobfigError {}
+
+/// Parse limit that was exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// Nesting depth of Objects and Lists
+    Depth,
+    /// Entries in one Object or items in one List
+    ContainerLen,
+    /// Values in the whole tree
+    TotalValues,
+    /// Byte length of a String value or Object key
+    StringLen,
+    /// Decompressed size of one Array or File payload
+    DecompressedSize,
+}
 
-impl<'a> From<ParsicombError<'a>> for BlobfigError<'a> {
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Depth => write!(f, "nesting depth"),
+            Limit::ContainerLen => write!(f, "container length"),
+            Limit::TotalValues => write!(f, "total value count"),
+            Limit::StringLen => write!(f, "string length"),
+            Limit::DecompressedSize => write!(f, "decompressed size"),
+        }
+    }
+}
+
+impl BlobfigError {
+    /// Create a generic error with the given message
+    pub fn custom(message: impl Into<Cow<'static, str>>) -> Self {
+        BlobfigError::Custom {
+            message: message.into(),
+        }
+    }
+}
+
+/// Error returned by the parsers
+///
+/// Owns all of its data, so it can outlive the input buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// What was wrong with the input
+    pub kind: BlobfigError,
+    /// Byte offset in the input where the problem was found
+    pub offset: usize,
+    /// Slash path of the value being decoded (empty for the root)
+    pub path: String,
+}
+
+impl ParseError {
+    /// Create an error at a byte offset, outside of any path
+    pub fn new(kind: BlobfigError, offset: usize) -> Self {
+        Self {
+            kind,
+            offset,
+            path: String::new(),
+        }
+    }
+
+    /// Prefix the path with the key or index of an enclosing container
+    pub(crate) fn within(mut self, prefix: &str) -> Self {
+        self.path = match (prefix.is_empty(), self.path.is_empty()) {
+            (true, _) => self.path,
+            (false, true) => prefix.to_string(),
+            (false, false) => format!("{}/{}", prefix, self.path),
+        };
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)?;
+        if !self.path.is_empty() {
+            write!(f, " in '{}'", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.kind)
+    }
+}
+
+impl<'a> From<ParsicombError<'a>> for ParseError {
     fn from(e: ParsicombError<'a>) -> Self {
-        BlobfigError::Parse(e)
+        match e {
+            ParsicombError::SyntaxError { message, loc } => {
+                ParseError::new(BlobfigError::Custom { message }, loc.position())
+            }
+            ParsicombError::UnexpectedEndOfFile(loc) => {
+                ParseError::new(BlobfigError::UnexpectedEof, loc.position())
+            }
+        }
     }
 }
 
-impl<'a> ErrorLeaf<'a> for BlobfigError<'a> {
+impl<'a> ErrorLeaf<'a> for ParseError {
     type Element = u8;
 
     fn loc(&self) -> CodeLoc<'a, Self::Element> {
-        match self {
-            BlobfigError::Parse(e) => e.loc(),
-            BlobfigError::Custom { loc, .. } => *loc,
-            // For errors without location info, return position 0
-            _ => CodeLoc::new(&[], 0),
-        }
+        // The error does not borrow the input, so only the offset is known
+        CodeLoc::new(&[], self.offset)
     }
 }
 
-impl<'a> ErrorNode<'a> for BlobfigError<'a> {
+impl<'a> ErrorNode<'a> for ParseError {
     type Element = u8;
 
     fn likely_error(&self) -> &dyn ErrorLeaf<'a, Element = Self::Element> {
@@ -93,6 +335,11 @@ pub enum AccessError {
         expected: &'static str,
         actual: ValueTag,
     },
+    /// References followed while resolving path lead back to themselves
+    ReferenceCycle { path: String },
+    /// Resolving path took a chain of more than `max` references, or more
+    /// than `max` references in all
+    TooManyReferences { path: String, max: usize },
 }
 
 impl fmt::Display for AccessError {
@@ -108,6 +355,10 @@ impl fmt::Display for AccessError {
                 "type mismatch at '{}': expected {}, got {:?}",
                 path, expected, actual
             ),
+            AccessError::ReferenceCycle { path } => write!(f, "reference cycle at '{}'", path),
+            AccessError::TooManyReferences { path, max } => {
+                write!(f, "more than {} references followed at '{}'", max, path)
+            }
         }
     }
 }
@@ -7,9 +7,24 @@
 //! # Features
 //!
 //! - Zero-copy parsing from memory-mapped files
+//! - Lazy access that decodes only the path being read
+//! - Random-access reading from any `Read + Seek` stream
+//! - Array and File payloads aligned for in-place typed views
+//! - Optional checksums per payload and for the whole file
+//! - Optional zstd or lz4 compression of Array and File payloads, with
+//!   byte-shuffle and delta filters for typed arrays
+//! - Optional AES-256-GCM or ChaCha20-Poly1305 encryption of selected payloads
+//! - Optional Ed25519 signatures, embedded or detached
+//! - Optional deduplication of identical Array and File payloads
 //! - Streaming write support for large files
 //! - Typed arrays (numpy-like) with shape information
+//! - Block-quantized int4, Q4_0 and Q8_0 arrays, dequantized to f32
+//! - Fortran-order and strided arrays with named axes
+//! - Sparse tensors in COO or CSR format
+//! - Chunked arrays, written from a producer and read one chunk at a time
+//! - Packed string arrays for vocabularies, readable without allocating
 //! - Nested key-value structure (like JSON)
+//! - References between paths, validated when written
 //! - Little-endian, portable across architectures
 //!
 //! # Example
@@ -36,6 +51,7 @@
 
 pub mod error;
 pub mod parser;
+pub mod reader;
 pub mod types;
 pub mod writer;
 
@@ -45,13 +61,35 @@ pub mod ndarray_ext;
 #[cfg(feature = "areamy")]
 pub mod areamy_ext;
 
+#[cfg(feature = "mmap")]
+pub mod mmap;
+
+#[cfg(feature = "proptest")]
+pub mod proptest_ext;
+
+#[cfg(feature = "signing")]
+pub mod signing;
+
 // Re-export common types at crate root
-pub use error::AccessError;
-pub use parser::parse;
+pub use error::{AccessError, BlobfigError, Limit, ParseError};
+pub use parser::{
+    LazyValue, LazyView, ParseOptions, PathIndex, parse, parse_index, parse_lazy, parse_verified,
+    parse_with,
+};
+pub use reader::Reader;
 pub use types::{
-    Array, ArrayView, DType, File, FileData, FileHandle, FileView, HEADER_SIZE, MAGIC, VERSION,
-    Value, ValueTag, ValueView,
+    Array, ArrayLayout, ArrayView, ChunkData, ChunkProducer, ChunkedArray, ChunkedArrayView,
+    Cipher, Compression, DEFAULT_ALIGNMENT, DType, File, FileData, FileHandle, FileView, Filters,
+    HEADER_SIZE, Header, KEY_SIZE, KeyId, KeyProvider, Layout, MAGIC, MAX_REFERENCE_HOPS,
+    MAX_REFERENCE_LOOKUPS, Order, PAGE_ALIGNMENT, Sparse, SparseFormat, SparseIndices, SparseView,
+    StringArray, StringArrayView, TAG_SIZE, Timestamp, VERSION, Value, ValueTag, ValueView,
 };
 
 #[cfg(feature = "ndarray")]
 pub use ndarray_ext::{ArrayType, NdarrayError};
+
+#[cfg(feature = "mmap")]
+pub use mmap::BlobfigFile;
+
+#[cfg(feature = "signing")]
+pub use signing::parse_signed;
@@ -0,0 +1,143 @@
+//! Memory-mapped blobfig documents
+//!
+//! [`BlobfigFile`] owns the mapping so views into it can live as long as the
+//! document itself, e.g. inside an `Arc` shared between threads.
+//!
+//! Enable with the `mmap` feature flag.
+
+use crate::error::ParseError;
+use crate::parser::{self, LazyValue, ParseOptions, PathIndex};
+use crate::types::{HEADER_SIZE, Layout, ValueView};
+use memmap2::Mmap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A memory-mapped blobfig with a validated header
+///
+/// All views borrow from `&self`. The mapping starts on a page boundary, so
+/// aligned Array payloads can be viewed in place.
+#[derive(Debug)]
+pub struct BlobfigFile {
+    map: Mmap,
+    layout: Layout,
+}
+
+impl BlobfigFile {
+    /// Map the file at `path` and validate its header
+    ///
+    /// The file must not be modified or truncated while it is mapped.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        // SAFETY: the mapping is read-only; callers must not modify the file
+        // while it is open, as documented above.
+        let map = unsafe { Mmap::map(&file)? };
+        Self::from_mmap(map)
+    }
+
+    /// Take ownership of an existing mapping and validate its header
+    pub fn from_mmap(map: Mmap) -> io::Result<Self> {
+        let layout = parser::parse_header(&map)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self { map, layout })
+    }
+
+    /// The mapped bytes
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Layout of the value tree
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Decode the whole value tree
+    pub fn parse(&self) -> Result<ValueView<'_>, ParseError> {
+        parser::parse(&self.map)
+    }
+
+    /// Decode the whole value tree, enforcing the limits in `options`
+    pub fn parse_with(&self, options: ParseOptions) -> Result<ValueView<'_>, ParseError> {
+        parser::parse_with(&self.map, options)
+    }
+
+    /// Decode the whole value tree, checking every recorded checksum
+    pub fn parse_verified(&self) -> Result<ValueView<'_>, ParseError> {
+        parser::parse_verified(&self.map)
+    }
+
+    /// Decode the whole value tree if one of `trusted_keys` signed the file
+    #[cfg(feature = "signing")]
+    pub fn parse_signed(
+        &self,
+        trusted_keys: &[ed25519_dalek::VerifyingKey],
+    ) -> Result<ValueView<'_>, ParseError> {
+        parser::parse_signed(&self.map, trusted_keys)
+    }
+
+    /// The root value, decoded node by node as it is visited
+    pub fn lazy(&self) -> LazyValue<'_> {
+        LazyValue::new(&self.map, HEADER_SIZE, self.layout)
+    }
+
+    /// Get a nested value by path, decoding only that path and the value found
+    pub fn get(&self, path: &str) -> Result<Option<ValueView<'_>>, ParseError> {
+        match self.lazy().get(path)? {
+            Some(value) => Ok(Some(value.to_view()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Open the path index trailer
+    pub fn index(&self) -> Result<PathIndex<'_>, ParseError> {
+        parser::parse_index(&self.map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+    use crate::writer;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("blobfig-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<BlobfigFile>();
+    }
+
+    #[test]
+    fn open_and_get() {
+        let path = temp_path("open_and_get");
+        let value = Value::Object(vec![("rate".into(), Value::Int(16000))]);
+        fs::write(&path, writer::to_bytes(value).unwrap()).unwrap();
+
+        let file = BlobfigFile::open(&path).unwrap();
+        assert_eq!(file.get("rate").unwrap().unwrap().as_int(), Some(16000));
+        assert_eq!(
+            file.parse().unwrap().get("rate").unwrap().as_int(),
+            Some(16000)
+        );
+        assert!(file.get("missing").unwrap().is_none());
+
+        drop(file);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_header() {
+        let path = temp_path("rejects_invalid_header");
+        fs::write(&path, [0u8; HEADER_SIZE]).unwrap();
+
+        let err = BlobfigFile::open(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -5,8 +5,13 @@
 //!
 //! Enable with the `ndarray` feature flag.
 
-use crate::types::{Array, ArrayView, DType};
-use ndarray::{ArrayD, ArrayViewD, IxDyn};
+use crate::error::BlobfigError;
+use crate::types::{
+    Array, ArrayLayout, ArrayView, DType, Order, Sparse, SparseFormat, SparseView, invalid_element,
+};
+use half::{bf16, f16};
+use ndarray::{ArrayD, ArrayViewD, IxDyn, ShapeBuilder, StrideShape};
+use num_complex::Complex;
 
 /// Error type for ndarray conversions
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,8 +22,18 @@ pub enum NdarrayError {
     ShapeMismatch { shape: Vec<u64>, data_len: usize },
     /// Data is not properly aligned for the element type
     AlignmentError,
-    /// Array is not in standard (contiguous row-major) layout
+    /// Array is neither contiguous row-major nor contiguous column-major
     NotContiguous,
+    /// Data is stored compressed, so it cannot be viewed in place
+    Compressed,
+    /// Data is stored encrypted, so it cannot be viewed in place
+    Encrypted,
+    /// Compressed data could not be decompressed
+    Decompress(BlobfigError),
+    /// Bool array element that is neither 0 nor 1
+    InvalidBool(u8),
+    /// Sparse tensor with inconsistent shapes or out-of-range indices
+    Sparse(BlobfigError),
 }
 
 impl std::fmt::Display for NdarrayError {
@@ -47,13 +62,36 @@ impl std::fmt::Display for NdarrayError {
                     "Array is not contiguous; call .as_standard_layout().into_owned() first"
                 )
             }
+            NdarrayError::Compressed => {
+                write!(f, "Array data is compressed; use to_ndarray instead")
+            }
+            NdarrayError::Encrypted => {
+                write!(f, "Array data is encrypted; decrypt it first")
+            }
+            NdarrayError::Decompress(e) => write!(f, "Failed to decompress array data: {}", e),
+            NdarrayError::InvalidBool(byte) => write!(f, "Invalid bool element: 0x{:02X}", byte),
+            NdarrayError::Sparse(e) => write!(f, "Failed to densify sparse tensor: {}", e),
         }
     }
 }
 
 impl std::error::Error for NdarrayError {}
 
+impl From<BlobfigError> for NdarrayError {
+    fn from(e: BlobfigError) -> Self {
+        match e {
+            BlobfigError::InvalidBool(byte) => NdarrayError::InvalidBool(byte),
+            e @ BlobfigError::InvalidSparse(_) => NdarrayError::Sparse(e),
+            e => NdarrayError::Decompress(e),
+        }
+    }
+}
+
 /// Trait for types that can be stored in a blobfig array
+///
+/// Elements are read by reinterpreting their bytes. Every byte pattern is a
+/// valid value of these types except for `bool`, whose elements are checked
+/// to be 0 or 1 before they are read.
 pub trait ArrayType: Sized + Clone + 'static {
     const DTYPE: DType;
 }
@@ -88,6 +126,21 @@ impl ArrayType for f32 {
 impl ArrayType for f64 {
     const DTYPE: DType = DType::F64;
 }
+impl ArrayType for f16 {
+    const DTYPE: DType = DType::F16;
+}
+impl ArrayType for bf16 {
+    const DTYPE: DType = DType::BF16;
+}
+impl ArrayType for bool {
+    const DTYPE: DType = DType::Bool;
+}
+impl ArrayType for Complex<f32> {
+    const DTYPE: DType = DType::C64;
+}
+impl ArrayType for Complex<f64> {
+    const DTYPE: DType = DType::C128;
+}
 
 // =============================================================================
 // From ndarray to blobfig
@@ -96,12 +149,18 @@ impl ArrayType for f64 {
 impl Array {
     /// Create a blobfig Array from an ndarray ArrayD
     ///
-    /// Takes ownership of a contiguous array. Returns error if not contiguous.
+    /// Takes ownership of a contiguous array, in C order or, for a
+    /// column-major array such as a transpose, in Fortran order. Returns error
+    /// if not contiguous.
     /// Use `.as_standard_layout().into_owned()` to make non-contiguous arrays contiguous.
     pub fn from_ndarray<T: ArrayType>(arr: ArrayD<T>) -> Result<Self, NdarrayError> {
-        if !arr.is_standard_layout() {
+        let order = if arr.is_standard_layout() {
+            Order::C
+        } else if arr.t().is_standard_layout() {
+            Order::F
+        } else {
             return Err(NdarrayError::NotContiguous);
-        }
+        };
 
         let shape: Vec<u64> = arr.shape().iter().map(|&d| d as u64).collect();
         let (vec, offset) = arr.into_raw_vec_and_offset();
@@ -124,7 +183,7 @@ impl Array {
         // - byte_len/cap are correctly scaled for u8
         // - T is a primitive (ArrayType) with same memory repr as bytes
         let data = unsafe { Vec::from_raw_parts(ptr as *mut u8, byte_len, cap) };
-        Ok(Array::new(T::DTYPE, shape, data))
+        Ok(Array::new(T::DTYPE, shape, data).with_order(order))
     }
 }
 
@@ -142,19 +201,17 @@ impl Array {
             });
         }
 
-        let shape: Vec<usize> = self.shape.iter().map(|&d| d as usize).collect();
-        let expected_len = shape.iter().product::<usize>() * std::mem::size_of::<T>();
-
-        if self.data.len() != expected_len {
+        let data = self.decompressed()?;
+        // dtype matches T, so this is also the expected byte length of T elements
+        if self.expected_size() != Some(data.len() as u64) {
             return Err(NdarrayError::ShapeMismatch {
                 shape: self.shape.clone(),
-                data_len: self.data.len(),
+                data_len: data.len(),
             });
         }
 
         // Copy data into properly typed vec
-        let elements: Vec<T> = self
-            .data
+        let elements: Vec<T> = data
             .chunks_exact(std::mem::size_of::<T>())
             .map(|chunk| {
                 let mut arr = [0u8; 16]; // Max size we support
@@ -162,16 +219,14 @@ impl Array {
                 // SAFETY:
                 // - arr is a local buffer we just wrote valid bytes into
                 // - T is constrained to ArrayType (primitives only)
-                // - All primitive types have no invalid bit patterns
+                // - decompressed() checked Bool elements; other types have no
+                //   invalid bit patterns
                 // - read_unaligned handles any alignment
                 unsafe { std::ptr::read_unaligned(arr.as_ptr() as *const T) }
             })
             .collect();
 
-        ArrayD::from_shape_vec(IxDyn(&shape), elements).map_err(|_| NdarrayError::ShapeMismatch {
-            shape: self.shape.clone(),
-            data_len: self.data.len(),
-        })
+        laid_out(self.dtype, &self.shape, &self.layout, elements)
     }
 }
 
@@ -185,19 +240,17 @@ impl<'a> ArrayView<'a> {
             });
         }
 
-        let shape: Vec<usize> = self.shape.iter().map(|&d| d as usize).collect();
-        let expected_len = shape.iter().product::<usize>() * std::mem::size_of::<T>();
-
-        if self.data.len() != expected_len {
+        let data = self.decompressed()?;
+        // dtype matches T, so this is also the expected byte length of T elements
+        if self.expected_size() != Some(data.len() as u64) {
             return Err(NdarrayError::ShapeMismatch {
                 shape: self.shape.clone(),
-                data_len: self.data.len(),
+                data_len: data.len(),
             });
         }
 
         // Copy data into properly typed vec
-        let elements: Vec<T> = self
-            .data
+        let elements: Vec<T> = data
             .chunks_exact(std::mem::size_of::<T>())
             .map(|chunk| {
                 let mut arr = [0u8; 16];
@@ -207,15 +260,15 @@ impl<'a> ArrayView<'a> {
             })
             .collect();
 
-        ArrayD::from_shape_vec(IxDyn(&shape), elements).map_err(|_| NdarrayError::ShapeMismatch {
-            shape: self.shape.clone(),
-            data_len: self.data.len(),
-        })
+        laid_out(self.dtype, &self.shape, &self.layout, elements)
     }
 
     /// Try to create a zero-copy ndarray view
     ///
-    /// This will fail if the data is not properly aligned for the element type.
+    /// This will fail if the data is compressed, encrypted or not properly
+    /// aligned for the element type.
+    /// Files written with aligned payloads (the writer default) satisfy this as
+    /// long as the buffer itself is aligned, e.g. a memory-mapped file.
     pub fn try_as_ndarray<T: ArrayType>(&self) -> Result<ArrayViewD<'a, T>, NdarrayError> {
         if T::DTYPE != self.dtype {
             return Err(NdarrayError::DTypeMismatch {
@@ -223,11 +276,15 @@ impl<'a> ArrayView<'a> {
                 actual: self.dtype,
             });
         }
+        if self.compression.is_some() {
+            return Err(NdarrayError::Compressed);
+        }
+        if self.encryption.is_some() {
+            return Err(NdarrayError::Encrypted);
+        }
 
-        let shape: Vec<usize> = self.shape.iter().map(|&d| d as usize).collect();
-        let expected_len = shape.iter().product::<usize>() * std::mem::size_of::<T>();
-
-        if self.data.len() != expected_len {
+        // dtype matches T, so this is also the expected byte length of T elements
+        if self.expected_size() != Some(self.data.len() as u64) {
             return Err(NdarrayError::ShapeMismatch {
                 shape: self.shape.clone(),
                 data_len: self.data.len(),
@@ -235,23 +292,157 @@ impl<'a> ArrayView<'a> {
         }
 
         // Check alignment
-        if (self.data.as_ptr() as usize) % std::mem::align_of::<T>() != 0 {
+        if !(self.data.as_ptr() as usize).is_multiple_of(std::mem::align_of::<T>()) {
             return Err(NdarrayError::AlignmentError);
         }
+        if let Some(i) = invalid_element(self.dtype, self.data) {
+            return Err(NdarrayError::InvalidBool(self.data[i]));
+        }
 
         // SAFETY:
         // - Alignment is checked above before the cast
         // - Length is validated earlier (shape matches data.len())
-        // - T is constrained to ArrayType (primitives with no invalid bit patterns)
+        // - Bool elements are checked above; the other ArrayTypes have no
+        //   invalid bit patterns
         // - Lifetime 'a from ArrayView<'a> is preserved in ArrayViewD<'a, T>
         let slice = unsafe {
-            std::slice::from_raw_parts(self.data.as_ptr() as *const T, shape.iter().product())
+            std::slice::from_raw_parts(
+                self.data.as_ptr() as *const T,
+                self.data.len() / std::mem::size_of::<T>(),
+            )
         };
 
-        ArrayViewD::from_shape(IxDyn(&shape), slice).map_err(|_| NdarrayError::ShapeMismatch {
-            shape: self.shape.clone(),
-            data_len: self.data.len(),
-        })
+        stride_shape(self.dtype, &self.shape, &self.layout)
+            .and_then(|shape| ArrayViewD::from_shape(shape, slice).ok())
+            .ok_or_else(|| NdarrayError::ShapeMismatch {
+                shape: self.shape.clone(),
+                data_len: self.data.len(),
+            })
+    }
+}
+
+/// Shape the elements of an array in its layout as an owned ndarray
+///
+/// Arrays in another layout than C order are copied into C order, since
+/// their strides may not be usable by an owned ndarray.
+fn laid_out<T: ArrayType>(
+    dtype: DType,
+    shape: &[u64],
+    layout: &ArrayLayout,
+    elements: Vec<T>,
+) -> Result<ArrayD<T>, NdarrayError> {
+    let data_len = elements.len() * std::mem::size_of::<T>();
+    let mismatch = || NdarrayError::ShapeMismatch {
+        shape: shape.to_vec(),
+        data_len,
+    };
+    if layout.strides.is_none() && layout.order == Order::C {
+        let dims: Vec<usize> = shape.iter().map(|&d| d as usize).collect();
+        return ArrayD::from_shape_vec(IxDyn(&dims), elements).map_err(|_| mismatch());
+    }
+    let view = stride_shape(dtype, shape, layout)
+        .and_then(|shape| ArrayViewD::from_shape(shape, &elements).ok())
+        .ok_or_else(mismatch)?;
+    Ok(view.as_standard_layout().into_owned())
+}
+
+/// ndarray shape with the element strides of `layout` (None if the layout
+/// does not fit the shape)
+fn stride_shape(dtype: DType, shape: &[u64], layout: &ArrayLayout) -> Option<StrideShape<IxDyn>> {
+    layout.validate(dtype, shape).ok()?;
+    let element_size = dtype.element_size()? as u64;
+    let strides = layout
+        .byte_strides(dtype, shape)?
+        .iter()
+        .map(|&stride| usize::try_from(stride / element_size).ok())
+        .collect::<Option<Vec<_>>>()?;
+    let dims: Vec<usize> = shape.iter().map(|&d| d as usize).collect();
+    Some(IxDyn(&dims).strides(IxDyn(&strides)))
+}
+
+// =============================================================================
+// Widening half-precision and quantized arrays to f32
+// =============================================================================
+
+impl Array {
+    /// Convert an F16, BF16, F32 or quantized array to an f32 ndarray
+    ///
+    /// Half-precision elements are widened, which is exact. Quantized
+    /// elements are dequantized as by `dequantize_f32`.
+    pub fn to_ndarray_f32(&self) -> Result<ArrayD<f32>, NdarrayError> {
+        match self.dtype {
+            DType::F16 => Ok(self.to_ndarray::<f16>()?.mapv(f16::to_f32)),
+            DType::BF16 => Ok(self.to_ndarray::<bf16>()?.mapv(bf16::to_f32)),
+            dtype if dtype.is_quantized() => dequantized(&self.shape, self.dequantize_f32()?),
+            _ => self.to_ndarray(),
+        }
+    }
+}
+
+impl<'a> ArrayView<'a> {
+    /// Convert an F16, BF16, F32 or quantized array to an f32 ndarray
+    ///
+    /// Half-precision elements are widened, which is exact. Quantized
+    /// elements are dequantized as by `dequantize_f32`.
+    pub fn to_ndarray_f32(&self) -> Result<ArrayD<f32>, NdarrayError> {
+        match self.dtype {
+            DType::F16 => Ok(self.to_ndarray::<f16>()?.mapv(f16::to_f32)),
+            DType::BF16 => Ok(self.to_ndarray::<bf16>()?.mapv(bf16::to_f32)),
+            dtype if dtype.is_quantized() => dequantized(&self.shape, self.dequantize_f32()?),
+            _ => self.to_ndarray(),
+        }
+    }
+}
+
+/// Shape dequantized elements as an ndarray
+fn dequantized(shape: &[u64], values: Vec<f32>) -> Result<ArrayD<f32>, NdarrayError> {
+    let dims: Vec<usize> = shape.iter().map(|&d| d as usize).collect();
+    let data_len = values.len() * std::mem::size_of::<f32>();
+    ArrayD::from_shape_vec(IxDyn(&dims), values).map_err(|_| NdarrayError::ShapeMismatch {
+        shape: shape.to_vec(),
+        data_len,
+    })
+}
+
+// =============================================================================
+// Sparse tensors
+// =============================================================================
+
+impl Sparse {
+    /// Store the nonzero elements of an ndarray ArrayD
+    ///
+    /// The array must be contiguous, as for [`Array::from_ndarray`]. CSR
+    /// needs a two-dimensional array.
+    pub fn from_ndarray<T: ArrayType>(
+        arr: ArrayD<T>,
+        format: SparseFormat,
+    ) -> Result<Self, NdarrayError> {
+        let dense = Array::from_ndarray(arr)?;
+        Ok(Sparse::from_dense(&dense, format)?)
+    }
+
+    /// Convert to a dense ndarray ArrayD
+    pub fn to_ndarray<T: ArrayType>(&self) -> Result<ArrayD<T>, NdarrayError> {
+        if T::DTYPE != self.dtype() {
+            return Err(NdarrayError::DTypeMismatch {
+                expected: T::DTYPE,
+                actual: self.dtype(),
+            });
+        }
+        self.to_dense()?.to_ndarray()
+    }
+}
+
+impl<'a> SparseView<'a> {
+    /// Convert to a dense ndarray ArrayD
+    pub fn to_ndarray<T: ArrayType>(&self) -> Result<ArrayD<T>, NdarrayError> {
+        if T::DTYPE != self.dtype() {
+            return Err(NdarrayError::DTypeMismatch {
+                expected: T::DTYPE,
+                actual: self.dtype(),
+            });
+        }
+        self.to_dense()?.to_ndarray()
     }
 }
 
@@ -308,6 +499,110 @@ mod tests {
         assert!(matches!(result, Err(NdarrayError::DTypeMismatch { .. })));
     }
 
+    #[test]
+    fn roundtrip_half_precision() {
+        let arr = array![f16::from_f32(0.5), f16::from_f32(-2.0)].into_dyn();
+        let blob = Array::from_ndarray(arr.clone()).unwrap();
+        assert_eq!(blob.dtype, DType::F16);
+        assert_eq!(blob.data.len(), 4);
+        assert_eq!(blob.to_ndarray::<f16>().unwrap(), arr);
+
+        let arr = array![bf16::from_f32(1.5), bf16::from_f32(-3.0)].into_dyn();
+        let blob = Array::from_ndarray(arr.clone()).unwrap();
+        assert_eq!(blob.dtype, DType::BF16);
+        assert_eq!(blob.to_ndarray::<bf16>().unwrap(), arr);
+    }
+
+    #[test]
+    fn roundtrip_bool_and_complex() {
+        let mask = array![[true, false], [false, true]].into_dyn();
+        let blob = Array::from_ndarray(mask.clone()).unwrap();
+        assert_eq!(blob.dtype, DType::Bool);
+        assert_eq!(blob.data, vec![1, 0, 0, 1]);
+        assert_eq!(blob.to_ndarray::<bool>().unwrap(), mask);
+
+        let bank = array![Complex::new(1.0f32, -1.0), Complex::new(0.5, 2.0)].into_dyn();
+        let blob = Array::from_ndarray(bank.clone()).unwrap();
+        assert_eq!(blob.dtype, DType::C64);
+        assert_eq!(blob.data.len(), 16);
+        assert_eq!(blob.to_ndarray::<Complex<f32>>().unwrap(), bank);
+
+        let bank = array![Complex::new(1.0f64, -1.0)].into_dyn();
+        let blob = Array::from_ndarray(bank.clone()).unwrap();
+        assert_eq!(blob.dtype, DType::C128);
+        assert_eq!(blob.to_ndarray::<Complex<f64>>().unwrap(), bank);
+    }
+
+    #[test]
+    fn invalid_bools_are_not_read() {
+        let blob = Array::new(DType::Bool, vec![2], vec![1, 2]);
+        assert_eq!(blob.to_ndarray::<bool>(), Err(NdarrayError::InvalidBool(2)));
+
+        let view = ArrayView {
+            dtype: DType::Bool,
+            shape: vec![2],
+            data: &[0, 3],
+            checksum: None,
+            compression: None,
+            encryption: None,
+            layout: ArrayLayout::default(),
+        };
+        assert_eq!(
+            view.try_as_ndarray::<bool>().unwrap_err(),
+            NdarrayError::InvalidBool(3)
+        );
+    }
+
+    #[test]
+    fn sparse_roundtrip() {
+        let dense = array![[0.0f32, 1.5, 0.0], [0.0, 0.0, -2.0]].into_dyn();
+        for format in [SparseFormat::Coo, SparseFormat::Csr] {
+            let sparse = Sparse::from_ndarray(dense.clone(), format).unwrap();
+            assert_eq!(sparse.values.shape, vec![2]);
+            assert_eq!(sparse.to_ndarray::<f32>().unwrap(), dense);
+        }
+
+        let sparse = Sparse::from_ndarray(dense, SparseFormat::Coo).unwrap();
+        assert!(matches!(
+            sparse.to_ndarray::<f64>(),
+            Err(NdarrayError::DTypeMismatch {
+                expected: DType::F64,
+                actual: DType::F32
+            })
+        ));
+
+        let cube = ndarray::ArrayD::<u8>::zeros(IxDyn(&[2, 2, 2]));
+        assert!(matches!(
+            Sparse::from_ndarray(cube, SparseFormat::Csr),
+            Err(NdarrayError::Sparse(_))
+        ));
+    }
+
+    #[test]
+    fn widen_to_f32() {
+        let expected = array![0.5f32, -2.0, 65504.0].into_dyn();
+
+        let half = Array::from_ndarray(expected.mapv(f16::from_f32)).unwrap();
+        assert_eq!(half.to_ndarray_f32().unwrap(), expected);
+
+        let brain = Array::from_ndarray(expected.mapv(bf16::from_f32)).unwrap();
+        let widened = brain.to_ndarray_f32().unwrap();
+        assert_eq!(widened[0], 0.5);
+        assert_eq!(widened[1], -2.0);
+
+        let single = Array::from_ndarray(expected.clone()).unwrap();
+        assert_eq!(single.to_ndarray_f32().unwrap(), expected);
+
+        let ints = Array::from_ndarray(array![1i32].into_dyn()).unwrap();
+        assert!(matches!(
+            ints.to_ndarray_f32(),
+            Err(NdarrayError::DTypeMismatch {
+                expected: DType::F32,
+                actual: DType::I32
+            })
+        ));
+    }
+
     #[test]
     fn zero_copy_view_aligned() {
         let arr = array![1.0f64, 2.0, 3.0, 4.0].into_dyn();
@@ -317,6 +612,10 @@ mod tests {
             dtype: blob.dtype,
             shape: blob.shape.clone(),
             data: &blob.data,
+            checksum: None,
+            compression: None,
+            encryption: None,
+            layout: ArrayLayout::default(),
         };
 
         let result = view.try_as_ndarray::<f64>();
@@ -325,6 +624,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn encrypted_data_is_not_viewed() {
+        use crate::types::{Cipher, Encrypted};
+
+        // Ciphertext of the right size, so only the encryption check catches it
+        let data = [0u8; 4];
+        let view = ArrayView {
+            dtype: DType::U8,
+            shape: vec![4],
+            data: &data,
+            checksum: None,
+            compression: None,
+            encryption: Some(Encrypted {
+                cipher: Cipher::Aes256Gcm,
+                key_id: "k".into(),
+                nonce: [0; 12],
+            }),
+            layout: ArrayLayout::default(),
+        };
+        assert_eq!(
+            view.try_as_ndarray::<u8>().unwrap_err(),
+            NdarrayError::Encrypted
+        );
+    }
+
     #[test]
     fn all_dtypes() {
         assert_eq!(
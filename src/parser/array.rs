@@ -1,33 +1,46 @@
 //! Array parsing
 
-use super::primitives::{u8_parser, u64_le};
-use super::take::take;
-use crate::types::{ArrayView, DType};
-use parsicomb::{ByteCursor, CodeLoc, Cursor, Parser, ParsicombError, ntimes};
-use std::borrow::Cow;
+use super::array_layout::parse_array_layout;
+use super::compression::parse_compression;
+use super::encryption::parse_encryption;
+use super::primitives::{u8_parser, u32_le, u64_le};
+use super::storage::{parse_storage, payload_data};
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{
+    ArrayView, DType, Layout, check_data_size, check_filters, invalid_element, raw_size,
+};
+use parsicomb::{ByteCursor, Cursor, Parser, ntimes};
 
 /// Parse an array value (after tag has been consumed)
-pub fn parse_array_body<'a>()
--> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ArrayView<'a>, Error = ParsicombError<'a>> {
-    ArrayBodyParser
+///
+/// The data is expected to start at a multiple of `layout.alignment` bytes,
+/// unless the array refers to an identical earlier payload.
+pub fn parse_array_body<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ArrayView<'a>, Error = ParseError> {
+    ArrayBodyParser { layout }
 }
 
-struct ArrayBodyParser;
+struct ArrayBodyParser {
+    layout: Layout,
+}
 
 impl<'a> Parser<'a> for ArrayBodyParser {
     type Cursor = ByteCursor<'a>;
     type Output = ArrayView<'a>;
-    type Error = ParsicombError<'a>;
+    type Error = ParseError;
 
     fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (_, start) = cursor.inner();
+
         // Parse dtype
         let (dtype_byte, cursor) = u8_parser().parse(cursor)?;
         let dtype = DType::from_u8(dtype_byte).ok_or_else(|| {
-            let (data, pos) = cursor.inner();
-            ParsicombError::SyntaxError {
-                message: Cow::Owned(format!("Invalid dtype: 0x{:02X}", dtype_byte)),
-                loc: CodeLoc::new(data, pos.saturating_sub(1)),
-            }
+            let (_, pos) = cursor.inner();
+            ParseError::new(
+                BlobfigError::InvalidDType(dtype_byte),
+                pos.saturating_sub(1),
+            )
         })?;
 
         // Parse ndim
@@ -36,13 +49,65 @@ impl<'a> Parser<'a> for ArrayBodyParser {
         // Parse shape (ndim u64 values)
         let (shape, cursor) = ntimes(ndim as usize, u64_le()).parse(cursor)?;
 
-        // Parse data size
+        // Parse element order, strides and axis names
+        let (_, layout_start) = cursor.inner();
+        let (array_layout, cursor) = parse_array_layout(self.layout, shape.len()).parse(cursor)?;
+
+        // Parse codec, cipher and storage fields, then the stored data size
+        let (_, compression_start) = cursor.inner();
+        let (compression, cursor) = parse_compression(self.layout).parse(cursor)?;
+        if let Some(compressed) = compression {
+            check_filters(compressed.filters, dtype.element_size())
+                .map_err(|e| ParseError::new(e, compression_start + 1))?;
+        }
+        let (encryption, cursor) = parse_encryption(self.layout).parse(cursor)?;
+        let (reference, cursor) = parse_storage(self.layout).parse(cursor)?;
         let (data_size, cursor) = u64_le().parse(cursor)?;
 
-        // Take data bytes (zero-copy)
-        let (data, cursor) = take(data_size as usize).parse(cursor)?;
+        // Check the (decrypted and decompressed) size against shape and dtype
+        let size = raw_size(data_size, compression, &encryption);
+        check_data_size(dtype, &shape, size).map_err(|e| {
+            let (_, pos) = cursor.inner();
+            ParseError::new(e, pos - 8)
+        })?;
+        array_layout
+            .validate(dtype, &shape)
+            .map_err(|e| ParseError::new(e, layout_start))?;
 
-        Ok((ArrayView { dtype, shape, data }, cursor))
+        // Take the aligned or referenced data bytes (zero-copy)
+        let (data, cursor) =
+            payload_data(self.layout, reference, data_size, start).parse(cursor)?;
+
+        // Uncompressed Bool elements must be 0 or 1
+        if compression.is_none()
+            && encryption.is_none()
+            && let Some(i) = invalid_element(dtype, data)
+        {
+            let (bytes, _) = cursor.inner();
+            let offset = data.as_ptr() as usize - bytes.as_ptr() as usize + i;
+            return Err(ParseError::new(BlobfigError::InvalidBool(data[i]), offset));
+        }
+
+        // Checksum following the data
+        let (checksum, cursor) = if self.layout.checksums {
+            let (checksum, cursor) = u32_le().parse(cursor)?;
+            (Some(checksum), cursor)
+        } else {
+            (None, cursor)
+        };
+
+        Ok((
+            ArrayView {
+                dtype,
+                shape,
+                data,
+                checksum,
+                compression,
+                encryption,
+                layout: array_layout,
+            },
+            cursor,
+        ))
     }
 }
 
@@ -68,7 +133,7 @@ mod tests {
         let bytes = make_array_bytes(DType::U8, &[4], &data);
         let cursor = ByteCursor::new(&bytes);
 
-        let (arr, _) = parse_array_body().parse(cursor).unwrap();
+        let (arr, _) = parse_array_body(Layout::UNALIGNED).parse(cursor).unwrap();
         assert_eq!(arr.dtype, DType::U8);
         assert_eq!(arr.shape, vec![4]);
         assert_eq!(arr.data, &[1, 2, 3, 4]);
@@ -80,7 +145,7 @@ mod tests {
         let bytes = make_array_bytes(DType::F32, &[2, 3], &data);
         let cursor = ByteCursor::new(&bytes);
 
-        let (arr, _) = parse_array_body().parse(cursor).unwrap();
+        let (arr, _) = parse_array_body(Layout::UNALIGNED).parse(cursor).unwrap();
         assert_eq!(arr.dtype, DType::F32);
         assert_eq!(arr.shape, vec![2, 3]);
         assert_eq!(arr.data.len(), 24);
@@ -92,7 +157,7 @@ mod tests {
         let bytes = make_array_bytes(DType::U8, &[4], &data);
         let cursor = ByteCursor::new(&bytes);
 
-        let (arr, _) = parse_array_body().parse(cursor).unwrap();
+        let (arr, _) = parse_array_body(Layout::UNALIGNED).parse(cursor).unwrap();
 
         // Verify data points into original bytes
         let data_offset = 1 + 1 + 8 + 8; // dtype + ndim + shape + data_len
@@ -102,6 +167,49 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_parse_array_aligned() {
+        let mut bytes = vec![DType::U8 as u8, 1];
+        bytes.extend_from_slice(&4u64.to_le_bytes()); // shape
+        bytes.extend_from_slice(&4u64.to_le_bytes()); // data_len
+        bytes.resize(32, 0); // padding up to the 32-byte boundary
+        bytes.extend_from_slice(&[1, 2, 3, 4]);
+        let cursor = ByteCursor::new(&bytes);
+
+        let (arr, _) = parse_array_body(Layout {
+            alignment: 32,
+            ..Layout::UNALIGNED
+        })
+        .parse(cursor)
+        .unwrap();
+        assert_eq!(arr.data, &[1, 2, 3, 4]);
+        assert!(std::ptr::eq(arr.data.as_ptr(), bytes[32..].as_ptr()));
+    }
+
+    #[test]
+    fn test_parse_array_half_precision() {
+        for dtype in [DType::F16, DType::BF16] {
+            let bytes = make_array_bytes(dtype, &[3], &[0u8; 6]);
+            let cursor = ByteCursor::new(&bytes);
+
+            let (arr, _) = parse_array_body(Layout::UNALIGNED).parse(cursor).unwrap();
+            assert_eq!(arr.dtype, dtype);
+            assert_eq!(arr.data.len(), 6);
+        }
+    }
+
+    #[test]
+    fn test_invalid_bool_element() {
+        let bytes = make_array_bytes(DType::Bool, &[3], &[1, 0, 7]);
+        let cursor = ByteCursor::new(&bytes);
+
+        let err = parse_array_body(Layout::UNALIGNED)
+            .parse(cursor)
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidBool(7));
+        assert_eq!(err.offset, bytes.len() - 1);
+    }
+
     #[test]
     fn test_invalid_dtype() {
         let mut bytes = vec![0xFF]; // Invalid dtype
@@ -110,7 +218,70 @@ mod tests {
         bytes.extend_from_slice(&0u64.to_le_bytes()); // data_len
 
         let cursor = ByteCursor::new(&bytes);
-        let result = parse_array_body().parse(cursor);
+        let result = parse_array_body(Layout::UNALIGNED).parse(cursor);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_data_size_mismatch() {
+        let bytes = make_array_bytes(DType::F32, &[2, 3], &[0u8; 20]);
+        let cursor = ByteCursor::new(&bytes);
+
+        let err = parse_array_body(Layout::UNALIGNED)
+            .parse(cursor)
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            BlobfigError::DataSizeMismatch {
+                expected: 24,
+                actual: 20
+            }
+        );
+        assert_eq!(err.offset, 1 + 1 + 16);
+    }
+
+    #[test]
+    fn test_parse_array_compressed_header() {
+        let mut bytes = vec![DType::F32 as u8, 1];
+        bytes.extend_from_slice(&4u64.to_le_bytes()); // shape
+        bytes.push(0x02); // lz4
+        bytes.push(0x00); // filters
+        bytes.extend_from_slice(&16u64.to_le_bytes()); // raw size
+        bytes.extend_from_slice(&3u64.to_le_bytes()); // stored size
+        bytes.extend_from_slice(&[1, 2, 3]);
+        let cursor = ByteCursor::new(&bytes);
+
+        let layout = Layout {
+            compression: true,
+            ..Layout::UNALIGNED
+        };
+        let (arr, _) = parse_array_body(layout).parse(cursor).unwrap();
+        assert_eq!(arr.data, &[1, 2, 3]);
+        assert_eq!(arr.compression.unwrap().raw_size, 16);
+
+        // The raw size, not the stored size, must match the shape
+        bytes[12..20].copy_from_slice(&12u64.to_le_bytes());
+        let err = parse_array_body(layout)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            BlobfigError::DataSizeMismatch {
+                expected: 16,
+                actual: 12
+            }
+        );
+        assert_eq!(err.offset, 20);
+    }
+
+    #[test]
+    fn test_shape_overflow() {
+        let bytes = make_array_bytes(DType::U8, &[1 << 32, 1 << 32], &[]);
+        let cursor = ByteCursor::new(&bytes);
+
+        let err = parse_array_body(Layout::UNALIGNED)
+            .parse(cursor)
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::ShapeOverflow);
+    }
 }
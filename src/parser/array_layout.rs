@@ -0,0 +1,122 @@
+//! Array layout parsing
+
+use super::primitives::{u8_parser, u64_le};
+use super::take::take;
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{ArrayLayout, LAYOUT_AXIS_NAMES, LAYOUT_FORTRAN, LAYOUT_STRIDES, Layout, Order};
+use parsicomb::{ByteCursor, Cursor, Parser, ntimes};
+
+/// Parse the layout fields following the shape of an `ndim` Array
+///
+/// Returns the default layout when the file layout has no array layout
+/// fields. The strides and names are not checked against the shape.
+pub fn parse_array_layout<'a>(
+    layout: Layout,
+    ndim: usize,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ArrayLayout, Error = ParseError> {
+    ArrayLayoutParser { layout, ndim }
+}
+
+struct ArrayLayoutParser {
+    layout: Layout,
+    ndim: usize,
+}
+
+impl<'a> Parser<'a> for ArrayLayoutParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = ArrayLayout;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        if !self.layout.array_layout {
+            return Ok((ArrayLayout::default(), cursor));
+        }
+
+        let (_, start) = cursor.inner();
+        let (flags, mut cursor) = u8_parser().parse(cursor)?;
+        if flags & !(LAYOUT_FORTRAN | LAYOUT_STRIDES | LAYOUT_AXIS_NAMES) != 0 {
+            return Err(ParseError::new(
+                BlobfigError::InvalidArrayLayoutFlags(flags),
+                start,
+            ));
+        }
+        let order = match flags & LAYOUT_FORTRAN {
+            0 => Order::C,
+            _ => Order::F,
+        };
+
+        let mut strides = None;
+        if flags & LAYOUT_STRIDES != 0 {
+            let (parsed, next) = ntimes(self.ndim, u64_le()).parse(cursor)?;
+            strides = Some(parsed);
+            cursor = next;
+        }
+
+        let mut axis_names = None;
+        if flags & LAYOUT_AXIS_NAMES != 0 {
+            let mut names = Vec::with_capacity(self.ndim);
+            for _ in 0..self.ndim {
+                let (len, next) = u8_parser().parse(cursor)?;
+                let (_, name_start) = next.inner();
+                let (bytes, next) = take(len as usize).parse(next)?;
+                let name = std::str::from_utf8(bytes)
+                    .map_err(|_| ParseError::new(BlobfigError::InvalidUtf8, name_start))?;
+                names.push(name.to_string());
+                cursor = next;
+            }
+            axis_names = Some(names);
+        }
+
+        let array_layout = ArrayLayout {
+            order,
+            strides,
+            axis_names,
+        };
+        Ok((array_layout, cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WITH_LAYOUT: Layout = Layout {
+        array_layout: true,
+        ..Layout::UNALIGNED
+    };
+
+    #[test]
+    fn absent_without_flag() {
+        let bytes = [LAYOUT_FORTRAN];
+        let (array_layout, cursor) = parse_array_layout(Layout::UNALIGNED, 2)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert!(array_layout.is_default());
+        assert!(!cursor.eos());
+    }
+
+    #[test]
+    fn strides_and_names() {
+        let mut bytes = vec![LAYOUT_FORTRAN | LAYOUT_STRIDES | LAYOUT_AXIS_NAMES];
+        bytes.extend(4u64.to_le_bytes());
+        bytes.extend(8u64.to_le_bytes());
+        bytes.extend(b"\x04time\x03mel");
+        let (array_layout, cursor) = parse_array_layout(WITH_LAYOUT, 2)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert!(cursor.eos());
+        assert_eq!(array_layout.order, Order::F);
+        assert_eq!(array_layout.strides, Some(vec![4, 8]));
+        assert_eq!(array_layout.axis_index("mel"), Some(1));
+    }
+
+    #[test]
+    fn unknown_flags() {
+        let bytes = [0x80];
+        let err = parse_array_layout(WITH_LAYOUT, 0)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidArrayLayoutFlags(0x80));
+        assert_eq!(err.offset, 0);
+    }
+}
@@ -0,0 +1,258 @@
+//! Chunked array parsing
+
+use super::array::parse_array_body;
+use super::primitives::{u8_parser, u64_le};
+use super::take::{cursor_at, take};
+use super::value::parse_tag;
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{
+    ArrayView, ChunkedArrayView, DType, Layout, ValueTag, check_chunk, check_grid, grid_shape,
+    num_chunks, unravel,
+};
+use parsicomb::{ByteCursor, Cursor, Parser, ntimes};
+
+/// Parse a chunked array (after tag has been consumed)
+///
+/// Layout: dtype byte, ndim byte, shape and chunk shape (ndim u64 values
+/// each), a table of one u64 offset per chunk counted from the end of the
+/// table, then the chunks as Array values in row-major grid order. Every
+/// chunk header is read and checked against the grid and the table.
+pub fn parse_chunked_body<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ChunkedArrayView<'a>, Error = ParseError> {
+    ChunkedBodyParser { layout }
+}
+
+/// Advance past a chunked array (after tag has been consumed)
+///
+/// Jumps to the last chunk through the offset table, without reading the
+/// other chunks.
+pub(super) fn skip_chunked_body<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = (), Error = ParseError> {
+    SkipChunkedBody { layout }
+}
+
+/// Dtype, shape and chunk shape of a chunked array
+struct Grid {
+    dtype: DType,
+    shape: Vec<u64>,
+    chunk_shape: Vec<u64>,
+}
+
+impl Grid {
+    fn parse(cursor: ByteCursor<'_>) -> Result<(Self, ByteCursor<'_>), ParseError> {
+        let (_, start) = cursor.inner();
+        let (dtype_byte, cursor) = u8_parser().parse(cursor)?;
+        let dtype = DType::from_u8(dtype_byte)
+            .ok_or_else(|| ParseError::new(BlobfigError::InvalidDType(dtype_byte), start))?;
+        let (ndim, cursor) = u8_parser().parse(cursor)?;
+        let (shape, cursor) = ntimes(ndim as usize, u64_le()).parse(cursor)?;
+        let (chunk_shape, cursor) = ntimes(ndim as usize, u64_le()).parse(cursor)?;
+        check_grid(&shape, &chunk_shape).map_err(|e| ParseError::new(e, start))?;
+        let grid = Grid {
+            dtype,
+            shape,
+            chunk_shape,
+        };
+        Ok((grid, cursor))
+    }
+
+    /// Chunk count and the offset table, which follows the grid
+    fn table<'a>(
+        &self,
+        cursor: ByteCursor<'a>,
+    ) -> Result<(u64, &'a [u8], ByteCursor<'a>), ParseError> {
+        let count = num_chunks(&grid_shape(&self.shape, &self.chunk_shape));
+        let len = usize::try_from(count.saturating_mul(8)).unwrap_or(usize::MAX);
+        let (table, cursor) = take(len).parse(cursor)?;
+        Ok((count, table, cursor))
+    }
+}
+
+/// Offset of chunk `i` from the table
+fn table_entry(table: &[u8], i: usize) -> u64 {
+    u64::from_le_bytes(table[i * 8..i * 8 + 8].try_into().unwrap())
+}
+
+/// Parse one chunk, which must be an Array
+fn parse_chunk(
+    layout: Layout,
+    cursor: ByteCursor<'_>,
+) -> Result<(ArrayView<'_>, ByteCursor<'_>), ParseError> {
+    let (_, start) = cursor.inner();
+    let (tag, cursor) = parse_tag().parse(cursor)?;
+    if tag != ValueTag::Array {
+        let reason = "chunks must be Arrays";
+        return Err(ParseError::new(
+            BlobfigError::InvalidChunkedArray(reason),
+            start,
+        ));
+    }
+    parse_array_body(layout).parse(cursor)
+}
+
+struct ChunkedBodyParser {
+    layout: Layout,
+}
+
+impl<'a> Parser<'a> for ChunkedBodyParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = ChunkedArrayView<'a>;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (grid, cursor) = Grid::parse(cursor)?;
+        let (_, table_start) = cursor.inner();
+        let (count, table, mut cursor) = grid.table(cursor)?;
+        let (_, chunks_start) = cursor.inner();
+
+        let grid_dims = grid_shape(&grid.shape, &grid.chunk_shape);
+        let mut chunks = Vec::new();
+        for flat in 0..count {
+            let (_, start) = cursor.inner();
+            let entry = table_entry(table, flat as usize);
+            if entry != (start - chunks_start) as u64 {
+                let reason = "chunk offset table does not match the chunks";
+                let offset = table_start + flat as usize * 8;
+                return Err(ParseError::new(
+                    BlobfigError::InvalidChunkedArray(reason),
+                    offset,
+                ));
+            }
+
+            let (chunk, next) = parse_chunk(self.layout, cursor)?;
+            let index = unravel(&grid_dims, flat);
+            let layout = (chunk.dtype, chunk.shape.as_slice(), &chunk.layout);
+            check_chunk(grid.dtype, &grid.shape, &grid.chunk_shape, &index, layout)
+                .map_err(|e| ParseError::new(e, start))?;
+            chunks.push(chunk);
+            cursor = next;
+        }
+
+        let view = ChunkedArrayView::new(grid.dtype, grid.shape, grid.chunk_shape, chunks);
+        Ok((view, cursor))
+    }
+}
+
+struct SkipChunkedBody {
+    layout: Layout,
+}
+
+impl<'a> Parser<'a> for SkipChunkedBody {
+    type Cursor = ByteCursor<'a>;
+    type Output = ();
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (grid, cursor) = Grid::parse(cursor)?;
+        let (_, table_start) = cursor.inner();
+        let (count, table, cursor) = grid.table(cursor)?;
+        let Some(last) = count.checked_sub(1) else {
+            return Ok(((), cursor));
+        };
+
+        let (data, chunks_start) = cursor.inner();
+        let entry = table_entry(table, last as usize);
+        let Some(at) = usize::try_from(entry)
+            .ok()
+            .and_then(|entry| chunks_start.checked_add(entry))
+            .filter(|&at| at <= data.len())
+        else {
+            let reason = "chunk offset out of bounds";
+            let offset = table_start + last as usize * 8;
+            return Err(ParseError::new(
+                BlobfigError::InvalidChunkedArray(reason),
+                offset,
+            ));
+        };
+        let (_, cursor) = parse_chunk(self.layout, cursor_at(data, at))?;
+        Ok(((), cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Array, ChunkedArray};
+    use crate::writer::{self, WriteOptions};
+
+    fn chunked_bytes() -> Vec<u8> {
+        // 3x2 u8 array in 2x2 chunks: [[0, 1], [2, 3]] and [[4, 5]]
+        let dense = Array::new(DType::U8, vec![3, 2], (0..6).collect());
+        let chunked = ChunkedArray::from_array(&dense, vec![2, 2]).unwrap();
+        let bytes = writer::to_bytes_with(chunked.into(), WriteOptions::unaligned()).unwrap();
+        bytes[crate::types::HEADER_SIZE + 1..].to_vec()
+    }
+
+    /// Offset of the offset table: dtype, ndim, shape and chunk shape
+    const TABLE: usize = 1 + 1 + 16 + 16;
+
+    #[test]
+    fn test_parse_chunked() {
+        let bytes = chunked_bytes();
+        let (view, cursor) = parse_chunked_body(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert!(cursor.eos());
+        assert_eq!(view.grid(), vec![2, 1]);
+        assert_eq!(view.chunk(&[0, 0]).unwrap().data, &[0, 1, 2, 3]);
+        assert_eq!(view.chunk(&[1, 0]).unwrap().shape, vec![1, 2]);
+        assert_eq!(view.chunk(&[1, 0]).unwrap().data, &[4, 5]);
+
+        let (_, cursor) = skip_chunked_body(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert!(cursor.eos());
+    }
+
+    #[test]
+    fn test_table_must_match_chunks() {
+        let mut bytes = chunked_bytes();
+        bytes[TABLE + 8] += 1;
+        let err = parse_chunked_body(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            BlobfigError::InvalidChunkedArray("chunk offset table does not match the chunks")
+        );
+        assert_eq!(err.offset, TABLE + 8);
+    }
+
+    #[test]
+    fn test_chunk_shape_must_match_grid() {
+        let mut bytes = chunked_bytes();
+        // The second chunk's first dimension, after its tag, dtype and ndim
+        let second = TABLE + 16 + table_entry(&bytes[TABLE..], 1) as usize;
+        bytes[second + 3] = 2;
+        let err = parse_chunked_body(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert!(matches!(err.kind, BlobfigError::DataSizeMismatch { .. }));
+
+        bytes[second + 3] = 1;
+        bytes[second + 1] = DType::I8 as u8;
+        let err = parse_chunked_body(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            BlobfigError::InvalidChunkedArray("chunk dtype differs from the array's")
+        );
+        assert_eq!(err.offset, second);
+    }
+
+    #[test]
+    fn test_zero_chunk_dimension() {
+        let mut bytes = chunked_bytes();
+        bytes[1 + 1 + 16..1 + 1 + 24].copy_from_slice(&0u64.to_le_bytes());
+        let err = parse_chunked_body(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            BlobfigError::InvalidChunkedArray("chunk dimensions must be nonzero")
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Compression header parsing
+
+use super::primitives::{u8_parser, u64_le};
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{Compressed, Compression, Filters, Layout};
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Parse the codec fields preceding an Array or File data size
+///
+/// Returns None for uncompressed payloads and when the layout has no codec
+/// fields at all.
+pub fn parse_compression<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = Option<Compressed>, Error = ParseError> {
+    CompressionParser { layout }
+}
+
+struct CompressionParser {
+    layout: Layout,
+}
+
+impl<'a> Parser<'a> for CompressionParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = Option<Compressed>;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        if !self.layout.compression {
+            return Ok((None, cursor));
+        }
+
+        let (codec_byte, cursor) = u8_parser().parse(cursor)?;
+        if codec_byte == 0 {
+            return Ok((None, cursor));
+        }
+        let codec = Compression::from_u8(codec_byte).ok_or_else(|| {
+            let (_, pos) = cursor.inner();
+            ParseError::new(
+                BlobfigError::InvalidCompression(codec_byte),
+                pos.saturating_sub(1),
+            )
+        })?;
+
+        let (filter_byte, cursor) = u8_parser().parse(cursor)?;
+        let filters = Filters::from_u8(filter_byte).ok_or_else(|| {
+            let (_, pos) = cursor.inner();
+            ParseError::new(
+                BlobfigError::InvalidFilters(filter_byte),
+                pos.saturating_sub(1),
+            )
+        })?;
+
+        let (raw_size, cursor) = u64_le().parse(cursor)?;
+        Ok((
+            Some(Compressed {
+                codec,
+                filters,
+                raw_size,
+            }),
+            cursor,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPRESSED: Layout = Layout {
+        compression: true,
+        ..Layout::UNALIGNED
+    };
+
+    #[test]
+    fn absent_without_flag() {
+        let bytes = [0x01];
+        let (compression, cursor) = parse_compression(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert_eq!(compression, None);
+        assert_eq!(cursor.inner().1, 0);
+    }
+
+    #[test]
+    fn raw_and_compressed() {
+        let (compression, _) = parse_compression(COMPRESSED)
+            .parse(ByteCursor::new(&[0x00]))
+            .unwrap();
+        assert_eq!(compression, None);
+
+        let mut bytes = vec![Compression::Lz4 as u8, Filters::SHUFFLE.bits()];
+        bytes.extend_from_slice(&100u64.to_le_bytes());
+        let (compression, _) = parse_compression(COMPRESSED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert_eq!(
+            compression,
+            Some(Compressed {
+                codec: Compression::Lz4,
+                filters: Filters::SHUFFLE,
+                raw_size: 100
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_codec() {
+        let err = parse_compression(COMPRESSED)
+            .parse(ByteCursor::new(&[0x7F]))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidCompression(0x7F));
+        assert_eq!(err.offset, 0);
+
+        let err = parse_compression(COMPRESSED)
+            .parse(ByteCursor::new(&[0x01, 0x80]))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidFilters(0x80));
+        assert_eq!(err.offset, 1);
+    }
+}
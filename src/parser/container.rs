@@ -0,0 +1,142 @@
+//! Object/List header parsing
+
+use super::primitives::{u32_le, u64_le};
+use crate::error::{BlobfigError, ParseError};
+use crate::types::Layout;
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Decoded Object/List header (after tag has been consumed)
+#[derive(Debug, Clone, Copy)]
+pub struct ContainerHeader {
+    /// Number of entries (Object) or items (List)
+    pub len: u32,
+    /// Offset of the body, just past the length prefix
+    pub start: usize,
+    /// Offset just past the container body, if the layout records it
+    pub end: Option<usize>,
+}
+
+impl ContainerHeader {
+    /// Check that the body ended exactly where the length prefix said it would
+    pub fn check_end(&self, cursor: &ByteCursor<'_>) -> Result<(), ParseError> {
+        let (_, pos) = cursor.inner();
+        match self.end {
+            Some(end) if end != pos => {
+                let declared = (end - self.start) as u64;
+                let actual = (pos - self.start) as u64;
+                Err(ParseError::new(
+                    BlobfigError::InvalidContainerLength { declared, actual },
+                    pos,
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parse an Object/List header: `[u64 body length (v2+)] u32 count`
+pub fn parse_container_header<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ContainerHeader, Error = ParseError> {
+    ContainerHeaderParser { layout }
+}
+
+struct ContainerHeaderParser {
+    layout: Layout,
+}
+
+impl<'a> Parser<'a> for ContainerHeaderParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = ContainerHeader;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        if !self.layout.has_container_lengths() {
+            let (_, start) = cursor.inner();
+            let (len, cursor) = u32_le().parse(cursor)?;
+            let header = ContainerHeader {
+                len,
+                start,
+                end: None,
+            };
+            return Ok((header, cursor));
+        }
+
+        let (body_len, cursor) = u64_le().parse(cursor)?;
+        let (data, start) = cursor.inner();
+        let end = usize::try_from(body_len)
+            .ok()
+            .and_then(|len| start.checked_add(len))
+            .filter(|&end| end <= data.len())
+            .ok_or_else(|| ParseError::new(BlobfigError::ContainerPastEnd(body_len), start - 8))?;
+
+        let (len, cursor) = u32_le().parse(cursor)?;
+        Ok((
+            ContainerHeader {
+                len,
+                start,
+                end: Some(end),
+            },
+            cursor,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_header() {
+        let bytes = 3u32.to_le_bytes();
+        let (header, _) = parse_container_header(Layout::V1)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert_eq!(header.len, 3);
+        assert_eq!(header.end, None);
+    }
+
+    #[test]
+    fn test_v2_header() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&6u64.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        let (header, _) = parse_container_header(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert_eq!(header.len, 1);
+        assert_eq!(header.end, Some(14));
+    }
+
+    #[test]
+    fn test_v2_length_past_end() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&100u64.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        let result = parse_container_header(Layout::UNALIGNED).parse(ByteCursor::new(&bytes));
+        let err = result.err().unwrap();
+        assert_eq!(err.kind, BlobfigError::ContainerPastEnd(100));
+        assert_eq!(err.offset, 0);
+    }
+
+    #[test]
+    fn test_v2_body_shorter_than_prefix() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&6u64.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        let (header, cursor) = parse_container_header(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        let err = header.check_end(&cursor).unwrap_err();
+        assert_eq!(
+            err.kind,
+            BlobfigError::InvalidContainerLength {
+                declared: 6,
+                actual: 4
+            }
+        );
+        assert_eq!(err.offset, 12);
+    }
+}
@@ -0,0 +1,108 @@
+//! Encryption header parsing
+
+use super::primitives::u8_parser;
+use super::take::take;
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{Cipher, Encrypted, Layout, NONCE_SIZE};
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Parse the cipher fields preceding an Array or File data size
+///
+/// Returns None for plaintext payloads and when the layout has no cipher
+/// fields at all.
+pub fn parse_encryption<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = Option<Encrypted>, Error = ParseError> {
+    EncryptionParser { layout }
+}
+
+struct EncryptionParser {
+    layout: Layout,
+}
+
+impl<'a> Parser<'a> for EncryptionParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = Option<Encrypted>;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        if !self.layout.encryption {
+            return Ok((None, cursor));
+        }
+
+        let (cipher_byte, cursor) = u8_parser().parse(cursor)?;
+        if cipher_byte == 0 {
+            return Ok((None, cursor));
+        }
+        let cipher = Cipher::from_u8(cipher_byte).ok_or_else(|| {
+            let (_, pos) = cursor.inner();
+            ParseError::new(
+                BlobfigError::InvalidCipher(cipher_byte),
+                pos.saturating_sub(1),
+            )
+        })?;
+
+        let (key_id_len, cursor) = u8_parser().parse(cursor)?;
+        let (key_id_bytes, cursor) = take(key_id_len as usize).parse(cursor)?;
+        let key_id = std::str::from_utf8(key_id_bytes).map_err(|_| {
+            let (_, pos) = cursor.inner();
+            ParseError::new(
+                BlobfigError::InvalidUtf8,
+                pos.saturating_sub(key_id_len as usize),
+            )
+        })?;
+
+        let (nonce, cursor) = take(NONCE_SIZE).parse(cursor)?;
+        Ok((
+            Some(Encrypted {
+                cipher,
+                key_id: key_id.to_string(),
+                nonce: nonce.try_into().unwrap(),
+            }),
+            cursor,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCRYPTED: Layout = Layout {
+        encryption: true,
+        ..Layout::UNALIGNED
+    };
+
+    #[test]
+    fn plaintext_and_encrypted() {
+        let (encryption, _) = parse_encryption(ENCRYPTED)
+            .parse(ByteCursor::new(&[0x00]))
+            .unwrap();
+        assert_eq!(encryption, None);
+
+        let mut bytes = vec![Cipher::ChaCha20Poly1305 as u8, 4];
+        bytes.extend_from_slice(b"prod");
+        bytes.extend_from_slice(&[9u8; NONCE_SIZE]);
+        let (encryption, cursor) = parse_encryption(ENCRYPTED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert_eq!(
+            encryption,
+            Some(Encrypted {
+                cipher: Cipher::ChaCha20Poly1305,
+                key_id: "prod".into(),
+                nonce: [9u8; NONCE_SIZE],
+            })
+        );
+        assert!(matches!(cursor, ByteCursor::EndOfFile { .. }));
+    }
+
+    #[test]
+    fn invalid_cipher() {
+        let err = parse_encryption(ENCRYPTED)
+            .parse(ByteCursor::new(&[0x7F]))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidCipher(0x7F));
+        assert_eq!(err.offset, 0);
+    }
+}
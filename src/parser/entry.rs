@@ -1,30 +1,40 @@
 //! Object entry parsing
 
+use super::options::ParseContext;
 use super::string::parse_key;
-use super::value::parse_value;
+use super::value::ValueParser;
+use crate::error::ParseError;
 use crate::types::ValueView;
-use parsicomb::{ByteCursor, Parser, ParsicombError};
-
-/// Parse a single object entry (key + value)
-pub fn parse_entry<'a>() -> impl Parser<
-    'a,
-    Cursor = ByteCursor<'a>,
-    Output = (&'a str, ValueView<'a>),
-    Error = ParsicombError<'a>,
-> {
-    EntryParser
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Parse a single object entry (key + value) at nesting `depth`
+///
+/// Errors in the value are reported under the entry's key.
+pub(super) fn parse_entry<'a, 'c>(
+    ctx: &'c ParseContext,
+    depth: usize,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = (&'a str, ValueView<'a>), Error = ParseError> + 'c
+{
+    EntryParser { ctx, depth }
 }
 
-struct EntryParser;
+struct EntryParser<'c> {
+    ctx: &'c ParseContext,
+    depth: usize,
+}
 
-impl<'a> Parser<'a> for EntryParser {
+impl<'a> Parser<'a> for EntryParser<'_> {
     type Cursor = ByteCursor<'a>;
     type Output = (&'a str, ValueView<'a>);
-    type Error = ParsicombError<'a>;
+    type Error = ParseError;
 
     fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (_, start) = cursor.inner();
         let (key, cursor) = parse_key().parse(cursor)?;
-        let (value, cursor) = parse_value().parse(cursor)?;
+        self.ctx.check_string_len(key.len(), start)?;
+        let (value, cursor) = ValueParser::new(self.ctx, self.depth)
+            .parse(cursor)
+            .map_err(|e| e.within(key))?;
         Ok(((key, value), cursor))
     }
 }
@@ -32,7 +42,7 @@ impl<'a> Parser<'a> for EntryParser {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::ValueTag;
+    use crate::types::{Layout, ValueTag};
 
     #[test]
     fn test_parse_entry() {
@@ -45,7 +55,8 @@ mod tests {
         bytes.extend_from_slice(&42i64.to_le_bytes());
 
         let cursor = ByteCursor::new(&bytes);
-        let ((key, value), _) = parse_entry().parse(cursor).unwrap();
+        let ctx = ParseContext::new(Layout::V1, Default::default());
+        let ((key, value), _) = parse_entry(&ctx, 1).parse(cursor).unwrap();
         assert_eq!(key, "count");
         assert_eq!(value.as_int(), Some(42));
     }
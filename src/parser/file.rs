@@ -1,45 +1,77 @@
 //! File blob parsing
 
-use super::primitives::{u16_le, u64_le};
+use super::compression::parse_compression;
+use super::encryption::parse_encryption;
+use super::primitives::{u16_le, u32_le, u64_le};
+use super::storage::{parse_storage, payload_data};
 use super::take::take;
-use crate::types::FileView;
-use parsicomb::{ByteCursor, CodeLoc, Cursor, Parser, ParsicombError};
-use std::borrow::Cow;
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{FileView, Layout};
+use parsicomb::{ByteCursor, Cursor, Parser};
 
 /// Parse a file value (after tag has been consumed)
-pub fn parse_file_body<'a>()
--> impl Parser<'a, Cursor = ByteCursor<'a>, Output = FileView<'a>, Error = ParsicombError<'a>> {
-    FileBodyParser
+///
+/// The data is expected to start at a multiple of `layout.alignment` bytes,
+/// unless the file refers to an identical earlier payload.
+pub fn parse_file_body<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = FileView<'a>, Error = ParseError> {
+    FileBodyParser { layout }
 }
 
-struct FileBodyParser;
+struct FileBodyParser {
+    layout: Layout,
+}
 
 impl<'a> Parser<'a> for FileBodyParser {
     type Cursor = ByteCursor<'a>;
     type Output = FileView<'a>;
-    type Error = ParsicombError<'a>;
+    type Error = ParseError;
 
     fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (_, start) = cursor.inner();
+
         // Parse mimetype length
         let (mimetype_len, cursor) = u16_le().parse(cursor)?;
 
         // Take mimetype bytes and convert to str
         let (mimetype_bytes, cursor) = take(mimetype_len as usize).parse(cursor)?;
         let mimetype = std::str::from_utf8(mimetype_bytes).map_err(|_| {
-            let (data, pos) = cursor.inner();
-            ParsicombError::SyntaxError {
-                message: Cow::Borrowed("Invalid UTF-8 in mimetype"),
-                loc: CodeLoc::new(data, pos.saturating_sub(mimetype_len as usize)),
-            }
+            let (_, pos) = cursor.inner();
+            ParseError::new(
+                BlobfigError::InvalidUtf8,
+                pos.saturating_sub(mimetype_len as usize),
+            )
         })?;
 
-        // Parse data size
+        // Parse codec, cipher and storage fields, then the stored data size
+        let (compression, cursor) = parse_compression(self.layout).parse(cursor)?;
+        let (encryption, cursor) = parse_encryption(self.layout).parse(cursor)?;
+        let (reference, cursor) = parse_storage(self.layout).parse(cursor)?;
         let (data_size, cursor) = u64_le().parse(cursor)?;
 
-        // Take data bytes (zero-copy)
-        let (data, cursor) = take(data_size as usize).parse(cursor)?;
-
-        Ok((FileView { mimetype, data }, cursor))
+        // Take the aligned or referenced data bytes (zero-copy)
+        let (data, cursor) =
+            payload_data(self.layout, reference, data_size, start).parse(cursor)?;
+
+        // Checksum following the data
+        let (checksum, cursor) = if self.layout.checksums {
+            let (checksum, cursor) = u32_le().parse(cursor)?;
+            (Some(checksum), cursor)
+        } else {
+            (None, cursor)
+        };
+
+        Ok((
+            FileView {
+                mimetype,
+                data,
+                checksum,
+                compression,
+                encryption,
+            },
+            cursor,
+        ))
     }
 }
 
@@ -62,7 +94,7 @@ mod tests {
         let bytes = make_file_bytes("text/plain", data);
         let cursor = ByteCursor::new(&bytes);
 
-        let (file, _) = parse_file_body().parse(cursor).unwrap();
+        let (file, _) = parse_file_body(Layout::UNALIGNED).parse(cursor).unwrap();
         assert_eq!(file.mimetype, "text/plain");
         assert_eq!(file.data, b"hello world");
     }
@@ -72,7 +104,7 @@ mod tests {
         let bytes = make_file_bytes("application/octet-stream", &[]);
         let cursor = ByteCursor::new(&bytes);
 
-        let (file, _) = parse_file_body().parse(cursor).unwrap();
+        let (file, _) = parse_file_body(Layout::UNALIGNED).parse(cursor).unwrap();
         assert_eq!(file.mimetype, "application/octet-stream");
         assert_eq!(file.data, &[]);
     }
@@ -83,7 +115,7 @@ mod tests {
         let bytes = make_file_bytes("text/plain", data);
         let cursor = ByteCursor::new(&bytes);
 
-        let (file, _) = parse_file_body().parse(cursor).unwrap();
+        let (file, _) = parse_file_body(Layout::UNALIGNED).parse(cursor).unwrap();
 
         // Verify data points into original bytes
         let mimetype = "text/plain";
@@ -100,7 +132,7 @@ mod tests {
         let bytes = make_file_bytes("application/x-tflite", &data);
         let cursor = ByteCursor::new(&bytes);
 
-        let (file, _) = parse_file_body().parse(cursor).unwrap();
+        let (file, _) = parse_file_body(Layout::UNALIGNED).parse(cursor).unwrap();
         assert_eq!(file.mimetype, "application/x-tflite");
         assert_eq!(file.data.len(), 256);
     }
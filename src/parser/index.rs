@@ -0,0 +1,236 @@
+//! Path index lookup
+
+use super::lazy::LazyValue;
+use super::primitives::{u32_le, u64_le};
+use super::string::parse_key;
+use super::take::cursor_at;
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{HEADER_SIZE, Layout, PATH_INDEX_RECORD_SIZE, ValueView, path_hash};
+use parsicomb::Parser;
+
+/// Path index trailer: jumps straight to the value at a full slash path
+///
+/// Created with [`parse_index`](super::parse_index). A lookup hashes the
+/// path, compares the records in one bucket and decodes nothing but the
+/// value found.
+#[derive(Debug, Clone, Copy)]
+pub struct PathIndex<'a> {
+    data: &'a [u8],
+    layout: Layout,
+    entry_count: usize,
+    bucket_count: usize,
+    /// Offset of the bucket start table
+    buckets: usize,
+    /// Offset of the first record
+    records: usize,
+}
+
+impl<'a> PathIndex<'a> {
+    /// Decode the table header of the trailer occupying `start..end`
+    pub(crate) fn new(
+        data: &'a [u8],
+        layout: Layout,
+        start: usize,
+        end: usize,
+    ) -> Result<Self, ParseError> {
+        let (entry_count, cursor) = u32_le().parse(cursor_at(data, start))?;
+        let (bucket_count, _) = u32_le().parse(cursor)?;
+        let entry_count = entry_count as usize;
+        let bucket_count = bucket_count as usize;
+
+        // Counts come from the file, so the sizes may overflow on 32-bit
+        let malformed = || ParseError::new(BlobfigError::MalformedPathIndex, start);
+        let buckets = start + 8;
+        let records = bucket_count
+            .checked_add(1)
+            .and_then(|n| n.checked_mul(4))
+            .and_then(|len| buckets.checked_add(len))
+            .ok_or_else(malformed)?;
+        let table_end = entry_count
+            .checked_mul(PATH_INDEX_RECORD_SIZE)
+            .and_then(|len| records.checked_add(len))
+            .ok_or_else(malformed)?;
+        if !bucket_count.is_power_of_two() || table_end > end {
+            return Err(malformed());
+        }
+
+        Ok(Self {
+            data,
+            layout,
+            entry_count,
+            bucket_count,
+            buckets,
+            records,
+        })
+    }
+
+    /// Number of indexed paths
+    pub fn len(&self) -> usize {
+        self.entry_count
+    }
+
+    /// Whether no paths are indexed
+    pub fn is_empty(&self) -> bool {
+        self.entry_count == 0
+    }
+
+    /// The root value of the document
+    pub fn root(&self) -> LazyValue<'a> {
+        LazyValue::new(self.data, HEADER_SIZE, self.layout)
+    }
+
+    /// Find the value at `path` without visiting its ancestors
+    pub fn lookup(&self, path: &str) -> Result<Option<LazyValue<'a>>, ParseError> {
+        let hash = path_hash(path);
+        let bucket = (hash & (self.bucket_count as u64 - 1)) as usize;
+        let lo = self.read_u32(self.buckets + 4 * bucket)? as usize;
+        let hi = self.read_u32(self.buckets + 4 * (bucket + 1))? as usize;
+        if lo > hi || hi > self.entry_count {
+            return Err(self.malformed(self.buckets + 4 * bucket));
+        }
+
+        for i in lo..hi {
+            let record = self.records + i * PATH_INDEX_RECORD_SIZE;
+            if self.read_u64(record)? != hash {
+                continue;
+            }
+            let path_offset = self.read_offset(record + 16)?;
+            let (stored, _) = parse_key().parse(cursor_at(self.data, path_offset))?;
+            if stored == path {
+                let value_offset = self.read_offset(record + 8)?;
+                return Ok(Some(LazyValue::new(self.data, value_offset, self.layout)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find and decode the value at `path` (and its subtree, if a container)
+    pub fn get(&self, path: &str) -> Result<Option<ValueView<'a>>, ParseError> {
+        match self.lookup(path)? {
+            Some(value) => Ok(Some(value.to_view().map_err(|e| e.within(path))?)),
+            None => Ok(None),
+        }
+    }
+
+    fn read_u32(&self, pos: usize) -> Result<u32, ParseError> {
+        Ok(u32_le().parse(cursor_at(self.data, pos))?.0)
+    }
+
+    fn read_u64(&self, pos: usize) -> Result<u64, ParseError> {
+        Ok(u64_le().parse(cursor_at(self.data, pos))?.0)
+    }
+
+    /// Read an absolute offset and check it points into the file
+    fn read_offset(&self, pos: usize) -> Result<usize, ParseError> {
+        let offset = self.read_u64(pos)?;
+        usize::try_from(offset)
+            .ok()
+            .filter(|&offset| offset < self.data.len())
+            .ok_or_else(|| self.malformed(pos))
+    }
+
+    fn malformed(&self, pos: usize) -> ParseError {
+        ParseError::new(BlobfigError::MalformedPathIndex, pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::BlobfigError;
+    use crate::parser::parse_index;
+    use crate::types::Value;
+    use crate::writer::{self, WriteOptions};
+
+    fn indexed(value: Value) -> Vec<u8> {
+        writer::to_bytes_with(value, WriteOptions::default().with_path_index()).unwrap()
+    }
+
+    #[test]
+    fn lookup_nested_paths() {
+        let bytes = indexed(Value::Object(vec![
+            ("version".into(), Value::Int(2)),
+            (
+                "models".into(),
+                Value::Object(vec![(
+                    "encoder".into(),
+                    Value::Object(vec![("layers".into(), Value::Int(12))]),
+                )]),
+            ),
+        ]));
+        let index = parse_index(&bytes).unwrap();
+        assert_eq!(index.len(), 4);
+
+        let layers = index.lookup("models/encoder/layers").unwrap().unwrap();
+        assert_eq!(layers.decode().unwrap().as_int(), Some(12));
+
+        let encoder = index.get("models/encoder").unwrap().unwrap();
+        assert_eq!(encoder.get("layers").unwrap().as_int(), Some(12));
+
+        assert!(index.lookup("models/decoder").unwrap().is_none());
+        assert!(index.lookup("").unwrap().is_none());
+    }
+
+    #[test]
+    fn list_items_are_not_indexed() {
+        let bytes = indexed(Value::Object(vec![(
+            "items".into(),
+            Value::List(vec![Value::Object(vec![("x".into(), Value::Int(1))])]),
+        )]));
+        let index = parse_index(&bytes).unwrap();
+        assert_eq!(index.len(), 1);
+        assert!(index.lookup("items").unwrap().is_some());
+        assert!(index.lookup("items/x").unwrap().is_none());
+    }
+
+    #[test]
+    fn many_paths() {
+        let layers = (0..500)
+            .map(|i| {
+                (
+                    format!("layer_{}", i),
+                    Value::Object(vec![("bias".into(), Value::Int(i))]),
+                )
+            })
+            .collect();
+        let bytes = indexed(Value::Object(layers));
+        let index = parse_index(&bytes).unwrap();
+        assert_eq!(index.len(), 1000);
+
+        for i in [0, 1, 250, 499] {
+            let bias = index.lookup(&format!("layer_{}/bias", i)).unwrap().unwrap();
+            assert_eq!(bias.decode().unwrap().as_int(), Some(i));
+        }
+    }
+
+    #[test]
+    fn missing_index_is_an_error() {
+        let bytes = writer::to_bytes(Value::Int(1)).unwrap();
+        let err = parse_index(&bytes).err().unwrap();
+        assert_eq!(err.kind, BlobfigError::MissingPathIndex);
+    }
+
+    #[test]
+    fn malformed_trailer_is_an_error() {
+        let bytes = indexed(Value::Object(vec![("a".into(), Value::Int(1))]));
+        let footer = bytes.len() - 16;
+        let start = u64::from_le_bytes(bytes[footer..footer + 8].try_into().unwrap()) as usize;
+
+        // Bucket counts must be powers of two
+        let mut corrupt = bytes.clone();
+        corrupt[start + 4..start + 8].copy_from_slice(&3u32.to_le_bytes());
+        let err = parse_index(&corrupt).err().unwrap();
+        assert_eq!(err.kind, BlobfigError::MalformedPathIndex);
+
+        // Entry counts past the trailer, however large
+        let mut corrupt = bytes.clone();
+        corrupt[start..start + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = parse_index(&corrupt).err().unwrap();
+        assert_eq!(err.kind, BlobfigError::MalformedPathIndex);
+
+        let mut corrupt = bytes;
+        corrupt[footer..footer + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let err = parse_index(&corrupt).err().unwrap();
+        assert_eq!(err.kind, BlobfigError::InvalidTrailerStart(u64::MAX));
+        assert_eq!(err.offset, footer);
+    }
+}
@@ -0,0 +1,531 @@
+//! Lazily decoded values
+//!
+//! A [`LazyValue`] is a position in the buffer. Decoding it yields a
+//! [`LazyView`] whose Objects and Lists have only their headers decoded;
+//! children are decoded when visited and siblings are skipped over.
+
+use super::array::parse_array_body;
+use super::chunked::parse_chunked_body;
+use super::container::parse_container_header;
+use super::file::parse_file_body;
+use super::primitives::{f64_le, i64_le, u8_parser, u64_le};
+use super::skip::skip_value;
+use super::sparse::parse_sparse_body;
+use super::string::{parse_bytes, parse_key, parse_string};
+use super::string_array::parse_string_array;
+use super::take::cursor_at;
+use super::timestamp::parse_timestamp;
+use super::value::{parse_tag, parse_value};
+use crate::error::ParseError;
+use crate::types::{
+    ArrayView, ChunkedArrayView, FileView, Layout, SparseView, StringArrayView, Timestamp,
+    ValueTag, ValueView,
+};
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Handle to an encoded value that has not been decoded yet
+#[derive(Debug, Clone, Copy)]
+pub struct LazyValue<'a> {
+    data: &'a [u8],
+    offset: usize,
+    layout: Layout,
+}
+
+impl<'a> LazyValue<'a> {
+    /// Handle to the value whose tag is at `offset` within the file `data`
+    pub(crate) fn new(data: &'a [u8], offset: usize, layout: Layout) -> Self {
+        Self {
+            data,
+            offset,
+            layout,
+        }
+    }
+
+    /// Byte offset of this value's tag from the file start
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn cursor(&self) -> ByteCursor<'a> {
+        cursor_at(self.data, self.offset)
+    }
+
+    /// Decode the tag of this value
+    pub fn tag(&self) -> Result<ValueTag, ParseError> {
+        let (tag, _) = parse_tag().parse(self.cursor())?;
+        Ok(tag)
+    }
+
+    /// Decode this node only
+    ///
+    /// Scalars, Arrays, Sparse tensors and Files are fully decoded (their data is
+    /// still zero-copy), as are the chunk headers of chunked arrays. Objects and
+    /// Lists decode only their entry count.
+    pub fn decode(&self) -> Result<LazyView<'a>, ParseError> {
+        let (tag, cursor) = parse_tag().parse(self.cursor())?;
+
+        let view = match tag {
+            ValueTag::Bool => LazyView::Bool(u8_parser().parse(cursor)?.0 != 0),
+            ValueTag::Int => LazyView::Int(i64_le().parse(cursor)?.0),
+            ValueTag::Float => LazyView::Float(f64_le().parse(cursor)?.0),
+            ValueTag::String => LazyView::String(parse_string().parse(cursor)?.0),
+            ValueTag::Ref => LazyView::Ref(parse_string().parse(cursor)?.0),
+            ValueTag::Null => LazyView::Null,
+            ValueTag::UInt => LazyView::UInt(u64_le().parse(cursor)?.0),
+            ValueTag::Bytes => LazyView::Bytes(parse_bytes().parse(cursor)?.0),
+            ValueTag::Timestamp => LazyView::Timestamp(parse_timestamp().parse(cursor)?.0),
+            ValueTag::StringArray => LazyView::StringArray(parse_string_array().parse(cursor)?.0),
+            ValueTag::Array => LazyView::Array(parse_array_body(self.layout).parse(cursor)?.0),
+            ValueTag::File => LazyView::File(parse_file_body(self.layout).parse(cursor)?.0),
+            ValueTag::Sparse => {
+                LazyView::Sparse(Box::new(parse_sparse_body(self.layout).parse(cursor)?.0))
+            }
+            ValueTag::ChunkedArray => {
+                LazyView::ChunkedArray(parse_chunked_body(self.layout).parse(cursor)?.0)
+            }
+            ValueTag::Object => {
+                let (header, cursor) = parse_container_header(self.layout).parse(cursor)?;
+                LazyView::Object(LazyObject {
+                    data: self.data,
+                    start: cursor.inner().1,
+                    len: header.len,
+                    layout: self.layout,
+                })
+            }
+            ValueTag::List => {
+                let (header, cursor) = parse_container_header(self.layout).parse(cursor)?;
+                LazyView::List(LazyList {
+                    data: self.data,
+                    start: cursor.inner().1,
+                    len: header.len,
+                    layout: self.layout,
+                })
+            }
+        };
+        Ok(view)
+    }
+
+    /// Eagerly decode this value and its whole subtree
+    pub fn to_view(&self) -> Result<ValueView<'a>, ParseError> {
+        let (value, _) = parse_value(self.layout).parse(self.cursor())?;
+        Ok(value)
+    }
+
+    /// Get a nested value by path (e.g., "audio/sample_rate")
+    ///
+    /// Only the Objects along the path are visited; sibling subtrees are
+    /// skipped. Returns `Ok(None)` if a key is missing or a non-Object is
+    /// encountered before the end of the path. References are not followed.
+    pub fn get(&self, path: &str) -> Result<Option<LazyValue<'a>>, ParseError> {
+        let mut current = *self;
+        let mut visited = 0usize;
+        for key in path.split('/') {
+            // Errors are reported under the path of the Object being searched
+            let parent = &path[..visited.saturating_sub(1)];
+            let object = match current.decode().map_err(|e| e.within(parent))? {
+                LazyView::Object(object) => object,
+                _ => return Ok(None),
+            };
+            match object.get(key).map_err(|e| e.within(parent))? {
+                Some(value) => current = value,
+                None => return Ok(None),
+            }
+            visited += key.len() + 1;
+        }
+        Ok(Some(current))
+    }
+}
+
+/// A value with only its own node decoded
+#[derive(Debug, Clone)]
+pub enum LazyView<'a> {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(&'a str),
+    Array(ArrayView<'a>),
+    File(FileView<'a>),
+    Object(LazyObject<'a>),
+    List(LazyList<'a>),
+    /// Slash path of another value in the same document
+    Ref(&'a str),
+    /// Explicitly unset value
+    Null,
+    UInt(u64),
+    Bytes(&'a [u8]),
+    Timestamp(Timestamp),
+    StringArray(StringArrayView<'a>),
+    Sparse(Box<SparseView<'a>>),
+    ChunkedArray(ChunkedArrayView<'a>),
+}
+
+impl<'a> LazyView<'a> {
+    /// Try to get as bool
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            LazyView::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Try to get as i64
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            LazyView::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Try to get as u64
+    pub fn as_uint(&self) -> Option<u64> {
+        match self {
+            LazyView::UInt(u) => Some(*u),
+            _ => None,
+        }
+    }
+
+    /// Try to get as f64
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            LazyView::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Try to get as string
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            LazyView::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Try to get as raw bytes
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            LazyView::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Try to get as timestamp
+    pub fn as_timestamp(&self) -> Option<Timestamp> {
+        match self {
+            LazyView::Timestamp(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    /// Whether this is Null
+    pub fn is_null(&self) -> bool {
+        matches!(self, LazyView::Null)
+    }
+
+    /// Try to get as string array
+    pub fn as_string_array(&self) -> Option<&StringArrayView<'a>> {
+        match self {
+            LazyView::StringArray(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Try to get as array
+    pub fn as_array(&self) -> Option<&ArrayView<'a>> {
+        match self {
+            LazyView::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Try to get as sparse tensor
+    pub fn as_sparse(&self) -> Option<&SparseView<'a>> {
+        match self {
+            LazyView::Sparse(s) => Some(s.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Try to get as chunked array
+    pub fn as_chunked_array(&self) -> Option<&ChunkedArrayView<'a>> {
+        match self {
+            LazyView::ChunkedArray(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Try to get as file
+    pub fn as_file(&self) -> Option<&FileView<'a>> {
+        match self {
+            LazyView::File(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Try to get as object
+    pub fn as_object(&self) -> Option<&LazyObject<'a>> {
+        match self {
+            LazyView::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+
+    /// Try to get as list
+    pub fn as_list(&self) -> Option<&LazyList<'a>> {
+        match self {
+            LazyView::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Try to get the target path of a reference
+    pub fn as_ref_path(&self) -> Option<&'a str> {
+        match self {
+            LazyView::Ref(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Get the tag for this value
+    pub fn tag(&self) -> ValueTag {
+        match self {
+            LazyView::Bool(_) => ValueTag::Bool,
+            LazyView::Int(_) => ValueTag::Int,
+            LazyView::Float(_) => ValueTag::Float,
+            LazyView::String(_) => ValueTag::String,
+            LazyView::Array(_) => ValueTag::Array,
+            LazyView::File(_) => ValueTag::File,
+            LazyView::Object(_) => ValueTag::Object,
+            LazyView::List(_) => ValueTag::List,
+            LazyView::Ref(_) => ValueTag::Ref,
+            LazyView::Null => ValueTag::Null,
+            LazyView::UInt(_) => ValueTag::UInt,
+            LazyView::Bytes(_) => ValueTag::Bytes,
+            LazyView::Timestamp(_) => ValueTag::Timestamp,
+            LazyView::StringArray(_) => ValueTag::StringArray,
+            LazyView::Sparse(_) => ValueTag::Sparse,
+            LazyView::ChunkedArray(_) => ValueTag::ChunkedArray,
+        }
+    }
+}
+
+/// Object whose entries are decoded on demand
+#[derive(Debug, Clone, Copy)]
+pub struct LazyObject<'a> {
+    data: &'a [u8],
+    start: usize,
+    len: u32,
+    layout: Layout,
+}
+
+impl<'a> LazyObject<'a> {
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Whether the object has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over entries, skipping each value without decoding it
+    pub fn iter(&self) -> LazyEntries<'a> {
+        LazyEntries {
+            data: self.data,
+            offset: self.start,
+            remaining: self.len,
+            layout: self.layout,
+        }
+    }
+
+    /// Find the value for `key` (linear scan over keys)
+    pub fn get(&self, key: &str) -> Result<Option<LazyValue<'a>>, ParseError> {
+        for entry in self.iter() {
+            let (k, v) = entry?;
+            if k == key {
+                return Ok(Some(v));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Iterator over the entries of a [`LazyObject`]
+///
+/// Stops after yielding the first error.
+#[derive(Debug, Clone)]
+pub struct LazyEntries<'a> {
+    data: &'a [u8],
+    offset: usize,
+    remaining: u32,
+    layout: Layout,
+}
+
+impl<'a> LazyEntries<'a> {
+    fn next_entry(&mut self) -> Result<(&'a str, LazyValue<'a>), ParseError> {
+        let (key, cursor) = parse_key().parse(cursor_at(self.data, self.offset))?;
+        let value = LazyValue::new(self.data, cursor.inner().1, self.layout);
+        let (_, cursor) = skip_value(self.layout)
+            .parse(cursor)
+            .map_err(|e| e.within(key))?;
+        self.offset = cursor.inner().1;
+        Ok((key, value))
+    }
+}
+
+impl<'a> Iterator for LazyEntries<'a> {
+    type Item = Result<(&'a str, LazyValue<'a>), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let entry = self.next_entry();
+        self.remaining = if entry.is_ok() { self.remaining - 1 } else { 0 };
+        Some(entry)
+    }
+}
+
+/// List whose items are decoded on demand
+#[derive(Debug, Clone, Copy)]
+pub struct LazyList<'a> {
+    data: &'a [u8],
+    start: usize,
+    len: u32,
+    layout: Layout,
+}
+
+impl<'a> LazyList<'a> {
+    /// Number of items
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Whether the list has no items
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over items, skipping each value without decoding it
+    pub fn iter(&self) -> LazyItems<'a> {
+        LazyItems {
+            data: self.data,
+            offset: self.start,
+            index: 0,
+            remaining: self.len,
+            layout: self.layout,
+        }
+    }
+
+    /// Get the item at `index` (skips over the preceding items)
+    pub fn get(&self, index: usize) -> Result<Option<LazyValue<'a>>, ParseError> {
+        self.iter().nth(index).transpose()
+    }
+}
+
+/// Iterator over the items of a [`LazyList`]
+///
+/// Stops after yielding the first error.
+#[derive(Debug, Clone)]
+pub struct LazyItems<'a> {
+    data: &'a [u8],
+    offset: usize,
+    index: u32,
+    remaining: u32,
+    layout: Layout,
+}
+
+impl<'a> Iterator for LazyItems<'a> {
+    type Item = Result<LazyValue<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let value = LazyValue::new(self.data, self.offset, self.layout);
+        match skip_value(self.layout).parse(cursor_at(self.data, self.offset)) {
+            Ok((_, cursor)) => {
+                self.offset = cursor.inner().1;
+                self.index += 1;
+                self.remaining -= 1;
+                Some(Ok(value))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e.within(&self.index.to_string())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_lazy;
+    use crate::types::{Array, DType, Value, ValueTag};
+    use crate::writer;
+
+    fn sample() -> Vec<u8> {
+        writer::to_bytes(Value::Object(vec![
+            ("version".into(), Value::Int(3)),
+            (
+                "encoder".into(),
+                Value::Object(vec![
+                    (
+                        "weights".into(),
+                        Value::Array(Array::new(DType::F32, vec![2], vec![0u8; 8])),
+                    ),
+                    ("name".into(), Value::String("enc".into())),
+                ]),
+            ),
+            (
+                "labels".into(),
+                Value::List(vec![Value::String("a".into()), Value::String("b".into())]),
+            ),
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn get_nested_path() {
+        let bytes = sample();
+        let root = parse_lazy(&bytes).unwrap();
+
+        let name = root.get("encoder/name").unwrap().unwrap();
+        assert_eq!(name.decode().unwrap().as_str(), Some("enc"));
+
+        let weights = root.get("encoder/weights").unwrap().unwrap();
+        let weights = weights.decode().unwrap();
+        assert_eq!(weights.as_array().unwrap().shape, vec![2]);
+
+        assert!(root.get("encoder/missing").unwrap().is_none());
+        assert!(root.get("version/deeper").unwrap().is_none());
+    }
+
+    #[test]
+    fn iterate_object_and_list() {
+        let bytes = sample();
+        let root = parse_lazy(&bytes).unwrap().decode().unwrap();
+        let object = root.as_object().unwrap();
+        assert_eq!(object.len(), 3);
+
+        let keys: Vec<&str> = object.iter().map(|e| e.unwrap().0).collect();
+        assert_eq!(keys, vec!["version", "encoder", "labels"]);
+
+        let labels = object.get("labels").unwrap().unwrap().decode().unwrap();
+        let labels = labels.as_list().unwrap();
+        assert_eq!(labels.len(), 2);
+        let second = labels.get(1).unwrap().unwrap().decode().unwrap();
+        assert_eq!(second.as_str(), Some("b"));
+        assert!(labels.get(2).unwrap().is_none());
+    }
+
+    #[test]
+    fn to_view_matches_eager_parse() {
+        let bytes = sample();
+        let encoder = parse_lazy(&bytes).unwrap().get("encoder").unwrap().unwrap();
+        assert_eq!(encoder.tag().unwrap(), ValueTag::Object);
+
+        let view = encoder.to_view().unwrap();
+        assert_eq!(view.get("name").unwrap().as_str(), Some("enc"));
+    }
+}
@@ -3,61 +3,160 @@
 //! Zero-copy parsing that returns views into the original byte buffer.
 
 mod array;
+mod array_layout;
+mod chunked;
+mod compression;
+mod container;
+mod encryption;
 mod entry;
 mod file;
+mod index;
+mod lazy;
+mod options;
 mod primitives;
+mod skip;
+mod sparse;
+mod storage;
 mod string;
+mod string_array;
 mod take;
+mod timestamp;
+mod trailer;
 mod value;
 
-pub use value::parse_value;
+pub use index::PathIndex;
+pub use lazy::{LazyEntries, LazyItems, LazyList, LazyObject, LazyValue, LazyView};
+pub use options::ParseOptions;
+pub use value::{parse_value, parse_value_with};
 
-use crate::types::{HEADER_SIZE, MAGIC, VERSION, ValueView};
-use parsicomb::{ByteCursor, CodeLoc, Parser, ParsicombError};
-use std::borrow::Cow;
+#[cfg(feature = "signing")]
+pub use crate::signing::{parse_signed, parse_signed_detached};
+
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{
+    CHECKSUM_MAGIC, CHECKSUM_SIZE, HEADER_SIZE, Header, Layout, MAGIC, MIN_VERSION,
+    PATH_INDEX_MAGIC, VERSION, ValueView, checksum,
+};
+use options::ParseContext;
+use parsicomb::Parser;
+use primitives::u32_le;
+use take::cursor_at;
 
 /// Parse a blobfig from bytes
 ///
 /// Returns a ValueView that borrows from the input bytes.
 /// For zero-copy parsing from memory-mapped files, the bytes
 /// must remain valid for the lifetime of the returned ValueView.
-pub fn parse(bytes: &[u8]) -> Result<ValueView<'_>, ParsicombError<'_>> {
+pub fn parse(bytes: &[u8]) -> Result<ValueView<'_>, ParseError> {
+    parse_with(bytes, ParseOptions::default())
+}
+
+/// Parse a blobfig from bytes, enforcing the limits in `options`
+///
+/// Use this for files from untrusted sources: exceeding a limit returns
+/// [`BlobfigError::LimitExceeded`] instead of exhausting stack or memory.
+pub fn parse_with(bytes: &[u8], options: ParseOptions) -> Result<ValueView<'_>, ParseError> {
+    let layout = parse_header(bytes)?;
+
+    // Parse value starting after header. The cursor spans the whole file so
+    // payload padding can be computed relative to the file start.
+    let cursor = cursor_at(bytes, HEADER_SIZE);
+    let (value, _) = parse_value_with(layout, options).parse(cursor)?;
+
+    Ok(value)
+}
+
+/// Parse a blobfig from bytes, checking every recorded checksum
+///
+/// Each Array and File payload is checked against its own checksum, then the
+/// whole document against the digest in the checksum trailer. Fails with
+/// [`BlobfigError::MissingChecksum`] if the file was not written
+/// [`WriteOptions::with_checksums`](crate::writer::WriteOptions::with_checksums)
+/// or the trailer was cut off.
+pub fn parse_verified(bytes: &[u8]) -> Result<ValueView<'_>, ParseError> {
+    let layout = parse_header(bytes)?;
+    let missing = || ParseError::new(BlobfigError::MissingChecksum, bytes.len());
+    if !layout.checksums {
+        return Err(missing());
+    }
+    let (start, end) = trailer::find_trailer(bytes, CHECKSUM_MAGIC)?.ok_or_else(missing)?;
+    if end - start != CHECKSUM_SIZE {
+        let kind = BlobfigError::InvalidTrailerSize((end - start) as u64);
+        return Err(ParseError::new(kind, start));
+    }
+
+    // Payloads first, so corruption inside one is reported under its path
+    let ctx = ParseContext::new(layout, ParseOptions::default()).verifying();
+    let (value, _) = value::parse_tree(ctx).parse(cursor_at(bytes, HEADER_SIZE))?;
+
+    let (expected, _) = u32_le().parse(cursor_at(bytes, start))?;
+    let actual = checksum(&bytes[..start]);
+    if actual != expected {
+        let kind = BlobfigError::ChecksumMismatch { expected, actual };
+        return Err(ParseError::new(kind, start));
+    }
+
+    Ok(value)
+}
+
+/// Open a blobfig from bytes without decoding the value tree
+///
+/// Only the header is validated. Objects and Lists are decoded node by node
+/// as they are visited, so looking up one path costs time proportional to
+/// that path rather than to the whole document.
+pub fn parse_lazy(bytes: &[u8]) -> Result<LazyValue<'_>, ParseError> {
+    let layout = parse_header(bytes)?;
+    Ok(LazyValue::new(bytes, HEADER_SIZE, layout))
+}
+
+/// Open the path index trailer of a blobfig written with
+/// [`WriteOptions::with_path_index`](crate::writer::WriteOptions::with_path_index)
+///
+/// Fails if the header does not flag a path index or the trailer is missing.
+pub fn parse_index(bytes: &[u8]) -> Result<PathIndex<'_>, ParseError> {
+    let layout = parse_header(bytes)?;
+    let header = Header::from_bytes(bytes[..HEADER_SIZE].try_into().unwrap());
+    let missing = || ParseError::new(BlobfigError::MissingPathIndex, 12);
+    if !header.has_path_index() {
+        return Err(missing());
+    }
+
+    let (start, end) = trailer::find_trailer(bytes, PATH_INDEX_MAGIC)?.ok_or_else(missing)?;
+    PathIndex::new(bytes, layout, start, end)
+}
+
+/// Validate the file header and return the layout of the value tree
+pub(crate) fn parse_header(bytes: &[u8]) -> Result<Layout, ParseError> {
     // Check minimum size for header
     if bytes.len() < HEADER_SIZE {
-        return Err(ParsicombError::UnexpectedEndOfFile(CodeLoc::new(bytes, 0)));
+        return Err(ParseError::new(BlobfigError::UnexpectedEof, bytes.len()));
     }
 
     // Validate magic bytes
     if &bytes[0..8] != MAGIC {
-        return Err(ParsicombError::SyntaxError {
-            message: Cow::Borrowed("Invalid magic bytes"),
-            loc: CodeLoc::new(bytes, 0),
-        });
+        return Err(ParseError::new(BlobfigError::InvalidMagic, 0));
     }
 
     // Validate version
-    let version = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
-    if version != VERSION {
-        return Err(ParsicombError::SyntaxError {
-            message: Cow::Owned(format!(
-                "Unsupported version: {}, expected {}",
-                version, VERSION
-            )),
-            loc: CodeLoc::new(bytes, 8),
-        });
+    let header = Header::from_bytes(bytes[..HEADER_SIZE].try_into().unwrap());
+    if !(MIN_VERSION..=VERSION).contains(&header.version) {
+        return Err(ParseError::new(
+            BlobfigError::UnsupportedVersion(header.version),
+            8,
+        ));
     }
 
-    // Parse value starting after header
-    let cursor = ByteCursor::new(&bytes[HEADER_SIZE..]);
-    let (value, _) = parse_value().parse(cursor)?;
-
-    Ok(value)
+    header.layout().ok_or_else(|| {
+        let kind = BlobfigError::UnsupportedAlignment(header.alignment_log2());
+        ParseError::new(kind, 12)
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::Value;
+    use crate::error::Limit;
+    use crate::types::{Array, DType, File, Timestamp, Value};
     use crate::writer;
 
     #[test]
@@ -93,6 +192,24 @@ mod tests {
         assert_eq!(parsed.as_str(), Some("hello world"));
     }
 
+    #[test]
+    fn test_roundtrip_null_uint_bytes_timestamp() {
+        let timestamp = Timestamp::new(1_700_000_000, 5).unwrap();
+        let value = Value::List(vec![
+            Value::Null,
+            Value::UInt(u64::MAX),
+            Value::Bytes(vec![0xFF, 0x00]),
+            Value::Timestamp(timestamp),
+        ]);
+        let bytes = writer::to_bytes(value).unwrap();
+        let parsed = parse(&bytes).unwrap();
+        let list = parsed.as_list().unwrap();
+        assert!(list[0].is_null());
+        assert_eq!(list[1].as_uint(), Some(u64::MAX));
+        assert_eq!(list[2].as_bytes(), Some(&[0xFF, 0x00][..]));
+        assert_eq!(list[3].as_timestamp(), Some(timestamp));
+    }
+
     #[test]
     fn test_roundtrip_list() {
         let value = Value::List(vec![Value::Int(1), Value::Int(2), Value::Int(3)]);
@@ -156,13 +273,240 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_unaligned_file_still_parses() {
+        // Files written without FLAG_ALIGNED carry no padding
+        let mut bytes = Header::new(0).to_bytes().to_vec();
+        bytes.push(crate::types::ValueTag::File as u8);
+        bytes.extend_from_slice(&10u16.to_le_bytes());
+        bytes.extend_from_slice(b"text/plain");
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        bytes.extend_from_slice(b"hi");
+
+        let parsed = parse(&bytes).unwrap();
+        assert_eq!(parsed.as_file().unwrap().data, b"hi");
+    }
+
+    #[test]
+    fn test_unsupported_alignment() {
+        let mut bytes = Header::new(crate::types::FLAG_ALIGNED | (63 << 8))
+            .to_bytes()
+            .to_vec();
+        bytes.push(crate::types::ValueTag::Bool as u8);
+        bytes.push(1);
+        let err = parse(&bytes).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::UnsupportedAlignment(63));
+    }
+
     #[test]
     fn test_invalid_version() {
         let mut bytes = Vec::new();
         bytes.extend_from_slice(MAGIC);
         bytes.extend_from_slice(&99u32.to_le_bytes()); // Wrong version
         bytes.extend_from_slice(&0u32.to_le_bytes()); // flags
-        let result = parse(&bytes);
-        assert!(result.is_err());
+        let err = parse(&bytes).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::UnsupportedVersion(99));
+        assert_eq!(err.offset, 8);
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let nested = |depth: usize| {
+            let mut value = Value::Int(1);
+            for _ in 0..depth {
+                value = Value::Object(vec![("a".into(), value)]);
+            }
+            writer::to_bytes(value).unwrap()
+        };
+        let options = ParseOptions {
+            max_depth: 3,
+            ..Default::default()
+        };
+
+        assert!(parse_with(&nested(3), options).is_ok());
+        let err = parse_with(&nested(4), options).unwrap_err();
+        assert_eq!(
+            err.kind,
+            BlobfigError::LimitExceeded {
+                limit: Limit::Depth,
+                max: 3
+            }
+        );
+        assert_eq!(err.path, "a/a/a");
+    }
+
+    #[test]
+    fn test_container_and_value_limits() {
+        let bytes = writer::to_bytes(Value::List(vec![
+            Value::Int(1),
+            Value::Int(2),
+            Value::Int(3),
+        ]))
+        .unwrap();
+
+        let options = ParseOptions {
+            max_container_len: 2,
+            ..Default::default()
+        };
+        let err = parse_with(&bytes, options).unwrap_err();
+        assert!(matches!(
+            err.kind,
+            BlobfigError::LimitExceeded {
+                limit: Limit::ContainerLen,
+                ..
+            }
+        ));
+
+        // The list itself counts as a value
+        let options = ParseOptions {
+            max_total_values: 4,
+            ..Default::default()
+        };
+        assert!(parse_with(&bytes, options).is_ok());
+        let options = ParseOptions {
+            max_total_values: 3,
+            ..Default::default()
+        };
+        let err = parse_with(&bytes, options).unwrap_err();
+        assert_eq!(err.path, "2");
+    }
+
+    #[test]
+    fn test_string_limit() {
+        let value = Value::Object(vec![("name".into(), Value::String("hello".into()))]);
+        let bytes = writer::to_bytes(value).unwrap();
+
+        let options = |max_string_len| ParseOptions {
+            max_string_len,
+            ..Default::default()
+        };
+        assert!(parse_with(&bytes, options(5)).is_ok());
+        let err = parse_with(&bytes, options(4)).unwrap_err();
+        assert_eq!(err.path, "name");
+
+        // Keys are limited too
+        let err = parse_with(&bytes, options(3)).unwrap_err();
+        assert_eq!(err.path, "");
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let bytes = vec![0x00; HEADER_SIZE];
+        assert_eq!(parse(&bytes).unwrap_err().kind, BlobfigError::InvalidMagic);
+        assert_eq!(
+            parse(&bytes[..4]).unwrap_err().kind,
+            BlobfigError::UnexpectedEof
+        );
+
+        let mut bytes = writer::to_bytes(Value::Bool(true)).unwrap();
+        bytes[HEADER_SIZE] = 0xEE;
+        let err = parse(&bytes).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidValueTag(0xEE));
+        assert_eq!(err.offset, HEADER_SIZE);
+        assert_eq!(err.path, "");
+    }
+
+    #[test]
+    fn test_error_path() {
+        let value = Value::Object(vec![(
+            "items".into(),
+            Value::List(vec![
+                Value::String("ok".into()),
+                Value::String("bad".into()),
+            ]),
+        )]);
+        let mut bytes = writer::to_bytes(value).unwrap();
+        let bad = bytes.windows(3).position(|w| w == b"bad").unwrap();
+        bytes[bad] = 0xFF;
+
+        let err = parse(&bytes).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidUtf8);
+        assert_eq!(err.offset, bad);
+        assert_eq!(err.path, "items/1");
+
+        let err = parse_lazy(&bytes).unwrap().get("items").unwrap().unwrap();
+        let err = err.to_view().unwrap_err();
+        assert_eq!(err.path, "1");
+    }
+
+    fn checksummed() -> Vec<u8> {
+        let value = Value::Object(vec![
+            (
+                "weights".into(),
+                Value::Array(Array::new(DType::U8, vec![4], vec![1, 2, 3, 4])),
+            ),
+            (
+                "vocab".into(),
+                Value::File(File::from_bytes("text/plain", b"a\nb\n".to_vec())),
+            ),
+        ]);
+        let options = writer::WriteOptions::default().with_checksums();
+        writer::to_bytes_with(value, options).unwrap()
+    }
+
+    #[test]
+    fn test_verified_roundtrip() {
+        let bytes = checksummed();
+        let parsed = parse_verified(&bytes).unwrap();
+        assert_eq!(
+            parsed.get("weights").unwrap().as_array().unwrap().data,
+            [1, 2, 3, 4]
+        );
+        assert!(
+            parsed
+                .get("vocab")
+                .unwrap()
+                .as_file()
+                .unwrap()
+                .verify()
+                .is_ok()
+        );
+
+        // Files without checksums cannot be verified
+        let bytes = writer::to_bytes(Value::Int(1)).unwrap();
+        let err = parse_verified(&bytes).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::MissingChecksum);
+    }
+
+    #[test]
+    fn test_verified_detects_payload_corruption() {
+        let mut bytes = checksummed();
+        let data = bytes.windows(3).position(|w| w == b"a\nb").unwrap();
+        bytes[data] = b'z';
+
+        // Plain parsing does not look at checksums
+        let parsed = parse(&bytes).unwrap();
+        let weights = parsed.get("weights").unwrap().as_array().unwrap().clone();
+        assert!(weights.verify().is_ok());
+        let err = parsed.get("vocab").unwrap().as_file().unwrap().verify();
+        assert!(matches!(err, Err(BlobfigError::ChecksumMismatch { .. })));
+
+        let err = parse_verified(&bytes).unwrap_err();
+        assert!(matches!(err.kind, BlobfigError::ChecksumMismatch { .. }));
+        assert_eq!(err.path, "vocab");
+    }
+
+    #[test]
+    fn test_verified_detects_document_corruption() {
+        // Corrupt a key, which no payload checksum covers
+        let mut bytes = checksummed();
+        let key = bytes.windows(5).position(|w| w == b"vocab").unwrap();
+        bytes[key] = b'V';
+        let err = parse_verified(&bytes).unwrap_err();
+        assert!(matches!(err.kind, BlobfigError::ChecksumMismatch { .. }));
+        assert_eq!(err.path, "");
+
+        // Truncation loses the trailer
+        let bytes = checksummed();
+        let err = parse_verified(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::MissingChecksum);
+
+        // A trailer start one byte early makes the checksum 5 bytes long
+        let mut bytes = checksummed();
+        let footer = bytes.len() - 16;
+        let start = u64::from_le_bytes(bytes[footer..footer + 8].try_into().unwrap());
+        bytes[footer..footer + 8].copy_from_slice(&(start - 1).to_le_bytes());
+        let err = parse_verified(&bytes).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidTrailerSize(5));
     }
 }
@@ -0,0 +1,114 @@
+//! Limits for parsing untrusted input
+
+use crate::error::{BlobfigError, Limit, ParseError};
+use crate::types::Layout;
+use std::cell::Cell;
+
+/// Limits applied while decoding a value tree
+///
+/// The defaults only bound nesting depth, which protects the stack. Set the
+/// other limits when parsing files from untrusted sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Maximum number of nested Objects and Lists (the root container is level 1)
+    pub max_depth: usize,
+    /// Maximum number of entries in one Object or items in one List or
+    /// string array
+    pub max_container_len: usize,
+    /// Maximum number of values in the whole tree, containers included
+    pub max_total_values: usize,
+    /// Maximum byte length of a String or Bytes value, string array
+    /// element or Object key
+    pub max_string_len: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 128,
+            max_container_len: usize::MAX,
+            max_total_values: usize::MAX,
+            max_string_len: usize::MAX,
+        }
+    }
+}
+
+/// Layout, limits and running totals shared by the parsers of one tree
+pub(crate) struct ParseContext {
+    pub layout: Layout,
+    pub options: ParseOptions,
+    /// Check every Array and File payload against its checksum
+    pub verify: bool,
+    values: Cell<usize>,
+}
+
+impl ParseContext {
+    pub fn new(layout: Layout, options: ParseOptions) -> Self {
+        Self {
+            layout,
+            options,
+            verify: false,
+            values: Cell::new(0),
+        }
+    }
+
+    /// Also verify payload checksums while parsing
+    pub fn verifying(mut self) -> Self {
+        self.verify = true;
+        self
+    }
+
+    /// Start counting values from zero
+    pub fn reset(&self) {
+        self.values.set(0);
+    }
+
+    /// Count one more value starting at `offset`
+    pub fn count_value(&self, offset: usize) -> Result<(), ParseError> {
+        let values = self.values.get() + 1;
+        self.values.set(values);
+        check(
+            Limit::TotalValues,
+            values,
+            self.options.max_total_values,
+            offset,
+        )
+    }
+
+    /// Check a container at nesting `level` (1 for the root container)
+    pub fn check_depth(&self, level: usize, offset: usize) -> Result<(), ParseError> {
+        check(Limit::Depth, level, self.options.max_depth, offset)
+    }
+
+    /// Check the entry or item count of a container
+    pub fn check_container_len(&self, len: u32, offset: usize) -> Result<(), ParseError> {
+        let len = usize::try_from(len).unwrap_or(usize::MAX);
+        check(
+            Limit::ContainerLen,
+            len,
+            self.options.max_container_len,
+            offset,
+        )
+    }
+
+    /// Check the byte length of a string or key
+    pub fn check_string_len(&self, len: usize, offset: usize) -> Result<(), ParseError> {
+        check(Limit::StringLen, len, self.options.max_string_len, offset)
+    }
+}
+
+/// Fail with [`BlobfigError::LimitExceeded`] if `actual` is above `max`
+pub(super) fn check(
+    limit: Limit,
+    actual: usize,
+    max: usize,
+    offset: usize,
+) -> Result<(), ParseError> {
+    if actual > max {
+        return Err(ParseError::new(
+            BlobfigError::LimitExceeded { limit, max },
+            offset,
+        ));
+    }
+    Ok(())
+}
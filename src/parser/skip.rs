@@ -0,0 +1,136 @@
+//! Skipping over encoded values without building views
+
+use super::array::parse_array_body;
+use super::chunked::skip_chunked_body;
+use super::container::parse_container_header;
+use super::file::parse_file_body;
+use super::options::{self, ParseOptions};
+use super::primitives::u32_le;
+use super::sparse::skip_sparse_body;
+use super::string::parse_key;
+use super::take::{cursor_at, take};
+use super::value::parse_tag;
+use crate::error::{Limit, ParseError};
+use crate::types::{Layout, ValueTag};
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Advance past one encoded value
+///
+/// Version 2 containers are skipped in O(1) using their length prefix.
+/// Version 1 containers have no length prefix and are walked to find their end,
+/// up to the default [`ParseOptions::max_depth`].
+pub fn skip_value<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = (), Error = ParseError> {
+    SkipValue { layout, depth: 0 }
+}
+
+struct SkipValue {
+    layout: Layout,
+    /// Number of enclosing version 1 containers being walked
+    depth: usize,
+}
+
+impl<'a> Parser<'a> for SkipValue {
+    type Cursor = ByteCursor<'a>;
+    type Output = ();
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (tag, cursor) = parse_tag().parse(cursor)?;
+
+        let cursor = match tag {
+            ValueTag::Null => cursor,
+            ValueTag::Bool => take(1).parse(cursor)?.1,
+            ValueTag::Int | ValueTag::UInt | ValueTag::Float => take(8).parse(cursor)?.1,
+            ValueTag::Timestamp => take(12).parse(cursor)?.1,
+            ValueTag::String | ValueTag::Ref | ValueTag::Bytes => {
+                let (len, cursor) = u32_le().parse(cursor)?;
+                take(len as usize).parse(cursor)?.1
+            }
+            ValueTag::StringArray => {
+                let (count, cursor) = u32_le().parse(cursor)?;
+                let (data_len, cursor) = u32_le().parse(cursor)?;
+                let len = (count as usize)
+                    .saturating_mul(4)
+                    .saturating_add(data_len as usize);
+                take(len).parse(cursor)?.1
+            }
+            ValueTag::Array => parse_array_body(self.layout).parse(cursor)?.1,
+            ValueTag::File => parse_file_body(self.layout).parse(cursor)?.1,
+            ValueTag::Sparse => skip_sparse_body(self.layout).parse(cursor)?.1,
+            ValueTag::ChunkedArray => skip_chunked_body(self.layout).parse(cursor)?.1,
+            ValueTag::Object | ValueTag::List => {
+                let (header, cursor) = parse_container_header(self.layout).parse(cursor)?;
+                match header.end {
+                    Some(end) => cursor_at(cursor.inner().0, end),
+                    None => {
+                        let (_, start) = cursor.inner();
+                        let depth = self.depth + 1;
+                        let max_depth = ParseOptions::default().max_depth;
+                        options::check(Limit::Depth, depth, max_depth, start)?;
+
+                        let child = SkipValue {
+                            layout: self.layout,
+                            depth,
+                        };
+                        let mut cursor = cursor;
+                        for _ in 0..header.len {
+                            if tag == ValueTag::Object {
+                                cursor = parse_key().parse(cursor)?.1;
+                            }
+                            cursor = child.parse(cursor)?.1;
+                        }
+                        cursor
+                    }
+                }
+            }
+        };
+
+        Ok(((), cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skip_scalar() {
+        let mut bytes = vec![ValueTag::Int as u8];
+        bytes.extend_from_slice(&7i64.to_le_bytes());
+        bytes.push(0xAB);
+
+        let (_, cursor) = skip_value(Layout::V1)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert_eq!(cursor.value().unwrap(), 0xAB);
+    }
+
+    #[test]
+    fn test_skip_v2_container_without_decoding() {
+        let mut bytes = vec![ValueTag::List as u8];
+        bytes.extend_from_slice(&6u64.to_le_bytes()); // count + 2 bytes of body
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[0xFF, 0xFF]); // garbage, never decoded
+        bytes.push(0xAB);
+
+        let (_, cursor) = skip_value(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert_eq!(cursor.value().unwrap(), 0xAB);
+    }
+
+    #[test]
+    fn test_skip_v1_container() {
+        let mut bytes = vec![ValueTag::List as u8];
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[ValueTag::Bool as u8, 1]);
+        bytes.push(0xAB);
+
+        let (_, cursor) = skip_value(Layout::V1)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert_eq!(cursor.value().unwrap(), 0xAB);
+    }
+}
@@ -0,0 +1,165 @@
+//! Sparse tensor parsing
+
+use super::array::parse_array_body;
+use super::primitives::{u8_parser, u64_le};
+use super::value::parse_tag;
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{ArrayView, Layout, SparseFormat, SparseIndices, SparseView, ValueTag};
+use parsicomb::{ByteCursor, Cursor, Parser, ntimes};
+
+/// Parse a sparse tensor (after tag has been consumed)
+///
+/// Layout: format byte, ndim byte, dense shape (ndim u64 values), then the
+/// index arrays and the values array as Array values. Shapes and dtypes are
+/// always checked; indices are checked unless they are stored compressed or
+/// encrypted.
+pub fn parse_sparse_body<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = SparseView<'a>, Error = ParseError> {
+    SparseBodyParser {
+        layout,
+        check_indices: true,
+    }
+}
+
+/// Parse a sparse tensor without checking its indices (for skipping it)
+pub(super) fn skip_sparse_body<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = SparseView<'a>, Error = ParseError> {
+    SparseBodyParser {
+        layout,
+        check_indices: false,
+    }
+}
+
+struct SparseBodyParser {
+    layout: Layout,
+    check_indices: bool,
+}
+
+impl SparseBodyParser {
+    fn parse_component<'a>(
+        &self,
+        cursor: ByteCursor<'a>,
+    ) -> Result<(ArrayView<'a>, ByteCursor<'a>), ParseError> {
+        let (_, start) = cursor.inner();
+        let (tag, cursor) = parse_tag().parse(cursor)?;
+        if tag != ValueTag::Array {
+            let reason = "index and value arrays must be Arrays";
+            return Err(ParseError::new(BlobfigError::InvalidSparse(reason), start));
+        }
+        parse_array_body(self.layout).parse(cursor)
+    }
+}
+
+impl<'a> Parser<'a> for SparseBodyParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = SparseView<'a>;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (_, start) = cursor.inner();
+
+        let (format_byte, cursor) = u8_parser().parse(cursor)?;
+        let format = SparseFormat::from_u8(format_byte).ok_or_else(|| {
+            ParseError::new(BlobfigError::InvalidSparseFormat(format_byte), start)
+        })?;
+        let (ndim, cursor) = u8_parser().parse(cursor)?;
+        let (shape, cursor) = ntimes(ndim as usize, u64_le()).parse(cursor)?;
+
+        let (indices, cursor) = match format {
+            SparseFormat::Coo => {
+                let (coords, cursor) = self.parse_component(cursor)?;
+                (SparseIndices::Coo(coords), cursor)
+            }
+            SparseFormat::Csr => {
+                let (indptr, cursor) = self.parse_component(cursor)?;
+                let (indices, cursor) = self.parse_component(cursor)?;
+                (SparseIndices::Csr { indptr, indices }, cursor)
+            }
+        };
+        let (values, cursor) = self.parse_component(cursor)?;
+
+        let sparse = SparseView {
+            shape,
+            indices,
+            values,
+        };
+        let checked = match self.check_indices {
+            true => sparse.check_stored(),
+            false => sparse.check_layout(),
+        };
+        checked.map_err(|e| ParseError::new(e, start))?;
+        Ok((sparse, cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Array, DType, Sparse};
+    use crate::writer::{self, WriteOptions};
+
+    fn coo_bytes(coords: &[u32], values: &[u8]) -> Vec<u8> {
+        let nnz = values.len() as u64;
+        let coords = coords.iter().flat_map(|c| c.to_le_bytes()).collect();
+        let sparse = Sparse::coo(
+            vec![4, 4],
+            Array::new(DType::U32, vec![nnz, 2], coords),
+            Array::new(DType::U8, vec![nnz], values.to_vec()),
+        );
+        let bytes = writer::to_bytes_with(sparse.into(), WriteOptions::unaligned()).unwrap();
+        bytes[crate::types::HEADER_SIZE + 1..].to_vec()
+    }
+
+    #[test]
+    fn test_parse_coo() {
+        let bytes = coo_bytes(&[0, 1, 3, 3], &[5, 6]);
+        let (sparse, cursor) = parse_sparse_body(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap();
+        assert!(cursor.eos());
+        assert_eq!(sparse.format(), SparseFormat::Coo);
+        assert_eq!(sparse.shape, vec![4, 4]);
+        assert_eq!(sparse.nnz(), 2);
+        assert_eq!(sparse.values.data, &[5, 6]);
+    }
+
+    #[test]
+    fn test_out_of_bounds_coordinate() {
+        let mut bytes = coo_bytes(&[0, 1, 3, 3], &[5, 6]);
+        // Move the second value from (3, 3) to (3, 4)
+        let coords: Vec<u8> = [0u32, 1, 3, 3]
+            .iter()
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+        let at = bytes.windows(16).position(|w| w == coords).unwrap() + 12;
+        bytes[at..at + 4].copy_from_slice(&4u32.to_le_bytes());
+
+        let err = parse_sparse_body(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            BlobfigError::InvalidSparse("coordinate out of bounds")
+        );
+        assert_eq!(err.offset, 0);
+
+        // Skipping does not look at the indices
+        assert!(
+            skip_sparse_body(Layout::UNALIGNED)
+                .parse(ByteCursor::new(&bytes))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_invalid_format() {
+        let mut bytes = coo_bytes(&[0, 0], &[1]);
+        bytes[0] = 0x7F;
+        let err = parse_sparse_body(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidSparseFormat(0x7F));
+    }
+}
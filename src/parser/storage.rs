@@ -0,0 +1,167 @@
+//! Payload storage parsing
+//!
+//! When [`FLAG_DEDUP`](crate::types::FLAG_DEDUP) is set, the data size of
+//! every Array and File is preceded by a storage byte. A payload identical to
+//! an earlier one is not repeated; it records where the earlier copy starts:
+//!
+//! ```text
+//! u8 storage         (0 = inline, 1 = reference)
+//! u64 data_offset    (only for references)
+//! u64 data_size
+//! ```
+//!
+//! References are followed directly by the checksum, if any, with no padding
+//! or data of their own.
+
+use super::primitives::{u8_parser, u64_le};
+use super::take::{align, cursor_at, take};
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{Layout, STORAGE_INLINE, STORAGE_REFERENCE};
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Parse the storage fields preceding an Array or File data size
+///
+/// Returns the offset of the referenced data, or None for inline payloads
+/// and when the layout has no storage fields at all.
+pub fn parse_storage<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = Option<u64>, Error = ParseError> {
+    StorageParser { layout }
+}
+
+struct StorageParser {
+    layout: Layout,
+}
+
+impl<'a> Parser<'a> for StorageParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = Option<u64>;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        if !self.layout.dedup {
+            return Ok((None, cursor));
+        }
+
+        let (storage, cursor) = u8_parser().parse(cursor)?;
+        match storage {
+            STORAGE_INLINE => Ok((None, cursor)),
+            STORAGE_REFERENCE => {
+                let (offset, cursor) = u64_le().parse(cursor)?;
+                Ok((Some(offset), cursor))
+            }
+            _ => {
+                let (_, pos) = cursor.inner();
+                let kind = BlobfigError::InvalidStorage(storage);
+                Err(ParseError::new(kind, pos.saturating_sub(1)))
+            }
+        }
+    }
+}
+
+/// Take the data of a payload, following its reference if it has one
+///
+/// Inline data is expected at the next multiple of `layout.alignment` bytes.
+/// Referenced data must end before `start`, the offset of the payload's own
+/// header, so references can only point backwards.
+pub fn payload_data<'a>(
+    layout: Layout,
+    reference: Option<u64>,
+    data_size: u64,
+    start: usize,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = &'a [u8], Error = ParseError> {
+    PayloadData {
+        layout,
+        reference,
+        data_size,
+        start,
+    }
+}
+
+struct PayloadData {
+    layout: Layout,
+    reference: Option<u64>,
+    data_size: u64,
+    start: usize,
+}
+
+impl<'a> Parser<'a> for PayloadData {
+    type Cursor = ByteCursor<'a>;
+    type Output = &'a [u8];
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let len = usize::try_from(self.data_size).map_err(|_| {
+            let (_, pos) = cursor.inner();
+            ParseError::new(BlobfigError::SizeOverflow(self.data_size), pos)
+        })?;
+
+        let Some(offset) = self.reference else {
+            // Skip padding up to the aligned payload
+            // At most 2^31 (see Header::alignment), so this fits usize
+            let (_, cursor) = align(self.layout.alignment as usize).parse(cursor)?;
+            // Take data bytes (zero-copy)
+            let (data, cursor) = take(len).parse(cursor)?;
+            return Ok((data, cursor));
+        };
+
+        let (bytes, _) = cursor.inner();
+        let out_of_range = || ParseError::new(BlobfigError::InvalidReference(offset), self.start);
+        let offset = usize::try_from(offset).map_err(|_| out_of_range())?;
+        if offset.checked_add(len).is_none_or(|end| end > self.start) {
+            return Err(out_of_range());
+        }
+        let (data, _) = take(len).parse(cursor_at(bytes, offset))?;
+        Ok((data, cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEDUP: Layout = Layout {
+        dedup: true,
+        ..Layout::UNALIGNED
+    };
+
+    #[test]
+    fn inline_and_reference() {
+        let (reference, _) = parse_storage(DEDUP)
+            .parse(ByteCursor::new(&[STORAGE_INLINE]))
+            .unwrap();
+        assert_eq!(reference, None);
+
+        let mut bytes = vec![STORAGE_REFERENCE];
+        bytes.extend_from_slice(&40u64.to_le_bytes());
+        let (reference, _) = parse_storage(DEDUP).parse(ByteCursor::new(&bytes)).unwrap();
+        assert_eq!(reference, Some(40));
+
+        let err = parse_storage(DEDUP)
+            .parse(ByteCursor::new(&[0x02]))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidStorage(0x02));
+        assert_eq!(err.offset, 0);
+    }
+
+    #[test]
+    fn references_point_backwards() {
+        let bytes = b"shared data, then a reference";
+        let cursor = cursor_at(bytes, 12);
+        let (data, rest) = payload_data(DEDUP, Some(0), 6, 12).parse(cursor).unwrap();
+        assert_eq!(data, b"shared");
+        assert_eq!(rest.inner().1, 12);
+
+        // The referenced bytes may not overlap the reference itself
+        let err = payload_data(DEDUP, Some(8), 6, 12)
+            .parse(cursor_at(bytes, 12))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidReference(8));
+        assert_eq!(err.offset, 12);
+
+        let err = payload_data(DEDUP, Some(u64::MAX), 6, 12)
+            .parse(cursor_at(bytes, 12))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidReference(u64::MAX));
+    }
+}
@@ -1,22 +1,41 @@
-//! String parsing helpers
+//! String and byte string parsing helpers
 
 use super::primitives::{u16_le, u32_le};
 use super::take::take;
-use parsicomb::{ByteCursor, CodeLoc, Cursor, Parser, ParsicombError};
-use std::borrow::Cow;
+use crate::error::{BlobfigError, ParseError};
+use parsicomb::{ByteCursor, Cursor, Parser};
 
 /// Parse a u32 length-prefixed UTF-8 string (for values)
 pub fn parse_string<'a>()
--> impl Parser<'a, Cursor = ByteCursor<'a>, Output = &'a str, Error = ParsicombError<'a>> {
+-> impl Parser<'a, Cursor = ByteCursor<'a>, Output = &'a str, Error = ParseError> {
     StringParser::<u32>::new()
 }
 
 /// Parse a u16 length-prefixed UTF-8 string (for object keys)
 pub fn parse_key<'a>()
--> impl Parser<'a, Cursor = ByteCursor<'a>, Output = &'a str, Error = ParsicombError<'a>> {
+-> impl Parser<'a, Cursor = ByteCursor<'a>, Output = &'a str, Error = ParseError> {
     StringParser::<u16>::new()
 }
 
+/// Parse a u32 length-prefixed byte string (for Bytes values)
+pub fn parse_bytes<'a>()
+-> impl Parser<'a, Cursor = ByteCursor<'a>, Output = &'a [u8], Error = ParseError> {
+    BytesParser
+}
+
+struct BytesParser;
+
+impl<'a> Parser<'a> for BytesParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = &'a [u8];
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (len, cursor) = u32_le().parse(cursor)?;
+        Ok(take(len as usize).parse(cursor)?)
+    }
+}
+
 struct StringParser<L> {
     _marker: std::marker::PhantomData<L>,
 }
@@ -32,17 +51,14 @@ impl<L> StringParser<L> {
 impl<'a> Parser<'a> for StringParser<u32> {
     type Cursor = ByteCursor<'a>;
     type Output = &'a str;
-    type Error = ParsicombError<'a>;
+    type Error = ParseError;
 
     fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
         let (len, cursor) = u32_le().parse(cursor)?;
         let (bytes, cursor) = take(len as usize).parse(cursor)?;
         let s = std::str::from_utf8(bytes).map_err(|_| {
-            let (data, pos) = cursor.inner();
-            ParsicombError::SyntaxError {
-                message: Cow::Borrowed("Invalid UTF-8 in string"),
-                loc: CodeLoc::new(data, pos.saturating_sub(len as usize)),
-            }
+            let (_, pos) = cursor.inner();
+            ParseError::new(BlobfigError::InvalidUtf8, pos.saturating_sub(len as usize))
         })?;
         Ok((s, cursor))
     }
@@ -51,17 +67,14 @@ impl<'a> Parser<'a> for StringParser<u32> {
 impl<'a> Parser<'a> for StringParser<u16> {
     type Cursor = ByteCursor<'a>;
     type Output = &'a str;
-    type Error = ParsicombError<'a>;
+    type Error = ParseError;
 
     fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
         let (len, cursor) = u16_le().parse(cursor)?;
         let (bytes, cursor) = take(len as usize).parse(cursor)?;
         let s = std::str::from_utf8(bytes).map_err(|_| {
-            let (data, pos) = cursor.inner();
-            ParsicombError::SyntaxError {
-                message: Cow::Borrowed("Invalid UTF-8 in string"),
-                loc: CodeLoc::new(data, pos.saturating_sub(len as usize)),
-            }
+            let (_, pos) = cursor.inner();
+            ParseError::new(BlobfigError::InvalidUtf8, pos.saturating_sub(len as usize))
         })?;
         Ok((s, cursor))
     }
@@ -93,6 +106,17 @@ mod tests {
         assert_eq!(s, "name");
     }
 
+    #[test]
+    fn test_parse_bytes() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&[0xFF, 0x00, 0xFE]);
+
+        let cursor = ByteCursor::new(&bytes);
+        let (b, _) = parse_bytes().parse(cursor).unwrap();
+        assert_eq!(b, [0xFF, 0x00, 0xFE]);
+    }
+
     #[test]
     fn test_parse_empty_string() {
         let bytes = 0u32.to_le_bytes();
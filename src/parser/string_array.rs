@@ -0,0 +1,96 @@
+//! String array parsing
+
+use super::primitives::u32_le;
+use super::take::take;
+use crate::error::ParseError;
+use crate::types::StringArrayView;
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Parse `u32 count, u32 data_len, count * u32 end offsets, data`
+///
+/// The offsets must be non-decreasing, end on character boundaries and end
+/// exactly at the end of the UTF-8 data.
+pub fn parse_string_array<'a>()
+-> impl Parser<'a, Cursor = ByteCursor<'a>, Output = StringArrayView<'a>, Error = ParseError> {
+    StringArrayParser
+}
+
+struct StringArrayParser;
+
+impl<'a> Parser<'a> for StringArrayParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = StringArrayView<'a>;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (count, cursor) = u32_le().parse(cursor)?;
+        let (data_len, cursor) = u32_le().parse(cursor)?;
+        let (_, table_start) = cursor.inner();
+        let (ends, cursor) = take((count as usize).saturating_mul(4)).parse(cursor)?;
+        let (data, cursor) = take(data_len as usize).parse(cursor)?;
+        let view = StringArrayView::new(ends, data)
+            .map_err(|(kind, pos)| ParseError::new(kind, table_start + pos))?;
+        Ok((view, cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::BlobfigError;
+
+    fn make_bytes(ends: &[u32], data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(ends.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        for end in ends {
+            bytes.extend_from_slice(&end.to_le_bytes());
+        }
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_string_array() {
+        let bytes = make_bytes(&[3, 3, 8], b"thehello");
+        let (view, cursor) = parse_string_array().parse(ByteCursor::new(&bytes)).unwrap();
+        assert!(cursor.eos());
+        assert_eq!(view.iter().collect::<Vec<_>>(), ["the", "", "hello"]);
+
+        // Strings point into the input
+        assert!(std::ptr::eq(
+            view.get(2).unwrap().as_ptr(),
+            bytes[23..].as_ptr()
+        ));
+    }
+
+    #[test]
+    fn test_invalid_offsets() {
+        let bytes = make_bytes(&[3, 9], b"thehello");
+        let err = parse_string_array()
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidStringOffset(9));
+        assert_eq!(err.offset, 12);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let bytes = make_bytes(&[2], &[b'a', 0xFF]);
+        let err = parse_string_array()
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidUtf8);
+        assert_eq!(err.offset, 13);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = make_bytes(&[3, 3, 8], b"thehello");
+        assert!(
+            parse_string_array()
+                .parse(ByteCursor::new(&bytes[..bytes.len() - 1]))
+                .is_err()
+        );
+    }
+}
@@ -21,23 +21,13 @@ impl<'a> Parser<'a> for Take {
     fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
         let (data, pos) = cursor.inner();
 
-        if pos + self.count > data.len() {
+        if self.count > data.len() - pos {
             return Err(ParsicombError::UnexpectedEndOfFile(CodeLoc::new(data, pos)));
         }
 
         let slice = &data[pos..pos + self.count];
-        let new_pos = pos + self.count;
-
-        let new_cursor = if new_pos >= data.len() {
-            ByteCursor::EndOfFile { data }
-        } else {
-            ByteCursor::Valid {
-                data,
-                position: new_pos,
-            }
-        };
-
-        Ok((slice, new_cursor))
+
+        Ok((slice, cursor_at(data, pos + self.count)))
     }
 }
 
@@ -46,6 +36,43 @@ pub fn take(count: usize) -> Take {
     Take::new(count)
 }
 
+/// Parser that skips padding up to the next multiple of `alignment`
+///
+/// Offsets are measured from the start of the cursor's underlying buffer.
+pub struct Align {
+    alignment: usize,
+}
+
+impl<'a> Parser<'a> for Align {
+    type Cursor = ByteCursor<'a>;
+    type Output = ();
+    type Error = ParsicombError<'a>;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (_, pos) = cursor.inner();
+        let padding = (self.alignment - pos % self.alignment) % self.alignment;
+        let (_, cursor) = take(padding).parse(cursor)?;
+        Ok(((), cursor))
+    }
+}
+
+/// Skip padding so the cursor lands on a multiple of `alignment`
+pub fn align(alignment: usize) -> Align {
+    Align { alignment }
+}
+
+/// Create a cursor positioned at `pos` within `data`
+pub fn cursor_at(data: &[u8], pos: usize) -> ByteCursor<'_> {
+    if pos >= data.len() {
+        ByteCursor::EndOfFile { data }
+    } else {
+        ByteCursor::Valid {
+            data,
+            position: pos,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,6 +112,13 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_take_huge_count() {
+        let data = b"hi";
+        let (_, cursor) = take(1).parse(ByteCursor::new(data)).unwrap();
+        assert!(take(usize::MAX).parse(cursor).is_err());
+    }
+
     #[test]
     fn test_take_chained() {
         let data = b"helloworld";
@@ -95,6 +129,25 @@ mod tests {
         assert_eq!(slice2, b"world");
     }
 
+    #[test]
+    fn test_align_skips_padding() {
+        let data = b"abcdefgh";
+        let (_, cursor) = take(3).parse(ByteCursor::new(data)).unwrap();
+        let (_, cursor) = align(4).parse(cursor).unwrap();
+        assert_eq!(cursor.value().unwrap(), b'e');
+
+        // Already aligned: nothing is skipped
+        let (_, cursor) = align(4).parse(cursor).unwrap();
+        assert_eq!(cursor.value().unwrap(), b'e');
+    }
+
+    #[test]
+    fn test_align_truncated_padding() {
+        let data = b"abc";
+        let (_, cursor) = take(1).parse(ByteCursor::new(data)).unwrap();
+        assert!(align(8).parse(cursor).is_err());
+    }
+
     #[test]
     fn test_take_is_zero_copy() {
         let data = b"hello";
@@ -0,0 +1,58 @@
+//! Timestamp parsing
+
+use super::primitives::{i64_le, u32_le};
+use crate::error::{BlobfigError, ParseError};
+use crate::types::Timestamp;
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Parse `i64 seconds, u32 nanos`, rejecting nanos of a second or more
+pub fn parse_timestamp<'a>()
+-> impl Parser<'a, Cursor = ByteCursor<'a>, Output = Timestamp, Error = ParseError> {
+    TimestampParser
+}
+
+struct TimestampParser;
+
+impl<'a> Parser<'a> for TimestampParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = Timestamp;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (seconds, cursor) = i64_le().parse(cursor)?;
+        let (nanos, cursor) = u32_le().parse(cursor)?;
+        let timestamp = Timestamp::new(seconds, nanos).ok_or_else(|| {
+            let (_, pos) = cursor.inner();
+            ParseError::new(BlobfigError::InvalidTimestamp(nanos), pos.saturating_sub(4))
+        })?;
+        Ok((timestamp, cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(-5i64).to_le_bytes());
+        bytes.extend_from_slice(&250u32.to_le_bytes());
+
+        let (timestamp, _) = parse_timestamp().parse(ByteCursor::new(&bytes)).unwrap();
+        assert_eq!(timestamp, Timestamp::new(-5, 250).unwrap());
+    }
+
+    #[test]
+    fn test_nanos_out_of_range() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0i64.to_le_bytes());
+        bytes.extend_from_slice(&1_000_000_000u32.to_le_bytes());
+
+        let err = parse_timestamp()
+            .parse(ByteCursor::new(&bytes))
+            .unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidTimestamp(1_000_000_000));
+        assert_eq!(err.offset, 8);
+    }
+}
@@ -0,0 +1,42 @@
+//! Locating trailers after the root value
+//!
+//! Each trailer ends with a footer of `u64 start offset` followed by an
+//! 8-byte magic. Trailers are chained backwards from the end of the file.
+
+use super::primitives::u64_le;
+use super::take::cursor_at;
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{
+    CHECKSUM_MAGIC, HEADER_SIZE, PATH_INDEX_MAGIC, SIGNATURE_MAGIC, TRAILER_FOOTER_SIZE,
+};
+use parsicomb::Parser;
+
+/// Magics of every trailer kind, used to walk past trailers we are not looking for
+const TRAILER_MAGICS: [&[u8; 8]; 3] = [PATH_INDEX_MAGIC, CHECKSUM_MAGIC, SIGNATURE_MAGIC];
+
+/// Find the trailer with the given magic
+///
+/// Returns the byte range of its body (excluding the footer), or None if the
+/// file does not end with a chain of trailers containing it.
+pub fn find_trailer(bytes: &[u8], magic: &[u8; 8]) -> Result<Option<(usize, usize)>, ParseError> {
+    let mut end = bytes.len();
+    while end >= HEADER_SIZE + TRAILER_FOOTER_SIZE {
+        let footer = end - TRAILER_FOOTER_SIZE;
+        let found = &bytes[end - 8..end];
+        if !TRAILER_MAGICS.iter().any(|m| m[..] == *found) {
+            return Ok(None);
+        }
+
+        let (start, _) = u64_le().parse(cursor_at(bytes, footer))?;
+        let start = usize::try_from(start)
+            .ok()
+            .filter(|&start| (HEADER_SIZE..=footer).contains(&start))
+            .ok_or_else(|| ParseError::new(BlobfigError::InvalidTrailerStart(start), footer))?;
+
+        if found == magic {
+            return Ok(Some((start, footer)));
+        }
+        end = start;
+    }
+    Ok(None)
+}
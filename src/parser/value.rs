@@ -1,36 +1,172 @@
 //! Value parsing
 
 use super::array::parse_array_body;
+use super::chunked::parse_chunked_body;
+use super::container::parse_container_header;
 use super::entry::parse_entry;
 use super::file::parse_file_body;
-use super::primitives::{f64_le, i64_le, u8_parser, u32_le};
-use super::string::parse_string;
-use crate::types::{ValueTag, ValueView};
-use parsicomb::{ByteCursor, CodeLoc, Cursor, Parser, ParsicombError, ntimes};
-use std::borrow::Cow;
-
-/// Parse a value
-pub fn parse_value<'a>()
--> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ValueView<'a>, Error = ParsicombError<'a>> {
-    ValueParser
+use super::options::{ParseContext, ParseOptions};
+use super::primitives::{f64_le, i64_le, u8_parser, u64_le};
+use super::sparse::parse_sparse_body;
+use super::string::{parse_bytes, parse_string};
+use super::string_array::parse_string_array;
+use super::timestamp::parse_timestamp;
+use crate::error::{BlobfigError, ParseError};
+use crate::types::{Layout, ValueTag, ValueView};
+use parsicomb::{ByteCursor, Cursor, Parser};
+
+/// Parse a value written with the given layout
+///
+/// Payload alignment is measured from the start of the cursor's buffer, which
+/// must therefore be the start of the file unless `layout.alignment` is 1.
+pub fn parse_value<'a>(
+    layout: Layout,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ValueView<'a>, Error = ParseError> {
+    parse_value_with(layout, ParseOptions::default())
+}
+
+/// Parse a value written with the given layout, enforcing `options`
+pub fn parse_value_with<'a>(
+    layout: Layout,
+    options: ParseOptions,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ValueView<'a>, Error = ParseError> {
+    parse_tree(ParseContext::new(layout, options))
 }
 
-struct ValueParser;
+/// Parse one whole value tree with the given context
+pub(super) fn parse_tree<'a>(
+    ctx: ParseContext,
+) -> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ValueView<'a>, Error = ParseError> {
+    TreeParser { ctx }
+}
 
-impl<'a> Parser<'a> for ValueParser {
+/// Parses one whole value tree, counting values from zero
+struct TreeParser {
+    ctx: ParseContext,
+}
+
+impl<'a> Parser<'a> for TreeParser {
     type Cursor = ByteCursor<'a>;
     type Output = ValueView<'a>;
-    type Error = ParsicombError<'a>;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        self.ctx.reset();
+        ValueParser::new(&self.ctx, 0).parse(cursor)
+    }
+}
+
+/// Parses a value nested inside `depth` containers
+pub(super) struct ValueParser<'c> {
+    ctx: &'c ParseContext,
+    depth: usize,
+}
+
+impl<'c> ValueParser<'c> {
+    pub(super) fn new(ctx: &'c ParseContext, depth: usize) -> Self {
+        Self { ctx, depth }
+    }
+
+    /// Parse an Array body starting at `cursor`
+    ///
+    /// Kept out of `parse` so the large view does not grow the stack frame
+    /// of every nesting level.
+    fn parse_array<'a>(
+        &self,
+        cursor: ByteCursor<'a>,
+        start: usize,
+    ) -> Result<(ValueView<'a>, ByteCursor<'a>), ParseError> {
+        let (arr, cursor) = parse_array_body(self.ctx.layout).parse(cursor)?;
+        if self.ctx.verify {
+            arr.verify().map_err(|e| ParseError::new(e, start))?;
+        }
+        Ok((ValueView::Array(arr), cursor))
+    }
+
+    /// Parse a File body starting at `cursor`, kept out of `parse` for the
+    /// same reason
+    fn parse_file<'a>(
+        &self,
+        cursor: ByteCursor<'a>,
+        start: usize,
+    ) -> Result<(ValueView<'a>, ByteCursor<'a>), ParseError> {
+        let (file, cursor) = parse_file_body(self.ctx.layout).parse(cursor)?;
+        if self.ctx.verify {
+            file.verify().map_err(|e| ParseError::new(e, start))?;
+        }
+        Ok((ValueView::File(file), cursor))
+    }
+
+    /// Parse a sparse tensor body starting at `cursor`, kept out of `parse`
+    /// for the same reason
+    fn parse_sparse<'a>(
+        &self,
+        cursor: ByteCursor<'a>,
+        start: usize,
+    ) -> Result<(ValueView<'a>, ByteCursor<'a>), ParseError> {
+        let (sparse, cursor) = parse_sparse_body(self.ctx.layout).parse(cursor)?;
+        if self.ctx.verify {
+            for arr in sparse.arrays() {
+                arr.verify().map_err(|e| ParseError::new(e, start))?;
+            }
+        }
+        Ok((ValueView::Sparse(Box::new(sparse)), cursor))
+    }
+
+    /// Parse a chunked array body starting at `cursor`, kept out of `parse`
+    /// for the same reason
+    fn parse_chunked<'a>(
+        &self,
+        cursor: ByteCursor<'a>,
+        start: usize,
+    ) -> Result<(ValueView<'a>, ByteCursor<'a>), ParseError> {
+        let (chunked, cursor) = parse_chunked_body(self.ctx.layout).parse(cursor)?;
+        if self.ctx.verify {
+            for chunk in chunked.chunks() {
+                chunk.verify().map_err(|e| ParseError::new(e, start))?;
+            }
+        }
+        Ok((ValueView::ChunkedArray(chunked), cursor))
+    }
+}
+
+/// Parse a value tag byte
+pub fn parse_tag<'a>()
+-> impl Parser<'a, Cursor = ByteCursor<'a>, Output = ValueTag, Error = ParseError> {
+    TagParser
+}
+
+struct TagParser;
+
+impl<'a> Parser<'a> for TagParser {
+    type Cursor = ByteCursor<'a>;
+    type Output = ValueTag;
+    type Error = ParseError;
 
     fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
         let (tag_byte, cursor) = u8_parser().parse(cursor)?;
         let tag = ValueTag::from_u8(tag_byte).ok_or_else(|| {
-            let (data, pos) = cursor.inner();
-            ParsicombError::SyntaxError {
-                message: Cow::Owned(format!("Invalid value tag: 0x{:02X}", tag_byte)),
-                loc: CodeLoc::new(data, pos.saturating_sub(1)),
-            }
+            let (_, pos) = cursor.inner();
+            ParseError::new(
+                BlobfigError::InvalidValueTag(tag_byte),
+                pos.saturating_sub(1),
+            )
         })?;
+        Ok((tag, cursor))
+    }
+}
+
+impl<'a> Parser<'a> for ValueParser<'_> {
+    type Cursor = ByteCursor<'a>;
+    type Output = ValueView<'a>;
+    type Error = ParseError;
+
+    fn parse(&self, cursor: Self::Cursor) -> Result<(Self::Output, Self::Cursor), Self::Error> {
+        let (data, start) = cursor.inner();
+        self.ctx.count_value(start)?;
+
+        let (tag, cursor) = parse_tag().parse(cursor)?;
+        let layout = self.ctx.layout;
 
         match tag {
             ValueTag::Bool => {
@@ -45,32 +181,87 @@ impl<'a> Parser<'a> for ValueParser {
                 let (v, cursor) = f64_le().parse(cursor)?;
                 Ok((ValueView::Float(v), cursor))
             }
+            ValueTag::Null => Ok((ValueView::Null, cursor)),
+            ValueTag::UInt => {
+                let (v, cursor) = u64_le().parse(cursor)?;
+                Ok((ValueView::UInt(v), cursor))
+            }
+            ValueTag::Bytes => {
+                let (b, cursor) = parse_bytes().parse(cursor)?;
+                self.ctx.check_string_len(b.len(), start + 1)?;
+                Ok((ValueView::Bytes(b), cursor))
+            }
+            ValueTag::Timestamp => {
+                let (t, cursor) = parse_timestamp().parse(cursor)?;
+                Ok((ValueView::Timestamp(t), cursor))
+            }
             ValueTag::String => {
                 let (s, cursor) = parse_string().parse(cursor)?;
+                self.ctx.check_string_len(s.len(), start + 1)?;
                 Ok((ValueView::String(s), cursor))
             }
-            ValueTag::Array => {
-                let (arr, cursor) = parse_array_body().parse(cursor)?;
-                Ok((ValueView::Array(arr), cursor))
+            ValueTag::Ref => {
+                let (path, cursor) = parse_string().parse(cursor)?;
+                self.ctx.check_string_len(path.len(), start + 1)?;
+                Ok((ValueView::Ref(path), cursor))
             }
-            ValueTag::File => {
-                let (file, cursor) = parse_file_body().parse(cursor)?;
-                Ok((ValueView::File(file), cursor))
+            ValueTag::StringArray => {
+                let (strings, cursor) = parse_string_array().parse(cursor)?;
+                self.ctx.check_container_len(strings.len() as u32, start)?;
+                if let Some(longest) = strings.iter().map(str::len).max() {
+                    self.ctx.check_string_len(longest, start + 1)?;
+                }
+                Ok((ValueView::StringArray(strings), cursor))
             }
+            ValueTag::Array => self.parse_array(cursor, start),
+            ValueTag::Sparse => self.parse_sparse(cursor, start),
+            ValueTag::ChunkedArray => self.parse_chunked(cursor, start),
+            ValueTag::File => self.parse_file(cursor, start),
             ValueTag::Object => {
-                let (n, cursor) = u32_le().parse(cursor)?;
-                let (entries, cursor) = ntimes(n as usize, parse_entry()).parse(cursor)?;
+                let depth = self.depth + 1;
+                self.ctx.check_depth(depth, start)?;
+                let (header, mut cursor) = parse_container_header(layout).parse(cursor)?;
+                self.ctx.check_container_len(header.len, start)?;
+
+                let mut entries = Vec::with_capacity(capacity(header.len, data, &cursor));
+                for _ in 0..header.len {
+                    let (entry, next) = parse_entry(self.ctx, depth).parse(cursor)?;
+                    entries.push(entry);
+                    cursor = next;
+                }
+                header.check_end(&cursor)?;
                 Ok((ValueView::Object(entries), cursor))
             }
             ValueTag::List => {
-                let (n, cursor) = u32_le().parse(cursor)?;
-                let (items, cursor) = ntimes(n as usize, parse_value()).parse(cursor)?;
+                let depth = self.depth + 1;
+                self.ctx.check_depth(depth, start)?;
+                let (header, mut cursor) = parse_container_header(layout).parse(cursor)?;
+                self.ctx.check_container_len(header.len, start)?;
+
+                let mut items = Vec::with_capacity(capacity(header.len, data, &cursor));
+                for index in 0..header.len {
+                    let (item, next) = ValueParser::new(self.ctx, depth)
+                        .parse(cursor)
+                        .map_err(|e| e.within(&index.to_string()))?;
+                    items.push(item);
+                    cursor = next;
+                }
+                header.check_end(&cursor)?;
                 Ok((ValueView::List(items), cursor))
             }
         }
     }
 }
 
+/// Capacity to reserve for `len` children, bounded by the bytes left so a
+/// forged count cannot force a huge allocation (every value takes 2+ bytes)
+fn capacity(len: u32, data: &[u8], cursor: &ByteCursor<'_>) -> usize {
+    let remaining = data.len().saturating_sub(cursor.inner().1);
+    usize::try_from(len)
+        .unwrap_or(usize::MAX)
+        .min(remaining / 2)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,7 +270,7 @@ mod tests {
     fn test_parse_bool_true() {
         let bytes = &[ValueTag::Bool as u8, 1];
         let cursor = ByteCursor::new(bytes);
-        let (val, _) = parse_value().parse(cursor).unwrap();
+        let (val, _) = parse_value(Layout::V1).parse(cursor).unwrap();
         assert_eq!(val.as_bool(), Some(true));
     }
 
@@ -87,7 +278,7 @@ mod tests {
     fn test_parse_bool_false() {
         let bytes = &[ValueTag::Bool as u8, 0];
         let cursor = ByteCursor::new(bytes);
-        let (val, _) = parse_value().parse(cursor).unwrap();
+        let (val, _) = parse_value(Layout::V1).parse(cursor).unwrap();
         assert_eq!(val.as_bool(), Some(false));
     }
 
@@ -96,7 +287,7 @@ mod tests {
         let mut bytes = vec![ValueTag::Int as u8];
         bytes.extend_from_slice(&42i64.to_le_bytes());
         let cursor = ByteCursor::new(&bytes);
-        let (val, _) = parse_value().parse(cursor).unwrap();
+        let (val, _) = parse_value(Layout::V1).parse(cursor).unwrap();
         assert_eq!(val.as_int(), Some(42));
     }
 
@@ -105,7 +296,7 @@ mod tests {
         let mut bytes = vec![ValueTag::Float as u8];
         bytes.extend_from_slice(&3.14f64.to_le_bytes());
         let cursor = ByteCursor::new(&bytes);
-        let (val, _) = parse_value().parse(cursor).unwrap();
+        let (val, _) = parse_value(Layout::V1).parse(cursor).unwrap();
         let f = val.as_float().unwrap();
         assert!((f - 3.14).abs() < 1e-10);
     }
@@ -117,7 +308,7 @@ mod tests {
         bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
         bytes.extend_from_slice(s.as_bytes());
         let cursor = ByteCursor::new(&bytes);
-        let (val, _) = parse_value().parse(cursor).unwrap();
+        let (val, _) = parse_value(Layout::V1).parse(cursor).unwrap();
         assert_eq!(val.as_str(), Some("hello"));
     }
 
@@ -139,7 +330,7 @@ mod tests {
         bytes.extend_from_slice(&3i64.to_le_bytes());
 
         let cursor = ByteCursor::new(&bytes);
-        let (val, _) = parse_value().parse(cursor).unwrap();
+        let (val, _) = parse_value(Layout::V1).parse(cursor).unwrap();
 
         let list = val.as_list().unwrap();
         assert_eq!(list.len(), 3);
@@ -170,7 +361,7 @@ mod tests {
         bytes.extend_from_slice(&42i64.to_le_bytes());
 
         let cursor = ByteCursor::new(&bytes);
-        let (val, _) = parse_value().parse(cursor).unwrap();
+        let (val, _) = parse_value(Layout::V1).parse(cursor).unwrap();
 
         let obj = val.as_object().unwrap();
         assert_eq!(obj.len(), 2);
@@ -200,7 +391,7 @@ mod tests {
         bytes.extend_from_slice(&123i64.to_le_bytes());
 
         let cursor = ByteCursor::new(&bytes);
-        let (val, _) = parse_value().parse(cursor).unwrap();
+        let (val, _) = parse_value(Layout::V1).parse(cursor).unwrap();
 
         // Use path accessor
         let inner_val = val.get("inner/value").unwrap();
@@ -211,7 +402,7 @@ mod tests {
     fn test_invalid_tag() {
         let bytes = &[0xFF]; // Invalid tag
         let cursor = ByteCursor::new(bytes);
-        let result = parse_value().parse(cursor);
+        let result = parse_value(Layout::V1).parse(cursor);
         assert!(result.is_err());
     }
 }
@@ -0,0 +1,168 @@
+//! proptest strategies for blobfig values
+//!
+//! Strategies generate random but valid trees: keys never contain `'/'` and
+//! Array data always matches shape and dtype, so every generated value can be
+//! written. Downstream crates can combine them to test their own schemas.
+//!
+//! Enable with the `proptest` feature flag.
+
+use crate::types::{
+    Array, ChunkedArray, DType, File, Order, Sparse, SparseFormat, Timestamp, Value,
+};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+use proptest::strategy::LazyJust;
+
+/// Every dtype, in tag order
+const DTYPES: [DType; 18] = [
+    DType::U8,
+    DType::I8,
+    DType::U16,
+    DType::I16,
+    DType::U32,
+    DType::I32,
+    DType::U64,
+    DType::I64,
+    DType::F32,
+    DType::F64,
+    DType::F16,
+    DType::BF16,
+    DType::Bool,
+    DType::C64,
+    DType::C128,
+    DType::I4,
+    DType::Q4_0,
+    DType::Q8_0,
+];
+
+/// Any dtype
+pub fn arb_dtype() -> impl Strategy<Value = DType> {
+    select(&DTYPES[..])
+}
+
+/// Shapes of up to 4 dimensions, each of at most 4 elements
+pub fn arb_shape() -> impl Strategy<Value = Vec<u64>> {
+    vec(0u64..=4, 0..=4)
+}
+
+/// Arrays of any dtype whose data matches their shape
+///
+/// Bool elements are 0 or 1. Unquantized arrays may be in Fortran order, and
+/// any array may have axis names.
+pub fn arb_array() -> impl Strategy<Value = Array> {
+    (arb_c_order_array(), any::<bool>(), any::<bool>()).prop_map(|(arr, fortran, named)| {
+        let arr = match fortran && !arr.dtype.is_quantized() {
+            true => arr.with_order(Order::F),
+            false => arr,
+        };
+        match named {
+            true => {
+                let names = (0..arr.shape.len()).map(|axis| format!("axis{axis}"));
+                arr.with_axis_names(names)
+            }
+            false => arr,
+        }
+    })
+}
+
+/// Arrays of any dtype in the default layout
+fn arb_c_order_array() -> impl Strategy<Value = Array> {
+    (arb_dtype(), arb_shape()).prop_flat_map(|(dtype, shape)| {
+        let size = dtype.data_size(shape.iter().product()).unwrap() as usize;
+        let max = if dtype == DType::Bool { 1 } else { u8::MAX };
+        vec(0..=max, size).prop_map(move |data| Array::new(dtype, shape.clone(), data))
+    })
+}
+
+/// Arrays in the default layout whose dtype is not quantized
+fn arb_unquantized_array() -> impl Strategy<Value = Array> {
+    arb_c_order_array().prop_filter("quantized dtype", |arr| !arr.dtype.is_quantized())
+}
+
+/// Sparse tensors holding the nonzero elements of an arbitrary array
+///
+/// Two-dimensional arrays are stored as CSR or COO, others as COO.
+pub fn arb_sparse() -> impl Strategy<Value = Sparse> {
+    (arb_unquantized_array(), any::<bool>()).prop_map(|(dense, csr)| {
+        let format = match csr && dense.shape.len() == 2 {
+            true => SparseFormat::Csr,
+            false => SparseFormat::Coo,
+        };
+        Sparse::from_dense(&dense, format).unwrap()
+    })
+}
+
+/// Chunked arrays splitting an arbitrary array into chunks of 1 to 3 elements
+/// along each dimension
+pub fn arb_chunked() -> impl Strategy<Value = ChunkedArray> {
+    arb_unquantized_array().prop_flat_map(|dense| {
+        vec(1..=3u64, dense.shape.len())
+            .prop_map(move |chunk_shape| ChunkedArray::from_array(&dense, chunk_shape).unwrap())
+    })
+}
+
+/// In-memory files with a `type/subtype` mimetype
+pub fn arb_file() -> impl Strategy<Value = File> {
+    ("[a-z]{1,12}/[a-z0-9.+-]{1,20}", vec(any::<u8>(), 0..256))
+        .prop_map(|(mimetype, data)| File::from_bytes(mimetype, data))
+}
+
+/// Timestamps anywhere in the i64 second range
+pub fn arb_timestamp() -> impl Strategy<Value = Timestamp> {
+    (any::<i64>(), 0u32..1_000_000_000)
+        .prop_map(|(seconds, nanos)| Timestamp::new(seconds, nanos).unwrap())
+}
+
+/// Object keys (any string without `'/'`)
+pub fn arb_key() -> impl Strategy<Value = String> {
+    "[^/]{0,16}"
+}
+
+/// Any non-container value
+///
+/// References are not generated, since their targets would have to exist.
+pub fn arb_leaf() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        LazyJust::new(|| Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::Int),
+        any::<u64>().prop_map(Value::UInt),
+        vec(any::<u8>(), 0..32).prop_map(Value::Bytes),
+        arb_timestamp().prop_map(Value::Timestamp),
+        any::<f64>().prop_map(Value::Float),
+        ".{0,32}".prop_map(Value::String),
+        vec(".{0,8}", 0..8).prop_map(|s| Value::StringArray(s.into())),
+        arb_array().prop_map(Value::Array),
+        arb_file().prop_map(Value::File),
+        arb_sparse().prop_map(Value::from),
+        arb_chunked().prop_map(Value::from),
+    ]
+}
+
+/// Any value, with Objects and Lists nested up to 4 levels deep
+pub fn arb_value() -> impl Strategy<Value = Value> {
+    arb_leaf().prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..8).prop_map(Value::List),
+            vec((arb_key(), inner), 0..8).prop_map(Value::Object),
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn arrays_are_consistent(array in arb_array()) {
+            prop_assert!(array.validate().is_ok());
+        }
+
+        #[test]
+        fn keys_have_no_separator(key in arb_key()) {
+            prop_assert!(!key.contains('/'));
+        }
+    }
+}
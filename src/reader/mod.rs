@@ -0,0 +1,696 @@
+//! Random-access reader over a seekable stream
+//!
+//! Navigates a blobfig without loading it into memory: values along a path
+//! are read one header at a time and unneeded siblings are skipped by
+//! seeking. Array and File payloads are exposed as bounded readers.
+
+mod node;
+mod payload;
+
+pub use node::{ArrayNode, ChunkedNode, ContainerNode, FileNode, Node, SparseNode};
+pub use payload::Payload;
+
+use crate::error::{BlobfigError, Limit};
+use crate::parser::ParseOptions;
+use crate::types::{
+    ArrayLayout, Cipher, Compressed, Compression, DType, Encrypted, Filters, HEADER_SIZE, Header,
+    LAYOUT_AXIS_NAMES, LAYOUT_FORTRAN, LAYOUT_STRIDES, Layout, MAGIC, MIN_VERSION, Order,
+    STORAGE_INLINE, STORAGE_REFERENCE, SparseFormat, SparseIndices, StringArrayView, Timestamp,
+    VERSION, ValueTag, check_chunk, check_data_size, check_filters, check_grid,
+    check_sparse_layout, flatten, raw_size,
+};
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Streaming blobfig reader
+///
+/// Malformed input is reported as [`io::ErrorKind::InvalidData`].
+#[derive(Debug)]
+pub struct Reader<R> {
+    inner: R,
+    layout: Layout,
+    len: u64,
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Open a reader, validating the header at the start of the stream
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let len = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(0))?;
+
+        let mut bytes = [0u8; HEADER_SIZE];
+        inner.read_exact(&mut bytes)?;
+        if &bytes[0..8] != MAGIC {
+            return Err(invalid("Invalid magic bytes"));
+        }
+        let header = Header::from_bytes(&bytes);
+        if !(MIN_VERSION..=VERSION).contains(&header.version) {
+            return Err(invalid(format!(
+                "Unsupported version: {}, expected {}..={}",
+                header.version, MIN_VERSION, VERSION
+            )));
+        }
+        let layout = header
+            .layout()
+            .ok_or_else(|| invalid(BlobfigError::UnsupportedAlignment(header.alignment_log2())))?;
+
+        Ok(Self { inner, layout, len })
+    }
+
+    /// Layout of the value tree
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Unwrap the underlying stream
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read the root value
+    pub fn root(&mut self) -> io::Result<Node> {
+        self.node_at(HEADER_SIZE as u64)
+    }
+
+    /// Get a nested value by path (e.g., "audio/sample_rate")
+    ///
+    /// Returns `Ok(None)` if a key is missing or a non-Object is encountered
+    /// before the end of the path. References are not followed.
+    pub fn get(&mut self, path: &str) -> io::Result<Option<Node>> {
+        let root = self.root()?;
+        self.get_in(&root, path)
+    }
+
+    /// Get a value by path relative to `node`
+    pub fn get_in(&mut self, node: &Node, path: &str) -> io::Result<Option<Node>> {
+        let mut current = node.clone();
+        for key in path.split('/') {
+            let Node::Object(object) = current else {
+                return Ok(None);
+            };
+            match self.find_key(&object, key)? {
+                Some(value) => current = value,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+
+    /// Read the keys and value headers of an Object
+    pub fn entries(&mut self, object: &ContainerNode) -> io::Result<Vec<(String, Node)>> {
+        self.seek(object.start)?;
+        let mut entries = Vec::with_capacity(object.len());
+        for _ in 0..object.len {
+            let key = self.read_key()?;
+            let value = self.read_node()?;
+            self.skip_rest(&value, 0)?;
+            entries.push((key, value));
+        }
+        Ok(entries)
+    }
+
+    /// Read the value headers of a List
+    pub fn items(&mut self, list: &ContainerNode) -> io::Result<Vec<Node>> {
+        self.seek(list.start)?;
+        let mut items = Vec::with_capacity(list.len());
+        for _ in 0..list.len {
+            let value = self.read_node()?;
+            self.skip_rest(&value, 0)?;
+            items.push(value);
+        }
+        Ok(items)
+    }
+
+    /// Reader bounded to the payload of an Array or File node
+    ///
+    /// Compressed payloads are read as stored. Returns None for other values.
+    pub fn payload(&mut self, node: &Node) -> io::Result<Option<Payload<'_, R>>> {
+        match node.payload_range() {
+            Some((start, len)) => Ok(Some(Payload::new(&mut self.inner, start, len)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reader bounded to the payload of an array, e.g. one inside a sparse tensor
+    pub fn array_payload(&mut self, array: &ArrayNode) -> io::Result<Payload<'_, R>> {
+        Payload::new(&mut self.inner, array.data_offset, array.data_size)
+    }
+
+    /// Read the header of the chunk at grid position `index`
+    ///
+    /// Returns `Ok(None)` if `index` lies outside the chunk grid. Read the
+    /// chunk data with [`array_payload`](Self::array_payload).
+    pub fn chunk(&mut self, chunked: &ChunkedNode, index: &[u64]) -> io::Result<Option<ArrayNode>> {
+        let Some(flat) = flatten(&chunked.grid(), index) else {
+            return Ok(None);
+        };
+        let chunk = self.read_chunk(chunked, flat)?;
+        let layout = (chunk.dtype, chunk.shape.as_slice(), &chunk.layout);
+        check_chunk(
+            chunked.dtype,
+            &chunked.shape,
+            &chunked.chunk_shape,
+            index,
+            layout,
+        )
+        .map_err(invalid)?;
+        Ok(Some(chunk))
+    }
+
+    /// Linear scan over the keys of an Object, skipping unmatched values
+    fn find_key(&mut self, object: &ContainerNode, key: &str) -> io::Result<Option<Node>> {
+        self.seek(object.start)?;
+        for _ in 0..object.len {
+            let found = self.read_key()? == key;
+            let value = self.read_node()?;
+            if found {
+                return Ok(Some(value));
+            }
+            self.skip_rest(&value, 0)?;
+        }
+        Ok(None)
+    }
+
+    fn node_at(&mut self, offset: u64) -> io::Result<Node> {
+        self.seek(offset)?;
+        self.read_node()
+    }
+
+    /// Read one value header at the current position
+    ///
+    /// Leaves the stream after scalars and strings, at the payload of Arrays
+    /// and Files (or after their header if they refer to an earlier payload),
+    /// and at the first child of Objects and Lists.
+    fn read_node(&mut self) -> io::Result<Node> {
+        let tag = self.read_u8()?;
+        let tag = ValueTag::from_u8(tag)
+            .ok_or_else(|| invalid(format!("Invalid value tag: 0x{:02X}", tag)))?;
+
+        match tag {
+            ValueTag::Bool => Ok(Node::Bool(self.read_u8()? != 0)),
+            ValueTag::Int => Ok(Node::Int(i64::from_le_bytes(self.read_array()?))),
+            ValueTag::Float => Ok(Node::Float(f64::from_le_bytes(self.read_array()?))),
+            ValueTag::String => {
+                let len = u32::from_le_bytes(self.read_array()?);
+                Ok(Node::String(self.read_utf8(len as u64)?))
+            }
+            ValueTag::Ref => {
+                let len = u32::from_le_bytes(self.read_array()?);
+                Ok(Node::Ref(self.read_utf8(len as u64)?))
+            }
+            ValueTag::Null => Ok(Node::Null),
+            ValueTag::UInt => Ok(Node::UInt(u64::from_le_bytes(self.read_array()?))),
+            ValueTag::Bytes => {
+                let len = u32::from_le_bytes(self.read_array()?);
+                Ok(Node::Bytes(self.read_bytes(len as u64)?))
+            }
+            ValueTag::Timestamp => {
+                let seconds = i64::from_le_bytes(self.read_array()?);
+                let nanos = u32::from_le_bytes(self.read_array()?);
+                let timestamp = Timestamp::new(seconds, nanos)
+                    .ok_or_else(|| invalid(BlobfigError::InvalidTimestamp(nanos)))?;
+                Ok(Node::Timestamp(timestamp))
+            }
+            ValueTag::StringArray => {
+                let count = u32::from_le_bytes(self.read_array()?);
+                let data_len = u32::from_le_bytes(self.read_array()?);
+                let ends = self.read_bytes(count as u64 * 4)?;
+                let data = self.read_bytes(data_len as u64)?;
+                let strings = StringArrayView::new(&ends, &data).map_err(|(e, _)| invalid(e))?;
+                Ok(Node::StringArray(strings.to_owned()))
+            }
+            ValueTag::Array => {
+                let start = self.position()?;
+                let dtype = self.read_u8()?;
+                let dtype = DType::from_u8(dtype)
+                    .ok_or_else(|| invalid(format!("Invalid dtype: 0x{:02X}", dtype)))?;
+                let ndim = self.read_u8()?;
+                let shape = (0..ndim)
+                    .map(|_| self.read_array().map(u64::from_le_bytes))
+                    .collect::<io::Result<Vec<_>>>()?;
+                let layout = self.read_array_layout(ndim as usize)?;
+                let compression = self.read_compression()?;
+                let encryption = self.read_encryption()?;
+                let reference = self.read_storage()?;
+                let data_size = u64::from_le_bytes(self.read_array()?);
+                let size = raw_size(data_size, compression, &encryption);
+                check_data_size(dtype, &shape, size).map_err(invalid)?;
+                if let Some(compressed) = compression {
+                    check_filters(compressed.filters, dtype.element_size()).map_err(invalid)?;
+                }
+                layout.validate(dtype, &shape).map_err(invalid)?;
+                let (data_offset, end) = self.payload_location(reference, data_size, start)?;
+                Ok(Node::Array(ArrayNode {
+                    dtype,
+                    shape,
+                    layout,
+                    data_offset,
+                    data_size,
+                    compression,
+                    encryption,
+                    end,
+                }))
+            }
+            ValueTag::Sparse => {
+                let format = self.read_u8()?;
+                let format = SparseFormat::from_u8(format)
+                    .ok_or_else(|| invalid(BlobfigError::InvalidSparseFormat(format)))?;
+                let ndim = self.read_u8()?;
+                let shape = (0..ndim)
+                    .map(|_| self.read_array().map(u64::from_le_bytes))
+                    .collect::<io::Result<Vec<_>>>()?;
+                let indices = match format {
+                    SparseFormat::Coo => SparseIndices::Coo(self.read_sparse_component()?),
+                    SparseFormat::Csr => SparseIndices::Csr {
+                        indptr: self.read_sparse_component()?,
+                        indices: self.read_sparse_component()?,
+                    },
+                };
+                let values = self.read_sparse_component()?;
+                let layout = indices.map(|a| (a.dtype, a.shape.as_slice()));
+                check_sparse_layout(&shape, &layout, (values.dtype, &values.shape))
+                    .map_err(invalid)?;
+                Ok(Node::Sparse(Box::new(SparseNode {
+                    shape,
+                    indices,
+                    values,
+                })))
+            }
+            ValueTag::ChunkedArray => {
+                let dtype = self.read_u8()?;
+                let dtype = DType::from_u8(dtype)
+                    .ok_or_else(|| invalid(format!("Invalid dtype: 0x{:02X}", dtype)))?;
+                let ndim = self.read_u8()?;
+                let dims = (0..2 * ndim as usize)
+                    .map(|_| self.read_array().map(u64::from_le_bytes))
+                    .collect::<io::Result<Vec<_>>>()?;
+                let (shape, chunk_shape) = dims.split_at(ndim as usize);
+                check_grid(shape, chunk_shape).map_err(invalid)?;
+                let chunked = ChunkedNode {
+                    dtype,
+                    shape: shape.to_vec(),
+                    chunk_shape: chunk_shape.to_vec(),
+                    table_offset: self.position()?,
+                };
+                if chunked.num_chunks() > (self.len - chunked.table_offset) / 8 {
+                    return Err(invalid("Chunk offset table exceeds file size"));
+                }
+                // The value ends with its last chunk
+                let end = match chunked.num_chunks().checked_sub(1) {
+                    Some(last) => self.read_chunk(&chunked, last)?.end,
+                    None => chunked.chunks_offset(),
+                };
+                self.seek(end)?;
+                Ok(Node::ChunkedArray(chunked))
+            }
+            ValueTag::File => {
+                let start = self.position()?;
+                let mimetype_len = u16::from_le_bytes(self.read_array()?);
+                let mimetype = self.read_utf8(mimetype_len as u64)?;
+                let compression = self.read_compression()?;
+                let encryption = self.read_encryption()?;
+                let reference = self.read_storage()?;
+                let size = u64::from_le_bytes(self.read_array()?);
+                let (data_offset, end) = self.payload_location(reference, size, start)?;
+                Ok(Node::File(FileNode {
+                    mimetype,
+                    data_offset,
+                    size,
+                    compression,
+                    encryption,
+                    end,
+                }))
+            }
+            ValueTag::Object | ValueTag::List => {
+                let container = self.read_container()?;
+                Ok(match tag {
+                    ValueTag::Object => Node::Object(container),
+                    _ => Node::List(container),
+                })
+            }
+        }
+    }
+
+    /// Read the chunk at flat grid position `flat` through the offset table
+    fn read_chunk(&mut self, chunked: &ChunkedNode, flat: u64) -> io::Result<ArrayNode> {
+        self.seek(chunked.table_offset + 8 * flat)?;
+        let entry = u64::from_le_bytes(self.read_array()?);
+        let offset = chunked
+            .chunks_offset()
+            .checked_add(entry)
+            .filter(|&offset| offset < self.len)
+            .ok_or_else(|| {
+                invalid(BlobfigError::InvalidChunkedArray(
+                    "chunk offset out of bounds",
+                ))
+            })?;
+        match self.node_at(offset)? {
+            Node::Array(array) => Ok(array),
+            _ => Err(invalid(BlobfigError::InvalidChunkedArray(
+                "chunks must be Arrays",
+            ))),
+        }
+    }
+
+    /// Read one of the arrays of a sparse tensor and move past it
+    fn read_sparse_component(&mut self) -> io::Result<ArrayNode> {
+        match self.read_node()? {
+            Node::Array(array) if !array.layout.is_default() => Err(invalid(
+                BlobfigError::InvalidSparse("arrays must have the default array layout"),
+            )),
+            Node::Array(array) => {
+                self.seek(array.end)?;
+                Ok(array)
+            }
+            _ => Err(invalid(BlobfigError::InvalidSparse(
+                "index and value arrays must be Arrays",
+            ))),
+        }
+    }
+
+    /// Move past the rest of a value whose header was just read
+    ///
+    /// `depth` counts the enclosing version 1 containers being walked.
+    fn skip_rest(&mut self, node: &Node, depth: usize) -> io::Result<()> {
+        match node {
+            // Bounds were checked when the header was read; sparse tensors
+            // and chunked arrays are read to their end
+            Node::Array(array) => self.seek(array.end),
+            Node::File(file) => self.seek(file.end),
+            Node::Object(container) | Node::List(container) => match container.end {
+                Some(end) => self.seek(end),
+                None => {
+                    // Version 1 containers have no length prefix
+                    let max = ParseOptions::default().max_depth;
+                    if depth >= max {
+                        let limit = Limit::Depth;
+                        return Err(invalid(BlobfigError::LimitExceeded { limit, max }));
+                    }
+                    let is_object = matches!(node, Node::Object(_));
+                    for _ in 0..container.len {
+                        if is_object {
+                            self.read_key()?;
+                        }
+                        let child = self.read_node()?;
+                        self.skip_rest(&child, depth + 1)?;
+                    }
+                    Ok(())
+                }
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn read_container(&mut self) -> io::Result<ContainerNode> {
+        let end = if self.layout.has_container_lengths() {
+            let body_len = u64::from_le_bytes(self.read_array()?);
+            let end = self
+                .position()?
+                .checked_add(body_len)
+                .filter(|&end| end <= self.len)
+                .ok_or_else(|| invalid("Container length exceeds file size"))?;
+            Some(end)
+        } else {
+            None
+        };
+        let len = u32::from_le_bytes(self.read_array()?);
+        let start = self.position()?;
+        if end.is_some_and(|end| end < start) {
+            return Err(invalid("Container length too small for its header"));
+        }
+        Ok(ContainerNode { len, start, end })
+    }
+
+    /// Locate the data of a payload whose header starts at `start`
+    ///
+    /// Inline data follows the alignment padding, which is skipped. Referenced
+    /// data must end before `start`. Returns the data offset and the offset
+    /// just past the value, checking that both lie within the file.
+    fn payload_location(
+        &mut self,
+        reference: Option<u64>,
+        size: u64,
+        start: u64,
+    ) -> io::Result<(u64, u64)> {
+        let position = self.position()?;
+        let checksum_size = self.layout.checksum_size() as u64;
+        if let Some(offset) = reference {
+            if offset.checked_add(size).is_none_or(|end| end > start) {
+                return Err(invalid(BlobfigError::InvalidReference(offset)));
+            }
+            let end = position + checksum_size;
+            if end > self.len {
+                return Err(invalid("Payload exceeds file size"));
+            }
+            return Ok((offset, end));
+        }
+
+        let alignment = self.layout.alignment;
+        let size = size.saturating_add(checksum_size);
+        let data_offset = position
+            .checked_add((alignment - position % alignment) % alignment)
+            .filter(|start| start.checked_add(size).is_some_and(|end| end <= self.len))
+            .ok_or_else(|| invalid("Payload exceeds file size"))?;
+        self.seek(data_offset)?;
+        Ok((data_offset, data_offset + size))
+    }
+
+    /// Read the layout fields following the shape of an `ndim` Array
+    fn read_array_layout(&mut self, ndim: usize) -> io::Result<ArrayLayout> {
+        if !self.layout.array_layout {
+            return Ok(ArrayLayout::default());
+        }
+        let flags = self.read_u8()?;
+        if flags & !(LAYOUT_FORTRAN | LAYOUT_STRIDES | LAYOUT_AXIS_NAMES) != 0 {
+            return Err(invalid(BlobfigError::InvalidArrayLayoutFlags(flags)));
+        }
+        let order = match flags & LAYOUT_FORTRAN {
+            0 => Order::C,
+            _ => Order::F,
+        };
+        let strides = match flags & LAYOUT_STRIDES {
+            0 => None,
+            _ => Some(
+                (0..ndim)
+                    .map(|_| self.read_array().map(u64::from_le_bytes))
+                    .collect::<io::Result<Vec<_>>>()?,
+            ),
+        };
+        let axis_names = match flags & LAYOUT_AXIS_NAMES {
+            0 => None,
+            _ => Some(
+                (0..ndim)
+                    .map(|_| {
+                        let len = self.read_u8()?;
+                        self.read_utf8(len as u64)
+                    })
+                    .collect::<io::Result<Vec<_>>>()?,
+            ),
+        };
+        Ok(ArrayLayout {
+            order,
+            strides,
+            axis_names,
+        })
+    }
+
+    /// Read the codec fields preceding an Array or File data size
+    fn read_compression(&mut self) -> io::Result<Option<Compressed>> {
+        if !self.layout.compression {
+            return Ok(None);
+        }
+        let codec = match self.read_u8()? {
+            0 => return Ok(None),
+            byte => Compression::from_u8(byte)
+                .ok_or_else(|| invalid(BlobfigError::InvalidCompression(byte)))?,
+        };
+        let filters = self.read_u8()?;
+        let filters = Filters::from_u8(filters)
+            .ok_or_else(|| invalid(BlobfigError::InvalidFilters(filters)))?;
+        let raw_size = u64::from_le_bytes(self.read_array()?);
+        Ok(Some(Compressed {
+            codec,
+            filters,
+            raw_size,
+        }))
+    }
+
+    /// Read the cipher fields preceding an Array or File data size
+    fn read_encryption(&mut self) -> io::Result<Option<Encrypted>> {
+        if !self.layout.encryption {
+            return Ok(None);
+        }
+        let cipher = match self.read_u8()? {
+            0 => return Ok(None),
+            byte => {
+                Cipher::from_u8(byte).ok_or_else(|| invalid(BlobfigError::InvalidCipher(byte)))?
+            }
+        };
+        let key_id_len = self.read_u8()?;
+        let key_id = self.read_utf8(key_id_len as u64)?;
+        let nonce = self.read_array()?;
+        Ok(Some(Encrypted {
+            cipher,
+            key_id,
+            nonce,
+        }))
+    }
+
+    /// Read the storage fields preceding an Array or File data size
+    ///
+    /// Returns the offset of the referenced data, if any.
+    fn read_storage(&mut self) -> io::Result<Option<u64>> {
+        if !self.layout.dedup {
+            return Ok(None);
+        }
+        match self.read_u8()? {
+            STORAGE_INLINE => Ok(None),
+            STORAGE_REFERENCE => Ok(Some(u64::from_le_bytes(self.read_array()?))),
+            byte => Err(invalid(BlobfigError::InvalidStorage(byte))),
+        }
+    }
+
+    fn read_key(&mut self) -> io::Result<String> {
+        let len = u16::from_le_bytes(self.read_array()?);
+        self.read_utf8(len as u64)
+    }
+
+    fn read_utf8(&mut self, len: u64) -> io::Result<String> {
+        let bytes = self.read_bytes(len)?;
+        String::from_utf8(bytes).map_err(|_| invalid("Invalid UTF-8 in string"))
+    }
+
+    fn read_bytes(&mut self, len: u64) -> io::Result<Vec<u8>> {
+        if self.position()?.saturating_add(len) > self.len {
+            return Err(invalid("String exceeds file size"));
+        }
+        let mut bytes = vec![0u8; len as usize];
+        self.inner.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.inner.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn position(&mut self) -> io::Result<u64> {
+        self.inner.stream_position()
+    }
+
+    fn seek(&mut self, offset: u64) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(offset)).map(|_| ())
+    }
+}
+
+fn invalid(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Array, File, Value};
+    use crate::writer::{self, WriteOptions};
+    use std::io::Cursor;
+
+    fn sample() -> Value {
+        Value::Object(vec![
+            ("name".into(), Value::String("test".into())),
+            (
+                "items".into(),
+                Value::List(vec![Value::Int(1), Value::Float(0.5)]),
+            ),
+            (
+                "model".into(),
+                Value::Object(vec![
+                    (
+                        "weights".into(),
+                        Value::Array(Array::new(DType::U8, vec![2, 2], vec![1, 2, 3, 4])),
+                    ),
+                    (
+                        "vocab".into(),
+                        Value::File(File::from_bytes("text/plain", b"a\nb\n".to_vec())),
+                    ),
+                ]),
+            ),
+            ("enabled".into(), Value::Bool(true)),
+        ])
+    }
+
+    fn reader(options: WriteOptions) -> Reader<Cursor<Vec<u8>>> {
+        let bytes = writer::to_bytes_with(sample(), options).unwrap();
+        Reader::new(Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn get_scalars() {
+        let mut reader = reader(WriteOptions::default());
+        assert_eq!(reader.get("name").unwrap().unwrap().as_str(), Some("test"));
+        assert_eq!(
+            reader.get("enabled").unwrap().unwrap().as_bool(),
+            Some(true)
+        );
+        assert!(reader.get("missing").unwrap().is_none());
+        assert!(reader.get("name/nested").unwrap().is_none());
+    }
+
+    #[test]
+    fn payloads_are_bounded() {
+        for options in [
+            WriteOptions::default(),
+            WriteOptions::unaligned(),
+            WriteOptions::unaligned().with_checksums(),
+        ] {
+            let mut reader = reader(options);
+            let weights = reader.get("model/weights").unwrap().unwrap();
+            let array = weights.as_array().unwrap();
+            assert_eq!(array.dtype, DType::U8);
+            assert_eq!(array.shape, vec![2, 2]);
+
+            let mut data = Vec::new();
+            let mut payload = reader.payload(&weights).unwrap().unwrap();
+            payload.read_to_end(&mut data).unwrap();
+            assert_eq!(data, [1, 2, 3, 4]);
+
+            let vocab = reader.get("model/vocab").unwrap().unwrap();
+            assert_eq!(vocab.as_file().unwrap().mimetype, "text/plain");
+            let mut text = String::new();
+            let mut payload = reader.payload(&vocab).unwrap().unwrap();
+            payload.read_to_string(&mut text).unwrap();
+            assert_eq!(text, "a\nb\n");
+        }
+    }
+
+    #[test]
+    fn entries_and_items() {
+        let mut reader = reader(WriteOptions::default());
+        let root = reader.root().unwrap();
+        let entries = reader.entries(root.as_object().unwrap()).unwrap();
+        let keys: Vec<_> = entries.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["name", "items", "model", "enabled"]);
+
+        let items = reader.items(entries[1].1.as_list().unwrap()).unwrap();
+        assert_eq!(items[0].as_int(), Some(1));
+        assert_eq!(items[1].as_float(), Some(0.5));
+    }
+
+    #[test]
+    fn truncated_file_is_invalid() {
+        let mut bytes = writer::to_bytes_with(sample(), WriteOptions::unaligned()).unwrap();
+        bytes.truncate(bytes.len() - 8);
+        let mut reader = Reader::new(Cursor::new(bytes)).unwrap();
+        let err = reader.get("model/vocab").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn invalid_magic() {
+        let err = Reader::new(Cursor::new(vec![0u8; HEADER_SIZE])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
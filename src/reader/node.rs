@@ -0,0 +1,304 @@
+//! Value headers decoded by the streaming reader
+
+use crate::types::{
+    ArrayLayout, Compressed, DType, Encrypted, SparseFormat, SparseIndices, StringArray, Timestamp,
+    ValueTag, grid_shape, num_chunks,
+};
+
+/// A value read from a stream
+///
+/// Scalars, strings, string arrays and raw bytes are owned. Arrays and Files carry the location of
+/// their payload, which is read through [`Reader::payload`](super::Reader::payload).
+/// Objects and Lists carry the location of their children.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(ArrayNode),
+    File(FileNode),
+    Object(ContainerNode),
+    List(ContainerNode),
+    /// Slash path of another value in the same document
+    Ref(String),
+    /// Explicitly unset value
+    Null,
+    UInt(u64),
+    Bytes(Vec<u8>),
+    Timestamp(Timestamp),
+    StringArray(StringArray),
+    Sparse(Box<SparseNode>),
+    ChunkedArray(ChunkedNode),
+}
+
+impl Node {
+    /// Get the tag for this value
+    pub fn tag(&self) -> ValueTag {
+        match self {
+            Node::Bool(_) => ValueTag::Bool,
+            Node::Int(_) => ValueTag::Int,
+            Node::Float(_) => ValueTag::Float,
+            Node::String(_) => ValueTag::String,
+            Node::Array(_) => ValueTag::Array,
+            Node::File(_) => ValueTag::File,
+            Node::Object(_) => ValueTag::Object,
+            Node::List(_) => ValueTag::List,
+            Node::Ref(_) => ValueTag::Ref,
+            Node::Null => ValueTag::Null,
+            Node::UInt(_) => ValueTag::UInt,
+            Node::Bytes(_) => ValueTag::Bytes,
+            Node::Timestamp(_) => ValueTag::Timestamp,
+            Node::StringArray(_) => ValueTag::StringArray,
+            Node::Sparse(_) => ValueTag::Sparse,
+            Node::ChunkedArray(_) => ValueTag::ChunkedArray,
+        }
+    }
+
+    /// Try to get as bool
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Node::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Try to get as i64
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Node::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Try to get as u64
+    pub fn as_uint(&self) -> Option<u64> {
+        match self {
+            Node::UInt(u) => Some(*u),
+            _ => None,
+        }
+    }
+
+    /// Try to get as f64
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Node::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Try to get as string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Node::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Try to get as raw bytes
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Node::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Try to get as timestamp
+    pub fn as_timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Node::Timestamp(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    /// Whether this is Null
+    pub fn is_null(&self) -> bool {
+        matches!(self, Node::Null)
+    }
+
+    /// Try to get as string array
+    pub fn as_string_array(&self) -> Option<&StringArray> {
+        match self {
+            Node::StringArray(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Try to get as array
+    pub fn as_array(&self) -> Option<&ArrayNode> {
+        match self {
+            Node::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Try to get as sparse tensor
+    pub fn as_sparse(&self) -> Option<&SparseNode> {
+        match self {
+            Node::Sparse(s) => Some(s.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Try to get as chunked array
+    pub fn as_chunked_array(&self) -> Option<&ChunkedNode> {
+        match self {
+            Node::ChunkedArray(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Try to get as file
+    pub fn as_file(&self) -> Option<&FileNode> {
+        match self {
+            Node::File(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Try to get as object
+    pub fn as_object(&self) -> Option<&ContainerNode> {
+        match self {
+            Node::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+
+    /// Try to get as list
+    pub fn as_list(&self) -> Option<&ContainerNode> {
+        match self {
+            Node::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Try to get the target path of a reference
+    pub fn as_ref_path(&self) -> Option<&str> {
+        match self {
+            Node::Ref(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Byte range of the Array or File payload, if any
+    pub fn payload_range(&self) -> Option<(u64, u64)> {
+        match self {
+            Node::Array(a) => Some((a.data_offset, a.data_size)),
+            Node::File(f) => Some((f.data_offset, f.size)),
+            _ => None,
+        }
+    }
+}
+
+/// Array header with the location of its data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayNode {
+    pub dtype: DType,
+    pub shape: Vec<u64>,
+    /// Element order, strides and axis names
+    pub layout: ArrayLayout,
+    /// Absolute offset of the first data byte
+    pub data_offset: u64,
+    /// Data size in bytes (as stored)
+    pub data_size: u64,
+    /// How the data is stored: None for raw element bytes
+    pub compression: Option<Compressed>,
+    /// Encryption of the (compressed) data, if any
+    pub encryption: Option<Encrypted>,
+    /// Absolute offset just past the value
+    pub(crate) end: u64,
+}
+
+/// Sparse tensor header with the locations of its arrays
+///
+/// Read the arrays with [`Reader::array_payload`](super::Reader::array_payload).
+/// Their shapes and dtypes are checked, but the indices are not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseNode {
+    /// Shape of the dense tensor
+    pub shape: Vec<u64>,
+    pub indices: SparseIndices<ArrayNode>,
+    /// `[nnz]` stored values
+    pub values: ArrayNode,
+}
+
+impl SparseNode {
+    /// Format of the indices
+    pub fn format(&self) -> SparseFormat {
+        self.indices.format()
+    }
+
+    /// Dtype of the tensor
+    pub fn dtype(&self) -> DType {
+        self.values.dtype
+    }
+}
+
+/// Chunked array header with the location of its offset table
+///
+/// Read single chunks with [`Reader::chunk`](super::Reader::chunk).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkedNode {
+    pub dtype: DType,
+    pub shape: Vec<u64>,
+    /// Shape of a full chunk; chunks at the upper edges are clipped
+    pub chunk_shape: Vec<u64>,
+    /// Absolute offset of the chunk offset table
+    pub(crate) table_offset: u64,
+}
+
+impl ChunkedNode {
+    /// Number of chunks along each dimension
+    pub fn grid(&self) -> Vec<u64> {
+        grid_shape(&self.shape, &self.chunk_shape)
+    }
+
+    /// Total number of chunks
+    pub fn num_chunks(&self) -> u64 {
+        num_chunks(&self.grid())
+    }
+
+    /// Absolute offset of the first chunk
+    pub(crate) fn chunks_offset(&self) -> u64 {
+        self.table_offset + 8 * self.num_chunks()
+    }
+}
+
+/// File header with the location of its data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileNode {
+    pub mimetype: String,
+    /// Absolute offset of the first data byte
+    pub data_offset: u64,
+    /// Data size in bytes (as stored)
+    pub size: u64,
+    /// How the data is stored: None for the raw file contents
+    pub compression: Option<Compressed>,
+    /// Encryption of the (compressed) data, if any
+    pub encryption: Option<Encrypted>,
+    /// Absolute offset just past the value
+    pub(crate) end: u64,
+}
+
+/// Object or List header with the location of its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContainerNode {
+    /// Number of entries or items
+    pub len: u32,
+    /// Absolute offset of the first child
+    pub(crate) start: u64,
+    /// Absolute offset just past the container, if length-prefixed
+    pub(crate) end: Option<u64>,
+}
+
+impl ContainerNode {
+    /// Number of entries or items
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Whether the container has no children
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
@@ -0,0 +1,102 @@
+//! Bounded reader over an Array or File payload
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Reads one payload byte range out of the underlying stream
+///
+/// Reads stop at the end of the payload, and seeks are relative to its
+/// first byte.
+#[derive(Debug)]
+pub struct Payload<'r, R> {
+    inner: &'r mut R,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<'r, R: Read + Seek> Payload<'r, R> {
+    pub(crate) fn new(inner: &'r mut R, start: u64, len: u64) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            inner,
+            start,
+            len,
+            pos: 0,
+        })
+    }
+
+    /// Payload size in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the payload is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes left to read
+    pub fn remaining(&self) -> u64 {
+        self.len.saturating_sub(self.pos)
+    }
+}
+
+impl<R: Read + Seek> Read for Payload<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = self.remaining().min(buf.len() as u64) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+        let n = self.inner.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for Payload<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        };
+        let target = target.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        // Seeking past the end is allowed, reads there return 0 bytes
+        self.inner
+            .seek(SeekFrom::Start(self.start.saturating_add(target)))?;
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_only_the_payload_range() {
+        let mut stream = Cursor::new(b"headerPAYLOADtrailer".to_vec());
+        let mut payload = Payload::new(&mut stream, 6, 7).unwrap();
+        let mut out = Vec::new();
+        payload.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"PAYLOAD");
+    }
+
+    #[test]
+    fn seeks_relative_to_payload() {
+        let mut stream = Cursor::new(b"headerPAYLOADtrailer".to_vec());
+        let mut payload = Payload::new(&mut stream, 6, 7).unwrap();
+        assert_eq!(payload.seek(SeekFrom::End(-4)).unwrap(), 3);
+
+        let mut out = String::new();
+        payload.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "LOAD");
+        assert!(payload.seek(SeekFrom::Current(-10)).is_err());
+    }
+}
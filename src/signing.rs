@@ -0,0 +1,227 @@
+//! Ed25519 signatures for blobfig documents
+//!
+//! [`sign`] appends a signature trailer to a serialized document and
+//! [`sign_detached`] returns the signature separately. [`parse_signed`] and
+//! [`parse_signed_detached`] refuse to decode anything unless the signature
+//! verifies against one of the trusted keys.
+//!
+//! Enable with the `signing` feature flag.
+
+use crate::error::{BlobfigError, ParseError};
+use crate::parser;
+use crate::types::{
+    HEADER_SIZE, KeyId, SIGNATURE_MAGIC, SIGNATURE_SIZE, TRAILER_FOOTER_SIZE, ValueView,
+};
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+
+pub use ed25519_dalek::Signature;
+
+impl KeyId {
+    /// ID of the given public key
+    pub fn of(key: &VerifyingKey) -> Self {
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&key.as_bytes()[..8]);
+        KeyId(id)
+    }
+}
+
+/// Signature over a whole document, stored apart from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetachedSignature {
+    /// ID of the key that made the signature
+    pub key_id: KeyId,
+    pub signature: Signature,
+}
+
+impl DetachedSignature {
+    /// Encode as key ID followed by the signature
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_SIZE] {
+        let mut bytes = [0u8; SIGNATURE_SIZE];
+        bytes[..8].copy_from_slice(&self.key_id.0);
+        bytes[8..].copy_from_slice(&self.signature.to_bytes());
+        bytes
+    }
+
+    /// Decode from the representation written by [`DetachedSignature::to_bytes`]
+    pub fn from_bytes(bytes: &[u8; SIGNATURE_SIZE]) -> Self {
+        let (key_id, signature) = bytes.split_at(8);
+        Self {
+            key_id: KeyId(key_id.try_into().unwrap()),
+            signature: Signature::from_slice(signature).unwrap(),
+        }
+    }
+
+    /// Check the signature over `bytes` with the trusted key it names
+    pub fn verify(&self, bytes: &[u8], trusted_keys: &[VerifyingKey]) -> Result<(), BlobfigError> {
+        let key = trusted_keys
+            .iter()
+            .find(|key| KeyId::of(key) == self.key_id)
+            .ok_or(BlobfigError::UntrustedKey(self.key_id))?;
+        key.verify_strict(bytes, &self.signature)
+            .map_err(|_| BlobfigError::InvalidSignature)
+    }
+}
+
+/// Sign a serialized document without modifying it
+pub fn sign_detached(bytes: &[u8], key: &SigningKey) -> DetachedSignature {
+    DetachedSignature {
+        key_id: KeyId::of(&key.verifying_key()),
+        signature: key.sign(bytes),
+    }
+}
+
+/// Append a signature trailer to a serialized document
+///
+/// The signature covers every byte already in `bytes`, including any path
+/// index or checksum trailers, so sign only once the document is complete.
+pub fn sign(bytes: &mut Vec<u8>, key: &SigningKey) {
+    let signature = sign_detached(bytes, key);
+    let start = bytes.len() as u64;
+    bytes.extend_from_slice(&signature.to_bytes());
+    bytes.extend_from_slice(&start.to_le_bytes());
+    bytes.extend_from_slice(SIGNATURE_MAGIC);
+}
+
+/// Parse a document signed with [`sign`], if one of `trusted_keys` signed it
+///
+/// Nothing is decoded before the signature verifies. The returned view covers
+/// only the signed bytes.
+pub fn parse_signed<'a>(
+    bytes: &'a [u8],
+    trusted_keys: &[VerifyingKey],
+) -> Result<ValueView<'a>, ParseError> {
+    let (signed, signature) = split_signature(bytes)?;
+    signature
+        .verify(signed, trusted_keys)
+        .map_err(|e| ParseError::new(e, signed.len()))?;
+    parser::parse(signed)
+}
+
+/// Parse a document whose signature was made with [`sign_detached`]
+pub fn parse_signed_detached<'a>(
+    bytes: &'a [u8],
+    signature: &DetachedSignature,
+    trusted_keys: &[VerifyingKey],
+) -> Result<ValueView<'a>, ParseError> {
+    signature
+        .verify(bytes, trusted_keys)
+        .map_err(|e| ParseError::new(e, 0))?;
+    parser::parse(bytes)
+}
+
+/// Split a signed document into the signed bytes and their signature
+///
+/// The signature trailer must be the last thing in the file.
+fn split_signature(bytes: &[u8]) -> Result<(&[u8], DetachedSignature), ParseError> {
+    let trailer_size = SIGNATURE_SIZE + TRAILER_FOOTER_SIZE;
+    if bytes.len() < HEADER_SIZE + trailer_size || !bytes.ends_with(SIGNATURE_MAGIC) {
+        return Err(ParseError::new(BlobfigError::MissingSignature, bytes.len()));
+    }
+
+    let start = bytes.len() - trailer_size;
+    let footer = start + SIGNATURE_SIZE;
+    let recorded = u64::from_le_bytes(bytes[footer..footer + 8].try_into().unwrap());
+    if recorded != start as u64 {
+        return Err(ParseError::new(
+            BlobfigError::InvalidTrailerStart(recorded),
+            footer,
+        ));
+    }
+
+    let signature = DetachedSignature::from_bytes(bytes[start..footer].try_into().unwrap());
+    Ok((&bytes[..start], signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+    use crate::writer::{self, WriteOptions};
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn document() -> Vec<u8> {
+        let value = Value::Object(vec![("version".into(), Value::Int(3))]);
+        writer::to_bytes(value).unwrap()
+    }
+
+    #[test]
+    fn embedded_roundtrip() {
+        let mut bytes = document();
+        sign(&mut bytes, &key(1));
+
+        let trusted = [key(2).verifying_key(), key(1).verifying_key()];
+        let parsed = parse_signed(&bytes, &trusted).unwrap();
+        assert_eq!(parsed.get("version").unwrap().as_int(), Some(3));
+
+        // Signed files still parse without verification
+        assert!(parser::parse(&bytes).is_ok());
+    }
+
+    #[test]
+    fn rejects_untrusted_key() {
+        let mut bytes = document();
+        sign(&mut bytes, &key(1));
+
+        let err = parse_signed(&bytes, &[key(2).verifying_key()]).unwrap_err();
+        assert_eq!(
+            err.kind,
+            BlobfigError::UntrustedKey(KeyId::of(&key(1).verifying_key()))
+        );
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let trusted = [key(1).verifying_key()];
+        let mut bytes = document();
+        sign(&mut bytes, &key(1));
+
+        let version = bytes.windows(7).position(|w| w == b"version").unwrap();
+        bytes[version] = b'V';
+        let err = parse_signed(&bytes, &trusted).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidSignature);
+
+        // Unsigned and truncated files
+        let err = parse_signed(&document(), &trusted).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::MissingSignature);
+        let err = parse_signed(&bytes[..bytes.len() - 1], &trusted).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::MissingSignature);
+
+        // The signature must be the last trailer
+        let footer = bytes.len() - 16;
+        bytes[footer..footer + 8].copy_from_slice(&7u64.to_le_bytes());
+        let err = parse_signed(&bytes, &trusted).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidTrailerStart(7));
+    }
+
+    #[test]
+    fn detached_roundtrip() {
+        let bytes = document();
+        let signature = sign_detached(&bytes, &key(1));
+        let decoded = DetachedSignature::from_bytes(&signature.to_bytes());
+        assert_eq!(decoded, signature);
+
+        let trusted = [key(1).verifying_key()];
+        assert!(parse_signed_detached(&bytes, &decoded, &trusted).is_ok());
+
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        let err = parse_signed_detached(&tampered, &decoded, &trusted).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::InvalidSignature);
+    }
+
+    #[test]
+    fn signature_covers_other_trailers() {
+        let value = Value::Object(vec![("rate".into(), Value::Int(16000))]);
+        let options = WriteOptions::default().with_path_index().with_checksums();
+        let mut bytes = writer::to_bytes_with(value, options).unwrap();
+        sign(&mut bytes, &key(1));
+
+        assert!(parse_signed(&bytes, &[key(1).verifying_key()]).is_ok());
+        assert!(parser::parse_verified(&bytes).is_ok());
+        let index = parser::parse_index(&bytes).unwrap();
+        assert_eq!(index.get("rate").unwrap().unwrap().as_int(), Some(16000));
+    }
+}
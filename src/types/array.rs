@@ -1,6 +1,20 @@
 //! Typed array types
 
 use super::DType;
+use super::array_layout::{ArrayLayout, Order};
+use super::checksum::verify_checksum;
+use super::compression::{
+    Compressed, Compression, DEFAULT_MAX_DECOMPRESSED_SIZE, check_filters, compress, decompress,
+};
+use super::encryption::{
+    Cipher, Encrypted, KeyProvider, TAG_SIZE, array_metadata, decrypt_payload, encrypt,
+    encrypt_error,
+};
+use super::filter::Filters;
+use super::quant::dequantize;
+use crate::error::BlobfigError;
+use std::borrow::Cow;
+use std::io;
 
 /// Owned typed array (for building/writing)
 #[derive(Debug, Clone)]
@@ -8,21 +22,170 @@ pub struct Array {
     pub dtype: DType,
     pub shape: Vec<u64>,
     pub data: Vec<u8>,
+    /// How `data` is stored: None for raw element bytes
+    pub compression: Option<Compressed>,
+    /// Encryption of the (compressed) data, if any
+    pub encryption: Option<Encrypted>,
+    /// Element order, strides and axis names
+    pub layout: ArrayLayout,
 }
 
 impl Array {
+    /// Create an array without checking `data` against `shape`
+    ///
+    /// Inconsistent arrays are rejected when written; use [`Array::try_new`]
+    /// to catch them here instead.
     pub fn new(dtype: DType, shape: Vec<u64>, data: Vec<u8>) -> Self {
-        Self { dtype, shape, data }
+        Self {
+            dtype,
+            shape,
+            data,
+            compression: None,
+            encryption: None,
+            layout: ArrayLayout::default(),
+        }
+    }
+
+    /// Create an array, checking that `data` holds exactly `shape` elements
+    pub fn try_new(dtype: DType, shape: Vec<u64>, data: Vec<u8>) -> Result<Self, BlobfigError> {
+        let array = Self::new(dtype, shape, data);
+        array.validate()?;
+        Ok(array)
+    }
+
+    /// Store the elements in `order` instead of C order
+    pub fn with_order(mut self, order: Order) -> Self {
+        self.layout.order = order;
+        self
+    }
+
+    /// Store the elements at explicit byte `strides`, one per axis
+    pub fn with_strides(mut self, strides: Vec<u64>) -> Self {
+        self.layout.strides = Some(strides);
+        self
+    }
+
+    /// Name the axes, one name per axis
+    pub fn with_axis_names<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.layout.axis_names = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Index of the axis called `name`
+    pub fn axis_index(&self, name: &str) -> Option<usize> {
+        self.layout.axis_index(name)
+    }
+
+    /// Total number of elements (None if the product overflows)
+    pub fn num_elements(&self) -> Option<u64> {
+        num_elements(&self.shape)
+    }
+
+    /// Expected data size in bytes (None if it overflows)
+    pub fn expected_size(&self) -> Option<u64> {
+        expected_size(self.dtype, &self.shape)
+    }
+
+    /// Check that the (decrypted and decompressed) data size matches shape
+    /// and dtype, that the filters and layout fit the dtype and shape, and
+    /// that uncompressed Bool elements are 0 or 1
+    pub fn validate(&self) -> Result<(), BlobfigError> {
+        let size = raw_size(self.data.len() as u64, self.compression, &self.encryption);
+        check_data_size(self.dtype, &self.shape, size)?;
+        if let Some(compressed) = self.compression {
+            check_filters(compressed.filters, self.dtype.element_size())?;
+        }
+        self.layout.validate(self.dtype, &self.shape)?;
+        if self.compression.is_none() && self.encryption.is_none() {
+            check_elements(self.dtype, &self.data)?;
+        }
+        Ok(())
+    }
+
+    /// Compress the data with `codec` so it is stored compressed
+    ///
+    /// The data is filtered first with [`Filters::for_dtype`]. Arrays that
+    /// are already compressed are returned unchanged.
+    pub fn compress(self, codec: Compression) -> io::Result<Self> {
+        let filters = Filters::for_dtype(self.dtype);
+        self.compress_with(codec, filters)
+    }
+
+    /// Apply `filters` to the data and compress it with `codec`
+    ///
+    /// Arrays that are already compressed are returned unchanged. Encrypted
+    /// arrays cannot be compressed.
+    pub fn compress_with(mut self, codec: Compression, filters: Filters) -> io::Result<Self> {
+        if self.compression.is_none() {
+            if self.encryption.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    BlobfigError::Encrypted,
+                ));
+            }
+            let raw_size = self.data.len() as u64;
+            self.data = compress(codec, filters, self.dtype.element_size(), &self.data)?;
+            self.compression = Some(Compressed {
+                codec,
+                filters,
+                raw_size,
+            });
+        }
+        Ok(self)
+    }
+
+    /// Encrypt the (compressed) data with the key named `key_id`
+    ///
+    /// Compress first: encrypted data does not compress. Set the layout
+    /// first too, as it is authenticated. Arrays that are already encrypted
+    /// are returned unchanged.
+    pub fn encrypt(
+        mut self,
+        cipher: Cipher,
+        key_id: &str,
+        keys: &impl KeyProvider,
+    ) -> io::Result<Self> {
+        if self.encryption.is_none() {
+            let metadata = array_metadata(self.dtype, &self.shape, &self.layout);
+            let (encrypted, data) = encrypt(
+                cipher,
+                key_id,
+                keys,
+                &metadata,
+                self.compression,
+                &self.data,
+            )
+            .map_err(encrypt_error)?;
+            self.data = data;
+            self.encryption = Some(encrypted);
+        }
+        Ok(self)
     }
 
-    /// Total number of elements
-    pub fn num_elements(&self) -> u64 {
-        self.shape.iter().product()
+    /// Raw element bytes, decompressed and unfiltered if needed
+    ///
+    /// Fails with [`BlobfigError::Encrypted`] for encrypted arrays, and with
+    /// [`BlobfigError::InvalidBool`] for Bool elements other than 0 or 1.
+    pub fn decompressed(&self) -> Result<Cow<'_, [u8]>, BlobfigError> {
+        if self.encryption.is_some() {
+            return Err(BlobfigError::Encrypted);
+        }
+        let element_size = self.dtype.element_size();
+        let data = decompress(
+            &self.data,
+            self.compression,
+            element_size,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+        )?;
+        check_elements(self.dtype, &data)?;
+        Ok(data)
     }
 
-    /// Expected data size in bytes
-    pub fn expected_size(&self) -> u64 {
-        self.num_elements() * self.dtype.element_size() as u64
+    /// Elements of a quantized array as f32, scaled per block
+    ///
+    /// Fails with [`BlobfigError::NotQuantized`] for other dtypes.
+    pub fn dequantize_f32(&self) -> Result<Vec<f32>, BlobfigError> {
+        dequantize(self.dtype, &self.shape, &self.decompressed()?)
     }
 }
 
@@ -32,17 +195,86 @@ pub struct ArrayView<'a> {
     pub dtype: DType,
     pub shape: Vec<u64>,
     pub data: &'a [u8],
+    /// Checksum recorded after the data, if the file was written with checksums
+    pub checksum: Option<u32>,
+    /// How `data` is stored: None for raw element bytes
+    pub compression: Option<Compressed>,
+    /// Encryption of the (compressed) data, if any
+    pub encryption: Option<Encrypted>,
+    /// Element order, strides and axis names
+    pub layout: ArrayLayout,
 }
 
 impl<'a> ArrayView<'a> {
-    /// Total number of elements
-    pub fn num_elements(&self) -> u64 {
-        self.shape.iter().product()
+    /// Raw element bytes, borrowed if the data is stored uncompressed
+    ///
+    /// Filters recorded with compressed data are undone. Compressed data
+    /// larger than [`DEFAULT_MAX_DECOMPRESSED_SIZE`] is refused; see
+    /// [`ArrayView::decompressed_with`].
+    pub fn decompressed(&self) -> Result<Cow<'a, [u8]>, BlobfigError> {
+        self.decompressed_with(DEFAULT_MAX_DECOMPRESSED_SIZE)
     }
 
-    /// Expected data size in bytes
-    pub fn expected_size(&self) -> u64 {
-        self.num_elements() * self.dtype.element_size() as u64
+    /// Raw element bytes, refusing to decompress more than `max_size` bytes
+    ///
+    /// Fails with [`BlobfigError::Encrypted`] for encrypted arrays, and with
+    /// [`BlobfigError::InvalidBool`] for Bool elements other than 0 or 1.
+    pub fn decompressed_with(&self, max_size: usize) -> Result<Cow<'a, [u8]>, BlobfigError> {
+        if self.encryption.is_some() {
+            return Err(BlobfigError::Encrypted);
+        }
+        let element_size = self.dtype.element_size();
+        let data = decompress(self.data, self.compression, element_size, max_size)?;
+        check_elements(self.dtype, &data)?;
+        Ok(data)
+    }
+
+    /// Raw element bytes, decrypted with a key from `keys` and decompressed
+    ///
+    /// Fails with [`BlobfigError::DecryptionFailed`] for a wrong key or if the
+    /// data, dtype, shape or layout were tampered with. Arrays that are not encrypted
+    /// are returned as by [`ArrayView::decompressed`].
+    pub fn decrypt(&self, keys: &impl KeyProvider) -> Result<Cow<'a, [u8]>, BlobfigError> {
+        let data = decrypt_payload(
+            self.data,
+            self.encryption.as_ref(),
+            keys,
+            || array_metadata(self.dtype, &self.shape, &self.layout),
+            self.compression,
+            self.dtype.element_size(),
+        )?;
+        check_elements(self.dtype, &data)?;
+        Ok(data)
+    }
+
+    /// Elements of a quantized array as f32, scaled per block
+    ///
+    /// Compressed data is decompressed first. Fails with
+    /// [`BlobfigError::NotQuantized`] for other dtypes.
+    pub fn dequantize_f32(&self) -> Result<Vec<f32>, BlobfigError> {
+        dequantize(self.dtype, &self.shape, &self.decompressed()?)
+    }
+
+    /// Check the data against its recorded checksum
+    ///
+    /// Hashes the whole payload, so call it only for the arrays you need.
+    pub fn verify(&self) -> Result<(), BlobfigError> {
+        verify_checksum(self.data, self.checksum)
+    }
+
+    /// Index of the axis called `name`
+    pub fn axis_index(&self, name: &str) -> Option<usize> {
+        self.layout.axis_index(name)
+    }
+
+    /// Total number of elements (None if the product overflows)
+    pub fn num_elements(&self) -> Option<u64> {
+        num_elements(&self.shape)
+    }
+
+    /// Expected data size in bytes (None if it overflows)
+    pub fn expected_size(&self) -> Option<u64> {
+        expected_size(self.dtype, &self.shape)
     }
 
     /// Convert to owned Array
@@ -51,6 +283,119 @@ impl<'a> ArrayView<'a> {
             dtype: self.dtype,
             shape: self.shape.clone(),
             data: self.data.to_vec(),
+            compression: self.compression,
+            encryption: self.encryption.clone(),
+            layout: self.layout.clone(),
         }
     }
 }
+
+/// Size of the data once decrypted and decompressed
+///
+/// Ciphertexts shorter than their tag count as empty; they fail to decrypt.
+pub(crate) fn raw_size(
+    stored_size: u64,
+    compression: Option<Compressed>,
+    encryption: &Option<Encrypted>,
+) -> u64 {
+    match (compression, encryption) {
+        (Some(compressed), _) => compressed.raw_size,
+        (None, Some(_)) => stored_size.saturating_sub(TAG_SIZE as u64),
+        (None, None) => stored_size,
+    }
+}
+
+fn num_elements(shape: &[u64]) -> Option<u64> {
+    shape.iter().try_fold(1u64, |n, &dim| n.checked_mul(dim))
+}
+
+pub(crate) fn expected_size(dtype: DType, shape: &[u64]) -> Option<u64> {
+    dtype.data_size(num_elements(shape)?)
+}
+
+/// Check that `actual` bytes is exactly the size of a `dtype` array of `shape`
+pub(crate) fn check_data_size(
+    dtype: DType,
+    shape: &[u64],
+    actual: u64,
+) -> Result<(), BlobfigError> {
+    let expected = expected_size(dtype, shape).ok_or(BlobfigError::ShapeOverflow)?;
+    if expected != actual {
+        return Err(BlobfigError::DataSizeMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+/// Index of the first invalid byte in the raw elements of a `dtype` array
+///
+/// Bool elements must be 0 or 1; every byte pattern is valid for the other
+/// dtypes.
+pub(crate) fn invalid_element(dtype: DType, data: &[u8]) -> Option<usize> {
+    match dtype {
+        DType::Bool => data.iter().position(|&byte| byte > 1),
+        _ => None,
+    }
+}
+
+/// Check that the raw elements of a `dtype` array are valid
+pub(crate) fn check_elements(dtype: DType, data: &[u8]) -> Result<(), BlobfigError> {
+    match invalid_element(dtype, data) {
+        Some(i) => Err(BlobfigError::InvalidBool(data[i])),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_new_checks_size() {
+        assert!(Array::try_new(DType::F32, vec![2, 3], vec![0; 24]).is_ok());
+        let err = Array::try_new(DType::F32, vec![2, 3], vec![0; 20]).unwrap_err();
+        assert_eq!(
+            err,
+            BlobfigError::DataSizeMismatch {
+                expected: 24,
+                actual: 20
+            }
+        );
+    }
+
+    #[test]
+    fn bool_elements_are_zero_or_one() {
+        assert!(Array::try_new(DType::Bool, vec![3], vec![0, 1, 1]).is_ok());
+        let err = Array::try_new(DType::Bool, vec![3], vec![0, 2, 1]).unwrap_err();
+        assert_eq!(err, BlobfigError::InvalidBool(2));
+
+        let array = Array::new(DType::Bool, vec![2], vec![1, 0xFF]);
+        assert_eq!(array.decompressed(), Err(BlobfigError::InvalidBool(0xFF)));
+    }
+
+    #[test]
+    fn scalar_shape_has_one_element() {
+        let array = Array::try_new(DType::I64, vec![], vec![0; 8]).unwrap();
+        assert_eq!(array.num_elements(), Some(1));
+    }
+
+    #[test]
+    fn huge_shapes_do_not_wrap() {
+        // 2^32 * 2^32 wraps to 0 elements with unchecked arithmetic
+        let array = Array::new(DType::U8, vec![1 << 32, 1 << 32], vec![]);
+        assert_eq!(array.num_elements(), None);
+        assert_eq!(array.validate(), Err(BlobfigError::ShapeOverflow));
+
+        let array = Array::new(DType::F64, vec![1 << 62], vec![]);
+        assert_eq!(array.num_elements(), Some(1 << 62));
+        assert_eq!(array.expected_size(), None);
+    }
+
+    #[test]
+    fn quantized_sizes_round_up_to_blocks() {
+        let size = |dtype, shape| expected_size(dtype, shape).unwrap();
+        assert_eq!(size(DType::I4, &[3, 5]), 8);
+        assert_eq!(size(DType::Q4_0, &[2, 32]), 2 * 18);
+        assert_eq!(size(DType::Q8_0, &[33]), 2 * 34);
+        assert_eq!(size(DType::Q8_0, &[0]), 0);
+    }
+}
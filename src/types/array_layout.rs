@@ -0,0 +1,207 @@
+//! Array element order, strides and axis names
+
+use super::DType;
+use super::array::expected_size;
+use crate::error::BlobfigError;
+
+/// Layout flag: elements are in Fortran (column-major) order
+pub(crate) const LAYOUT_FORTRAN: u8 = 1 << 0;
+
+/// Layout flag: explicit byte strides follow
+pub(crate) const LAYOUT_STRIDES: u8 = 1 << 1;
+
+/// Layout flag: axis names follow
+pub(crate) const LAYOUT_AXIS_NAMES: u8 = 1 << 2;
+
+/// Order of the elements of an array in its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    /// Row-major: the last index varies fastest
+    #[default]
+    C,
+    /// Column-major: the first index varies fastest
+    F,
+}
+
+/// How the elements of an array are laid out in its data, and the names of
+/// its axes
+///
+/// The default is C order without strides or names. Other layouts are only
+/// written to files with [`FLAG_ARRAY_LAYOUT`](super::FLAG_ARRAY_LAYOUT),
+/// which the writer sets when an array needs it. The data size is that of
+/// the dense array either way.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArrayLayout {
+    pub order: Order,
+    /// Byte distance between consecutive elements along each axis, which
+    /// takes precedence over `order`
+    pub strides: Option<Vec<u64>>,
+    /// Name of each axis, e.g. `["batch", "time", "mel"]`
+    pub axis_names: Option<Vec<String>>,
+}
+
+impl ArrayLayout {
+    /// Whether this is C order without strides or names
+    pub fn is_default(&self) -> bool {
+        *self == ArrayLayout::default()
+    }
+
+    /// Whether the elements are in C order without explicit strides
+    pub fn is_c_order(&self) -> bool {
+        self.order == Order::C && self.strides.is_none()
+    }
+
+    /// Byte strides of a `dtype` array of `shape` in this layout
+    ///
+    /// These are the explicit strides if there are any, and otherwise the
+    /// strides of a dense array in `order`. Quantized elements have no byte
+    /// strides of their own, so this is None for them.
+    pub fn byte_strides(&self, dtype: DType, shape: &[u64]) -> Option<Vec<u64>> {
+        let element_size = dtype.element_size()?;
+        if let Some(strides) = &self.strides {
+            return Some(strides.clone());
+        }
+        let mut strides = vec![0; shape.len()];
+        let mut stride = element_size as u64;
+        let mut axes: Vec<usize> = (0..shape.len()).collect();
+        if self.order == Order::C {
+            axes.reverse();
+        }
+        for axis in axes {
+            strides[axis] = stride;
+            stride = stride.saturating_mul(shape[axis]);
+        }
+        Some(strides)
+    }
+
+    /// Index of the axis called `name`
+    pub fn axis_index(&self, name: &str) -> Option<usize> {
+        self.axis_names.as_ref()?.iter().position(|n| n == name)
+    }
+
+    /// Flags byte written before the strides and names
+    pub(crate) fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.order == Order::F {
+            flags |= LAYOUT_FORTRAN;
+        }
+        if self.strides.is_some() {
+            flags |= LAYOUT_STRIDES;
+        }
+        if self.axis_names.is_some() {
+            flags |= LAYOUT_AXIS_NAMES;
+        }
+        flags
+    }
+
+    /// Check the layout against the dtype and shape of its array
+    ///
+    /// Strides and names need one entry per axis. Strides must be multiples
+    /// of the element size and stay within the data. Names must be unique and
+    /// at most 255 bytes long. Quantized arrays must be in C order.
+    pub fn validate(&self, dtype: DType, shape: &[u64]) -> Result<(), BlobfigError> {
+        let invalid = |reason| Err(BlobfigError::InvalidArrayLayout(reason));
+        if dtype.is_quantized() && !self.is_c_order() {
+            return invalid("quantized arrays must be in C order");
+        }
+        if let Some(strides) = &self.strides {
+            if strides.len() != shape.len() {
+                return invalid("strides must have one entry per axis");
+            }
+            // Quantized arrays with strides were refused above
+            let Some(element_size) = dtype.element_size() else {
+                return invalid("quantized arrays must be in C order");
+            };
+            let element_size = element_size as u64;
+            if strides.iter().any(|s| s % element_size != 0) {
+                return invalid("strides must be multiples of the element size");
+            }
+            let size = expected_size(dtype, shape).ok_or(BlobfigError::ShapeOverflow)?;
+            // Offset of the last element, if there is one
+            let last = shape
+                .iter()
+                .zip(strides)
+                .try_fold(0u64, |end, (&dim, &stride)| {
+                    let steps = dim.checked_sub(1)?;
+                    end.checked_add(steps.checked_mul(stride)?)
+                });
+            let fits = match last {
+                Some(last) => last
+                    .checked_add(element_size)
+                    .is_some_and(|end| end <= size),
+                None => shape.contains(&0),
+            };
+            if !fits {
+                return invalid("strides reach past the data");
+            }
+        }
+        if let Some(names) = &self.axis_names {
+            if names.len() != shape.len() {
+                return invalid("axis names must have one entry per axis");
+            }
+            if names.iter().any(|name| name.len() > u8::MAX as usize) {
+                return invalid("axis names must be at most 255 bytes long");
+            }
+            if names
+                .iter()
+                .enumerate()
+                .any(|(i, name)| names[..i].contains(name))
+            {
+                return invalid("axis names must be unique");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strides_follow_order() {
+        let c = ArrayLayout::default();
+        assert_eq!(c.byte_strides(DType::F32, &[2, 3, 4]).unwrap(), [48, 16, 4]);
+        assert_eq!(c.byte_strides(DType::Q8_0, &[64]), None);
+        let f = ArrayLayout {
+            order: Order::F,
+            ..ArrayLayout::default()
+        };
+        assert_eq!(f.byte_strides(DType::F32, &[2, 3, 4]).unwrap(), [4, 8, 24]);
+        assert_eq!(f.byte_strides(DType::F32, &[]).unwrap(), [0u64; 0]);
+    }
+
+    #[test]
+    fn strides_must_fit() {
+        let strided = |strides: Vec<u64>| ArrayLayout {
+            strides: Some(strides),
+            ..ArrayLayout::default()
+        };
+        assert!(strided(vec![4, 8]).validate(DType::F32, &[2, 3]).is_ok());
+        assert!(strided(vec![0, 4]).validate(DType::F32, &[2, 3]).is_ok());
+        assert!(strided(vec![64, 4]).validate(DType::F32, &[0, 3]).is_ok());
+        assert_eq!(
+            strided(vec![12, 8]).validate(DType::F32, &[2, 3]),
+            Err(BlobfigError::InvalidArrayLayout(
+                "strides reach past the data"
+            ))
+        );
+        assert!(strided(vec![4, 2]).validate(DType::F32, &[2, 3]).is_err());
+        assert!(strided(vec![4]).validate(DType::F32, &[2, 3]).is_err());
+        assert!(strided(vec![1]).validate(DType::I4, &[2]).is_err());
+    }
+
+    #[test]
+    fn axis_names_are_unique() {
+        let named = |names: &[&str]| ArrayLayout {
+            axis_names: Some(names.iter().map(|n| n.to_string()).collect()),
+            ..ArrayLayout::default()
+        };
+        let layout = named(&["time", "mel"]);
+        assert!(layout.validate(DType::F32, &[10, 80]).is_ok());
+        assert_eq!(layout.axis_index("mel"), Some(1));
+        assert_eq!(layout.axis_index("batch"), None);
+        assert!(named(&["x", "x"]).validate(DType::F32, &[1, 1]).is_err());
+        assert!(named(&["x"]).validate(DType::F32, &[1, 1]).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+//! Checksum layout
+//!
+//! When [`FLAG_CHECKSUMS`](super::FLAG_CHECKSUMS) is set, every Array and File
+//! payload is followed by the `u32` CRC-32C of its data, and the file ends
+//! with a trailer holding the CRC-32C of every byte before it:
+//!
+//! ```text
+//! u32 digest
+//! u64 trailer_offset
+//! [u8; 8] CHECKSUM_MAGIC
+//! ```
+//!
+//! The checksum trailer is written after the path index, so the digest
+//! covers it too. Only a signature trailer may follow.
+
+use crate::error::BlobfigError;
+
+/// Magic closing the checksum trailer
+pub const CHECKSUM_MAGIC: &[u8; 8] = b"BFCHKSUM";
+
+/// Size of one checksum in bytes
+pub const CHECKSUM_SIZE: usize = 4;
+
+/// Checksum of `data` as stored in the file (CRC-32C)
+pub fn checksum(data: &[u8]) -> u32 {
+    crc32c::crc32c(data)
+}
+
+/// Extend a checksum of earlier bytes with `data`
+pub fn checksum_append(checksum: u32, data: &[u8]) -> u32 {
+    crc32c::crc32c_append(checksum, data)
+}
+
+/// Check `data` against the checksum recorded for it
+pub(crate) fn verify_checksum(data: &[u8], recorded: Option<u32>) -> Result<(), BlobfigError> {
+    let expected = recorded.ok_or(BlobfigError::MissingChecksum)?;
+    let actual = checksum(data);
+    if actual != expected {
+        return Err(BlobfigError::ChecksumMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_value() {
+        // CRC-32C check value from RFC 3720
+        assert_eq!(checksum(b"123456789"), 0xE306_9283);
+    }
+
+    #[test]
+    fn append_matches_whole() {
+        let whole = checksum(b"hello world");
+        let split = checksum_append(checksum(b"hello "), b"world");
+        assert_eq!(split, whole);
+    }
+}
@@ -0,0 +1,516 @@
+//! Chunked arrays
+
+use super::array::expected_size;
+use super::{Array, ArrayLayout, ArrayView, DType};
+use crate::error::BlobfigError;
+use std::io;
+
+/// Source of the chunks of an array that is written chunk by chunk
+///
+/// Only one chunk is held in memory at a time, so arrays larger than memory
+/// can be written. Closures taking the chunk's grid position and shape
+/// implement this trait.
+pub trait ChunkProducer: Send {
+    /// Raw element bytes of the chunk at grid position `index`
+    ///
+    /// Called once per chunk, in row-major grid order, while the array is
+    /// written. `shape` is the shape of the chunk, which is clipped to the
+    /// array at its upper edges.
+    fn chunk(&mut self, index: &[u64], shape: &[u64]) -> io::Result<Vec<u8>>;
+}
+
+impl<F> ChunkProducer for F
+where
+    F: FnMut(&[u64], &[u64]) -> io::Result<Vec<u8>> + Send,
+{
+    fn chunk(&mut self, index: &[u64], shape: &[u64]) -> io::Result<Vec<u8>> {
+        self(index, shape)
+    }
+}
+
+/// Chunks of a chunked array - either in memory or from a producer
+pub enum ChunkData {
+    /// In-memory chunks in row-major grid order
+    Arrays(Vec<Array>),
+    /// Producer called for each chunk while writing
+    Producer(Box<dyn ChunkProducer>),
+}
+
+impl std::fmt::Debug for ChunkData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkData::Arrays(chunks) => f.debug_tuple("Arrays").field(&chunks.len()).finish(),
+            ChunkData::Producer(_) => f.write_str("Producer"),
+        }
+    }
+}
+
+/// Owned array split into a regular grid of chunks (for building/writing)
+///
+/// Every chunk is stored as an Array of the chunk shape, clipped to the
+/// array at its upper edges, and is aligned, compressed, encrypted,
+/// checksummed and deduplicated on its own. A table of chunk offsets
+/// precedes the chunks so a single one can be read without the others.
+#[derive(Debug)]
+pub struct ChunkedArray {
+    pub dtype: DType,
+    pub shape: Vec<u64>,
+    pub chunk_shape: Vec<u64>,
+    pub chunks: ChunkData,
+}
+
+impl ChunkedArray {
+    /// Create from in-memory chunks in row-major grid order
+    pub fn new(dtype: DType, shape: Vec<u64>, chunk_shape: Vec<u64>, chunks: Vec<Array>) -> Self {
+        Self {
+            dtype,
+            shape,
+            chunk_shape,
+            chunks: ChunkData::Arrays(chunks),
+        }
+    }
+
+    /// Create from a producer that is asked for each chunk while writing
+    ///
+    /// Produced chunks are written raw, since their encoded size has to be
+    /// known before they are produced.
+    pub fn from_producer(
+        dtype: DType,
+        shape: Vec<u64>,
+        chunk_shape: Vec<u64>,
+        producer: impl ChunkProducer + 'static,
+    ) -> Self {
+        Self {
+            dtype,
+            shape,
+            chunk_shape,
+            chunks: ChunkData::Producer(Box::new(producer)),
+        }
+    }
+
+    /// Split a dense array into chunks of `chunk_shape`
+    ///
+    /// Compressed arrays are decompressed first; the chunks are raw.
+    /// Quantized arrays cannot be split, as chunks may cut through blocks.
+    /// The dense array must be in C order; its axis names are not kept.
+    pub fn from_array(dense: &Array, chunk_shape: Vec<u64>) -> Result<Self, BlobfigError> {
+        dense.validate()?;
+        check_grid(&dense.shape, &chunk_shape)?;
+        let element_size = element_size(dense.dtype)?;
+        if !dense.layout.is_c_order() {
+            return Err(BlobfigError::InvalidChunkedArray(
+                "dense arrays must be in C order",
+            ));
+        }
+        let data = dense.decompressed()?;
+
+        let grid = grid_shape(&dense.shape, &chunk_shape);
+        let chunks = (0..num_chunks(&grid))
+            .map(|flat| {
+                let index = unravel(&grid, flat);
+                let (origin, extent) = chunk_bounds(&dense.shape, &chunk_shape, &index);
+                let mut chunk = Vec::new();
+                rows(
+                    &dense.shape,
+                    &origin,
+                    &extent,
+                    element_size,
+                    |offset, len| {
+                        chunk.extend_from_slice(&data[offset..offset + len]);
+                    },
+                );
+                Array::new(dense.dtype, extent, chunk)
+            })
+            .collect();
+        Ok(Self::new(
+            dense.dtype,
+            dense.shape.clone(),
+            chunk_shape,
+            chunks,
+        ))
+    }
+
+    /// Number of chunks along each dimension
+    pub fn grid(&self) -> Vec<u64> {
+        grid_shape(&self.shape, &self.chunk_shape)
+    }
+
+    /// Total number of chunks
+    pub fn num_chunks(&self) -> u64 {
+        num_chunks(&self.grid())
+    }
+
+    /// Shape of the chunk at grid position `index`, or None if out of bounds
+    pub fn chunk_shape_at(&self, index: &[u64]) -> Option<Vec<u64>> {
+        flatten(&self.grid(), index)?;
+        Some(chunk_bounds(&self.shape, &self.chunk_shape, index).1)
+    }
+
+    /// Check the grid, and the dtype, shape and data of in-memory chunks
+    pub fn validate(&self) -> Result<(), BlobfigError> {
+        check_grid(&self.shape, &self.chunk_shape)?;
+        let ChunkData::Arrays(chunks) = &self.chunks else {
+            return Ok(());
+        };
+        let grid = self.grid();
+        if chunks.len() as u64 != num_chunks(&grid) {
+            return Err(BlobfigError::InvalidChunkedArray(
+                "one chunk is needed per grid position",
+            ));
+        }
+        for (flat, chunk) in chunks.iter().enumerate() {
+            let index = unravel(&grid, flat as u64);
+            let chunk_layout = (chunk.dtype, chunk.shape.as_slice(), &chunk.layout);
+            check_chunk(
+                self.dtype,
+                &self.shape,
+                &self.chunk_shape,
+                &index,
+                chunk_layout,
+            )?;
+            chunk.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// View into a chunked array stored in the blob (zero-copy)
+///
+/// Every chunk header was read when parsing; the chunks' data is not
+/// touched until it is used.
+#[derive(Debug, Clone)]
+pub struct ChunkedArrayView<'a> {
+    pub dtype: DType,
+    pub shape: Vec<u64>,
+    pub chunk_shape: Vec<u64>,
+    chunks: Vec<ArrayView<'a>>,
+}
+
+impl<'a> ChunkedArrayView<'a> {
+    /// Create from chunks already checked against the grid
+    pub(crate) fn new(
+        dtype: DType,
+        shape: Vec<u64>,
+        chunk_shape: Vec<u64>,
+        chunks: Vec<ArrayView<'a>>,
+    ) -> Self {
+        Self {
+            dtype,
+            shape,
+            chunk_shape,
+            chunks,
+        }
+    }
+
+    /// Number of chunks along each dimension
+    pub fn grid(&self) -> Vec<u64> {
+        grid_shape(&self.shape, &self.chunk_shape)
+    }
+
+    /// Total number of chunks
+    pub fn num_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// The chunk at grid position `index`, or None if out of bounds
+    pub fn chunk(&self, index: &[u64]) -> Option<&ArrayView<'a>> {
+        let flat = flatten(&self.grid(), index)?;
+        self.chunks.get(flat as usize)
+    }
+
+    /// Iterate over the chunks in row-major grid order
+    pub fn chunks(&self) -> impl ExactSizeIterator<Item = &ArrayView<'a>> {
+        self.chunks.iter()
+    }
+
+    /// Assemble the chunks into one dense array
+    ///
+    /// Compressed chunks are decompressed; encrypted ones fail with
+    /// [`BlobfigError::Encrypted`]. Chunks of quantized arrays can only be
+    /// read one at a time.
+    pub fn to_dense(&self) -> Result<Array, BlobfigError> {
+        let element_size = element_size(self.dtype)?;
+        let size = expected_size(self.dtype, &self.shape).ok_or(BlobfigError::ShapeOverflow)?;
+        let size = usize::try_from(size).map_err(|_| BlobfigError::SizeOverflow(size))?;
+
+        let grid = self.grid();
+        let mut data = vec![0u8; size];
+        for (flat, chunk) in self.chunks.iter().enumerate() {
+            let index = unravel(&grid, flat as u64);
+            let (origin, extent) = chunk_bounds(&self.shape, &self.chunk_shape, &index);
+            let bytes = chunk.decompressed()?;
+            let mut read = 0;
+            rows(
+                &self.shape,
+                &origin,
+                &extent,
+                element_size,
+                |offset, len| {
+                    data[offset..offset + len].copy_from_slice(&bytes[read..read + len]);
+                    read += len;
+                },
+            );
+        }
+        Ok(Array::new(self.dtype, self.shape.clone(), data))
+    }
+
+    /// Convert to owned ChunkedArray
+    pub fn to_owned(&self) -> ChunkedArray {
+        ChunkedArray::new(
+            self.dtype,
+            self.shape.clone(),
+            self.chunk_shape.clone(),
+            self.chunks.iter().map(ArrayView::to_owned).collect(),
+        )
+    }
+}
+
+/// Check that `chunk_shape` splits `shape` into a grid of nonzero chunks
+pub(crate) fn check_grid(shape: &[u64], chunk_shape: &[u64]) -> Result<(), BlobfigError> {
+    let invalid = |reason| Err(BlobfigError::InvalidChunkedArray(reason));
+    if chunk_shape.len() != shape.len() {
+        return invalid("chunk shape must have one dimension per array dimension");
+    }
+    if chunk_shape.contains(&0) {
+        return invalid("chunk dimensions must be nonzero");
+    }
+    // The chunk count is at most the element count
+    expected_size(DType::U8, shape).ok_or(BlobfigError::ShapeOverflow)?;
+    Ok(())
+}
+
+/// Size of the elements of `dtype`, which must be copied one by one
+fn element_size(dtype: DType) -> Result<usize, BlobfigError> {
+    dtype
+        .element_size()
+        .ok_or(BlobfigError::InvalidChunkedArray(
+            "quantized arrays cannot be split or joined",
+        ))
+}
+
+/// Check the dtype and shape of the chunk at grid position `index` against
+/// the array's `dtype`, `shape` and `chunk_shape`, and that the chunk is in
+/// C order without axis names
+pub(crate) fn check_chunk(
+    dtype: DType,
+    shape: &[u64],
+    chunk_shape: &[u64],
+    index: &[u64],
+    (chunk_dtype, chunk_extent, chunk_layout): (DType, &[u64], &ArrayLayout),
+) -> Result<(), BlobfigError> {
+    if !chunk_layout.is_default() {
+        return Err(BlobfigError::InvalidChunkedArray(
+            "chunks must have the default array layout",
+        ));
+    }
+    if chunk_dtype != dtype {
+        return Err(BlobfigError::InvalidChunkedArray(
+            "chunk dtype differs from the array's",
+        ));
+    }
+    if chunk_extent != chunk_bounds(shape, chunk_shape, index).1 {
+        return Err(BlobfigError::InvalidChunkedArray(
+            "chunk shape does not match its grid position",
+        ));
+    }
+    Ok(())
+}
+
+/// Number of chunks along each dimension (zero for zero-sized chunks)
+pub(crate) fn grid_shape(shape: &[u64], chunk_shape: &[u64]) -> Vec<u64> {
+    shape
+        .iter()
+        .zip(chunk_shape)
+        .map(|(&dim, &chunk)| match chunk {
+            0 => 0,
+            chunk => dim.div_ceil(chunk),
+        })
+        .collect()
+}
+
+/// Total number of chunks in a grid
+pub(crate) fn num_chunks(grid: &[u64]) -> u64 {
+    grid.iter()
+        .try_fold(1u64, |n, &dim| n.checked_mul(dim))
+        .unwrap_or(u64::MAX)
+}
+
+/// Grid position of the `flat`-th chunk in row-major order
+pub(crate) fn unravel(grid: &[u64], mut flat: u64) -> Vec<u64> {
+    let mut index = vec![0; grid.len()];
+    for (i, &dim) in grid.iter().enumerate().rev() {
+        index[i] = flat % dim.max(1);
+        flat /= dim.max(1);
+    }
+    index
+}
+
+/// Row-major position of the chunk at grid position `index`, or None if it
+/// is outside the grid
+pub(crate) fn flatten(grid: &[u64], index: &[u64]) -> Option<u64> {
+    if index.len() != grid.len() || index.iter().zip(grid).any(|(i, dim)| i >= dim) {
+        return None;
+    }
+    Some(
+        index
+            .iter()
+            .zip(grid)
+            .fold(0, |flat, (&i, &dim)| flat * dim + i),
+    )
+}
+
+/// First element and shape of the chunk at grid position `index`
+pub(crate) fn chunk_bounds(
+    shape: &[u64],
+    chunk_shape: &[u64],
+    index: &[u64],
+) -> (Vec<u64>, Vec<u64>) {
+    shape
+        .iter()
+        .zip(chunk_shape)
+        .zip(index)
+        .map(|((&dim, &chunk), &i)| {
+            let origin = i.saturating_mul(chunk);
+            (origin, chunk.min(dim.saturating_sub(origin)))
+        })
+        .unzip()
+}
+
+/// Call `visit` with the byte offset in the dense array and the length of
+/// every contiguous row of the block at `origin` with shape `extent`, in
+/// row-major order
+fn rows(
+    shape: &[u64],
+    origin: &[u64],
+    extent: &[u64],
+    element_size: usize,
+    mut visit: impl FnMut(usize, usize),
+) {
+    let Some((&row, outer)) = extent.split_last() else {
+        return visit(0, element_size);
+    };
+    if row == 0 || outer.contains(&0) {
+        return;
+    }
+
+    // Element strides of the dense array
+    let mut strides = vec![1u64; shape.len()];
+    for i in (0..shape.len() - 1).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+    let mut position = vec![0u64; outer.len()];
+    loop {
+        let start: u64 = origin
+            .iter()
+            .zip(position.iter().chain([&0]))
+            .zip(&strides)
+            .map(|((&o, &p), &stride)| (o + p) * stride)
+            .sum();
+        visit(start as usize * element_size, row as usize * element_size);
+
+        // Advance to the next row, last dimension fastest
+        let Some(dim) = (0..outer.len()).rev().find(|&d| position[d] + 1 < outer[d]) else {
+            return;
+        };
+        position[dim] += 1;
+        position[dim + 1..].fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dense() -> Array {
+        // 5x3 u16 matrix holding 0..15
+        let data = (0u16..15).flat_map(|v| v.to_le_bytes()).collect();
+        Array::new(DType::U16, vec![5, 3], data)
+    }
+
+    fn values(arr: &Array) -> Vec<u16> {
+        arr.data
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn edge_chunks_are_clipped() {
+        let chunked = ChunkedArray::from_array(&dense(), vec![2, 2]).unwrap();
+        assert_eq!(chunked.grid(), vec![3, 2]);
+        assert_eq!(chunked.num_chunks(), 6);
+        assert_eq!(chunked.chunk_shape_at(&[2, 1]), Some(vec![1, 1]));
+        assert_eq!(chunked.chunk_shape_at(&[3, 0]), None);
+        chunked.validate().unwrap();
+
+        let ChunkData::Arrays(chunks) = &chunked.chunks else {
+            unreachable!()
+        };
+        assert_eq!(values(&chunks[0]), [0, 1, 3, 4]);
+        assert_eq!(values(&chunks[1]), [2, 5]);
+        assert_eq!(values(&chunks[5]), [14]);
+    }
+
+    #[test]
+    fn view_reassembles_chunks() {
+        let chunked = ChunkedArray::from_array(&dense(), vec![2, 2]).unwrap();
+        let ChunkData::Arrays(chunks) = &chunked.chunks else {
+            unreachable!()
+        };
+        let views = chunks
+            .iter()
+            .map(|chunk| ArrayView {
+                dtype: chunk.dtype,
+                shape: chunk.shape.clone(),
+                data: &chunk.data,
+                checksum: None,
+                compression: None,
+                encryption: None,
+                layout: ArrayLayout::default(),
+            })
+            .collect();
+        let view = ChunkedArrayView::new(DType::U16, vec![5, 3], vec![2, 2], views);
+
+        assert_eq!(view.chunk(&[1, 0]).unwrap().shape, vec![2, 2]);
+        assert!(view.chunk(&[0, 2]).is_none());
+        assert!(view.chunk(&[0]).is_none());
+        assert_eq!(view.to_dense().unwrap().data, dense().data);
+    }
+
+    #[test]
+    fn scalars_and_empty_arrays() {
+        let scalar = Array::new(DType::I64, vec![], vec![7; 8]);
+        let chunked = ChunkedArray::from_array(&scalar, vec![]).unwrap();
+        assert_eq!(chunked.num_chunks(), 1);
+        chunked.validate().unwrap();
+
+        let empty = Array::new(DType::F32, vec![4, 0], vec![]);
+        let chunked = ChunkedArray::from_array(&empty, vec![2, 2]).unwrap();
+        assert_eq!(chunked.grid(), vec![2, 0]);
+        assert_eq!(chunked.num_chunks(), 0);
+    }
+
+    #[test]
+    fn bad_grids_are_rejected() {
+        let err = ChunkedArray::from_array(&dense(), vec![2, 0]).unwrap_err();
+        assert_eq!(
+            err,
+            BlobfigError::InvalidChunkedArray("chunk dimensions must be nonzero")
+        );
+        assert!(ChunkedArray::from_array(&dense(), vec![2]).is_err());
+
+        let mut chunked = ChunkedArray::from_array(&dense(), vec![2, 2]).unwrap();
+        if let ChunkData::Arrays(chunks) = &mut chunked.chunks {
+            chunks.swap(0, 1);
+        }
+        assert_eq!(
+            chunked.validate(),
+            Err(BlobfigError::InvalidChunkedArray(
+                "chunk shape does not match its grid position"
+            ))
+        );
+
+        let quantized = Array::new(DType::I4, vec![4], vec![0; 2]);
+        assert!(ChunkedArray::from_array(&quantized, vec![2]).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Payload compression
+//!
+//! When [`FLAG_COMPRESSION`](super::FLAG_COMPRESSION) is set, the data size
+//! of every Array and File is preceded by a codec byte (0 for raw data).
+//! Compressed payloads also record the [`Filters`] applied before compressing
+//! and their decompressed size:
+//!
+//! ```text
+//! u8 codec
+//! u8 filters         (only if codec != 0)
+//! u64 raw_size       (only if codec != 0)
+//! u64 data_size      (stored bytes)
+//! ```
+//!
+//! Codecs are compiled in with the `zstd` and `lz4` feature flags. Payloads
+//! using a codec that is not compiled in still parse; only decompressing
+//! them fails.
+
+use super::filter::Filters;
+use crate::error::{BlobfigError, Limit};
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+
+/// Default cap on the decompressed size of one payload (1 GiB)
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 1 << 30;
+
+/// Compression codec of a payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Compression {
+    /// Zstandard (feature `zstd`)
+    Zstd = 0x01,
+    /// LZ4 block format (feature `lz4`)
+    Lz4 = 0x02,
+}
+
+impl Compression {
+    /// Parse from the codec byte (0 means uncompressed and is not a codec)
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0x01 => Some(Compression::Zstd),
+            0x02 => Some(Compression::Lz4),
+            _ => None,
+        }
+    }
+
+    /// Whether this codec is compiled in
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Compression::Zstd => cfg!(feature = "zstd"),
+            Compression::Lz4 => cfg!(feature = "lz4"),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Lz4 => write!(f, "lz4"),
+        }
+    }
+}
+
+/// Encoding of a compressed payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compressed {
+    pub codec: Compression,
+    /// Filters applied to the data before compressing it
+    pub filters: Filters,
+    /// Size of the data once decompressed (and unfiltered)
+    pub raw_size: u64,
+}
+
+/// Filter `data` (elements of `element_size` bytes, None for quantized
+/// blocks) and compress it with `codec`
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if the filters do not apply
+/// to elements of that size.
+pub(crate) fn compress(
+    codec: Compression,
+    filters: Filters,
+    element_size: Option<usize>,
+    data: &[u8],
+) -> io::Result<Vec<u8>> {
+    if filters.is_empty() {
+        return compress_bytes(codec, data);
+    }
+    check_filters(filters, element_size)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let Some(element_size) = element_size else {
+        unreachable!("checked above")
+    };
+    compress_bytes(codec, &filters.encode(data, element_size))
+}
+
+#[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+fn compress_bytes(codec: Compression, data: &[u8]) -> io::Result<Vec<u8>> {
+    match codec {
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => zstd::bulk::compress(data, 0),
+        #[cfg(feature = "lz4")]
+        Compression::Lz4 => Ok(lz4_flex::block::compress(data)),
+        #[allow(unreachable_patterns)]
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            BlobfigError::UnsupportedCompression(codec),
+        )),
+    }
+}
+
+/// Check that `filters` apply to elements of `element_size` bytes (None for
+/// quantized blocks)
+pub(crate) fn check_filters(
+    filters: Filters,
+    element_size: Option<usize>,
+) -> Result<(), BlobfigError> {
+    match filters.supports(element_size) {
+        true => Ok(()),
+        false => Err(BlobfigError::InvalidFilters(filters.bits())),
+    }
+}
+
+/// Decompress and unfilter stored payload bytes, borrowing them if they are
+/// not compressed
+///
+/// Fails without allocating if the recorded size exceeds `max_size` or the
+/// filters do not apply to elements of `element_size` bytes, and if the data
+/// does not decompress to exactly the recorded size.
+pub(crate) fn decompress(
+    data: &[u8],
+    compression: Option<Compressed>,
+    element_size: Option<usize>,
+    max_size: usize,
+) -> Result<Cow<'_, [u8]>, BlobfigError> {
+    let Some(Compressed {
+        codec,
+        filters,
+        raw_size,
+    }) = compression
+    else {
+        return Ok(Cow::Borrowed(data));
+    };
+    check_filters(filters, element_size)?;
+    let size = usize::try_from(raw_size).map_err(|_| BlobfigError::SizeOverflow(raw_size))?;
+    if size > max_size {
+        let limit = Limit::DecompressedSize;
+        return Err(BlobfigError::LimitExceeded {
+            limit,
+            max: max_size,
+        });
+    }
+
+    let raw = decompress_bounded(codec, data, size)?;
+    if raw.len() != size {
+        return Err(BlobfigError::InvalidCompressedData);
+    }
+    Ok(Cow::Owned(match element_size {
+        Some(size) => filters.decode(raw, size),
+        // Quantized blocks are not filtered (checked above)
+        None => raw,
+    }))
+}
+
+/// Decompress `data` into at most `size` bytes
+#[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+fn decompress_bounded(
+    codec: Compression,
+    data: &[u8],
+    size: usize,
+) -> Result<Vec<u8>, BlobfigError> {
+    match codec {
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            zstd::bulk::decompress(data, size).map_err(|_| BlobfigError::InvalidCompressedData)
+        }
+        #[cfg(feature = "lz4")]
+        Compression::Lz4 => {
+            lz4_flex::block::decompress(data, size).map_err(|_| BlobfigError::InvalidCompressedData)
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(BlobfigError::UnsupportedCompression(codec)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressed(codec: Compression, raw_size: u64) -> Option<Compressed> {
+        Some(Compressed {
+            codec,
+            filters: Filters::NONE,
+            raw_size,
+        })
+    }
+
+    #[test]
+    fn raw_data_is_borrowed() {
+        let data = [1u8, 2, 3];
+        let out = decompress(&data, None, Some(1), 0).unwrap();
+        assert!(matches!(out, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn recorded_size_is_capped() {
+        let err =
+            decompress(&[], compressed(Compression::Lz4, 1 << 40), Some(1), 1 << 20).unwrap_err();
+        assert_eq!(
+            err,
+            BlobfigError::LimitExceeded {
+                limit: Limit::DecompressedSize,
+                max: 1 << 20
+            }
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_roundtrip() {
+        let data = vec![7u8; 10_000];
+        let stored = compress(Compression::Zstd, Filters::NONE, Some(1), &data).unwrap();
+        assert!(stored.len() < 100);
+
+        let raw = compressed(Compression::Zstd, data.len() as u64);
+        assert_eq!(decompress(&stored, raw, Some(1), usize::MAX).unwrap(), data);
+
+        // A wrong recorded size is corrupt, even if smaller than the cap
+        let short = compressed(Compression::Zstd, data.len() as u64 - 1);
+        let err = decompress(&stored, short, Some(1), usize::MAX).unwrap_err();
+        assert_eq!(err, BlobfigError::InvalidCompressedData);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_roundtrip() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 7) as u8).collect();
+        let stored = compress(Compression::Lz4, Filters::NONE, Some(1), &data).unwrap();
+        assert!(stored.len() < data.len());
+
+        let raw = compressed(Compression::Lz4, data.len() as u64);
+        assert_eq!(decompress(&stored, raw, Some(1), usize::MAX).unwrap(), data);
+
+        let err = decompress(&stored[..10], raw, Some(1), usize::MAX).unwrap_err();
+        assert_eq!(err, BlobfigError::InvalidCompressedData);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn missing_codec_is_reported() {
+        let err =
+            decompress(&[0], compressed(Compression::Zstd, 1), Some(1), usize::MAX).unwrap_err();
+        assert_eq!(err, BlobfigError::UnsupportedCompression(Compression::Zstd));
+        assert!(compress(Compression::Zstd, Filters::NONE, Some(1), &[0]).is_err());
+    }
+}
@@ -14,19 +14,70 @@ pub enum DType {
     I64 = 0x08,
     F32 = 0x09,
     F64 = 0x0A,
+    /// IEEE 754 half precision
+    F16 = 0x0B,
+    /// bfloat16: the upper half of an f32
+    BF16 = 0x0C,
+    /// One byte per element, 0 or 1
+    Bool = 0x0D,
+    /// Complex number of two f32s (real, imaginary)
+    C64 = 0x0E,
+    /// Complex number of two f64s (real, imaginary)
+    C128 = 0x0F,
+    /// Signed 4-bit integers packed two per byte, low nibble first
+    I4 = 0x10,
+    /// Blocks of 32 4-bit values with an f16 scale, laid out as GGML's Q4_0
+    Q4_0 = 0x11,
+    /// Blocks of 32 i8 values with an f16 scale, laid out as GGML's Q8_0
+    Q8_0 = 0x12,
 }
 
 impl DType {
-    /// Size in bytes of a single element
-    pub fn element_size(self) -> usize {
+    /// Size in bytes of a single element (None for quantized dtypes, whose
+    /// elements do not fill whole bytes of their own)
+    pub fn element_size(self) -> Option<usize> {
+        (!self.is_quantized()).then(|| self.block_size())
+    }
+
+    /// Size in bytes of one block of [`block_len`](Self::block_len) elements
+    /// (the element size for dtypes that are not quantized)
+    pub fn block_size(self) -> usize {
         match self {
-            DType::U8 | DType::I8 => 1,
-            DType::U16 | DType::I16 => 2,
+            DType::U8 | DType::I8 | DType::Bool | DType::I4 => 1,
+            DType::U16 | DType::I16 | DType::F16 | DType::BF16 => 2,
             DType::U32 | DType::I32 | DType::F32 => 4,
-            DType::U64 | DType::I64 | DType::F64 => 8,
+            DType::U64 | DType::I64 | DType::F64 | DType::C64 => 8,
+            DType::C128 => 16,
+            DType::Q4_0 => 2 + 16,
+            DType::Q8_0 => 2 + 32,
+        }
+    }
+
+    /// Number of elements stored together in one block (1 for dtypes that
+    /// are not quantized)
+    pub fn block_len(self) -> usize {
+        match self {
+            DType::I4 => 2,
+            DType::Q4_0 | DType::Q8_0 => 32,
+            _ => 1,
         }
     }
 
+    /// Whether elements are packed into blocks of more than one
+    pub fn is_quantized(self) -> bool {
+        self.block_len() > 1
+    }
+
+    /// Size in bytes of `count` elements (None if it overflows)
+    ///
+    /// The last block of a quantized array is stored whole; the elements
+    /// past `count` are padding.
+    pub fn data_size(self, count: u64) -> Option<u64> {
+        count
+            .div_ceil(self.block_len() as u64)
+            .checked_mul(self.block_size() as u64)
+    }
+
     /// Try to convert from u8 tag
     pub fn from_u8(tag: u8) -> Option<Self> {
         match tag {
@@ -40,6 +91,14 @@ impl DType {
             0x08 => Some(DType::I64),
             0x09 => Some(DType::F32),
             0x0A => Some(DType::F64),
+            0x0B => Some(DType::F16),
+            0x0C => Some(DType::BF16),
+            0x0D => Some(DType::Bool),
+            0x0E => Some(DType::C64),
+            0x0F => Some(DType::C128),
+            0x10 => Some(DType::I4),
+            0x11 => Some(DType::Q4_0),
+            0x12 => Some(DType::Q8_0),
             _ => None,
         }
     }
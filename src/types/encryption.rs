@@ -0,0 +1,341 @@
+//! Payload encryption
+//!
+//! When [`FLAG_ENCRYPTION`](super::FLAG_ENCRYPTION) is set, the data size of
+//! every Array and File is preceded by a cipher byte (0 for plaintext).
+//! Encrypted payloads also record the name of their key and their nonce:
+//!
+//! ```text
+//! u8 cipher
+//! u8 key_id_len      (only if cipher != 0)
+//! key_id bytes       (only if cipher != 0)
+//! [u8; 12] nonce     (only if cipher != 0)
+//! u64 data_size      (ciphertext and 16-byte tag)
+//! ```
+//!
+//! The cipher fields follow the compression fields; payloads are compressed
+//! before they are encrypted. The mimetype, dtype, shape, array layout and
+//! compression fields stay in plaintext but are authenticated along with the
+//! data, so changing any of them makes decryption fail.
+//!
+//! Ciphers are compiled in with the `encryption` feature flag. Encrypted
+//! payloads still parse without it; only encrypting and decrypting fail.
+
+use super::array_layout::ArrayLayout;
+use super::compression::{Compressed, DEFAULT_MAX_DECOMPRESSED_SIZE, decompress};
+use super::dtype::DType;
+use crate::error::BlobfigError;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+/// Size of an encryption key in bytes
+pub const KEY_SIZE: usize = 32;
+
+/// Size of a nonce in bytes
+pub const NONCE_SIZE: usize = 12;
+
+/// Size of the authentication tag appended to the ciphertext
+pub const TAG_SIZE: usize = 16;
+
+/// Source of the keys used to encrypt and decrypt payloads
+pub trait KeyProvider {
+    /// Key named `key_id`, or None if this provider does not have it
+    fn key(&self, key_id: &str) -> Option<[u8; KEY_SIZE]>;
+}
+
+impl KeyProvider for HashMap<String, [u8; KEY_SIZE]> {
+    fn key(&self, key_id: &str) -> Option<[u8; KEY_SIZE]> {
+        self.get(key_id).copied()
+    }
+}
+
+/// Authenticated cipher of a payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Cipher {
+    /// AES-256 in Galois/Counter Mode
+    Aes256Gcm = 0x01,
+    /// ChaCha20 with a Poly1305 authenticator
+    ChaCha20Poly1305 = 0x02,
+}
+
+impl Cipher {
+    /// Parse from the cipher byte (0 means plaintext and is not a cipher)
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0x01 => Some(Cipher::Aes256Gcm),
+            0x02 => Some(Cipher::ChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    /// Whether this cipher is compiled in
+    pub fn is_supported(&self) -> bool {
+        cfg!(feature = "encryption")
+    }
+}
+
+impl fmt::Display for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cipher::Aes256Gcm => write!(f, "AES-256-GCM"),
+            Cipher::ChaCha20Poly1305 => write!(f, "ChaCha20-Poly1305"),
+        }
+    }
+}
+
+/// Encryption of a payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encrypted {
+    pub cipher: Cipher,
+    /// Name of the key, passed to [`KeyProvider::key`]
+    pub key_id: String,
+    pub nonce: [u8; NONCE_SIZE],
+}
+
+/// Authenticated metadata of an Array payload
+///
+/// The layout is included whether or not the file has layout fields, so an
+/// array cannot be reinterpreted in another order by dropping them.
+pub(crate) fn array_metadata(dtype: DType, shape: &[u64], layout: &ArrayLayout) -> Vec<u8> {
+    let mut metadata = vec![dtype as u8, shape.len() as u8];
+    for dim in shape {
+        metadata.extend_from_slice(&dim.to_le_bytes());
+    }
+    metadata.push(layout.flags());
+    for stride in layout.strides.iter().flatten() {
+        metadata.extend_from_slice(&stride.to_le_bytes());
+    }
+    for name in layout.axis_names.iter().flatten() {
+        metadata.push(name.len() as u8);
+        metadata.extend_from_slice(name.as_bytes());
+    }
+    metadata
+}
+
+/// Authenticated metadata of a File payload
+pub(crate) fn file_metadata(mimetype: &str) -> Vec<u8> {
+    let mut metadata = (mimetype.len() as u16).to_le_bytes().to_vec();
+    metadata.extend_from_slice(mimetype.as_bytes());
+    metadata
+}
+
+/// Additional data authenticated with the ciphertext
+///
+/// Binds the payload to its plaintext metadata, compression and cipher fields.
+/// Variable-length fields are prefixed with their length, as in the file, so
+/// no bytes can move from one field to the next.
+fn associated_data(
+    metadata: &[u8],
+    compression: Option<Compressed>,
+    cipher: Cipher,
+    key_id: &str,
+) -> Vec<u8> {
+    let mut aad = metadata.to_vec();
+    match compression {
+        None => aad.push(0),
+        Some(compressed) => {
+            aad.push(compressed.codec as u8);
+            aad.push(compressed.filters.bits());
+            aad.extend_from_slice(&compressed.raw_size.to_le_bytes());
+        }
+    }
+    aad.push(cipher as u8);
+    aad.push(key_id.len() as u8);
+    aad.extend_from_slice(key_id.as_bytes());
+    aad
+}
+
+/// Encrypt stored payload bytes with a fresh random nonce
+pub(crate) fn encrypt(
+    cipher: Cipher,
+    key_id: &str,
+    keys: &impl KeyProvider,
+    metadata: &[u8],
+    compression: Option<Compressed>,
+    data: &[u8],
+) -> Result<(Encrypted, Vec<u8>), BlobfigError> {
+    let key = keys
+        .key(key_id)
+        .ok_or_else(|| BlobfigError::UnknownKey(key_id.to_string()))?;
+    let aad = associated_data(metadata, compression, cipher, key_id);
+    let (nonce, ciphertext) = seal(cipher, &key, &aad, data)?;
+    let encrypted = Encrypted {
+        cipher,
+        key_id: key_id.to_string(),
+        nonce,
+    };
+    Ok((encrypted, ciphertext))
+}
+
+/// I/O error for a payload that could not be encrypted
+pub(crate) fn encrypt_error(e: BlobfigError) -> io::Error {
+    let kind = match e {
+        BlobfigError::UnsupportedCipher(_) => io::ErrorKind::Unsupported,
+        _ => io::ErrorKind::InvalidInput,
+    };
+    io::Error::new(kind, e)
+}
+
+/// Decrypt stored payload bytes, checking them and their metadata
+pub(crate) fn decrypt(
+    encrypted: &Encrypted,
+    keys: &impl KeyProvider,
+    metadata: &[u8],
+    compression: Option<Compressed>,
+    data: &[u8],
+) -> Result<Vec<u8>, BlobfigError> {
+    let key_id = &encrypted.key_id;
+    let key = keys
+        .key(key_id)
+        .ok_or_else(|| BlobfigError::UnknownKey(key_id.clone()))?;
+    let aad = associated_data(metadata, compression, encrypted.cipher, key_id);
+    open(encrypted.cipher, &key, &encrypted.nonce, &aad, data)
+}
+
+/// Decrypt (if encrypted) and decompress (if compressed) stored payload bytes
+pub(crate) fn decrypt_payload<'a>(
+    data: &'a [u8],
+    encryption: Option<&Encrypted>,
+    keys: &impl KeyProvider,
+    metadata: impl FnOnce() -> Vec<u8>,
+    compression: Option<Compressed>,
+    element_size: Option<usize>,
+) -> Result<Cow<'a, [u8]>, BlobfigError> {
+    let max_size = DEFAULT_MAX_DECOMPRESSED_SIZE;
+    let Some(encrypted) = encryption else {
+        return decompress(data, compression, element_size, max_size);
+    };
+    let plaintext = decrypt(encrypted, keys, &metadata(), compression, data)?;
+    if compression.is_none() {
+        return Ok(Cow::Owned(plaintext));
+    }
+    let raw = decompress(&plaintext, compression, element_size, max_size)?;
+    Ok(Cow::Owned(raw.into_owned()))
+}
+
+#[cfg(feature = "encryption")]
+fn seal(
+    cipher: Cipher,
+    key: &[u8; KEY_SIZE],
+    aad: &[u8],
+    data: &[u8],
+) -> Result<([u8; NONCE_SIZE], Vec<u8>), BlobfigError> {
+    use aes_gcm::Aes256Gcm;
+    use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+    use chacha20poly1305::ChaCha20Poly1305;
+
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let payload = Payload { msg: data, aad };
+    let ciphertext = match cipher {
+        Cipher::Aes256Gcm => Aes256Gcm::new(key.into()).encrypt(&nonce, payload),
+        Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into()).encrypt(&nonce, payload),
+    };
+    // Encryption only fails for inputs far beyond any payload size
+    let ciphertext = ciphertext.map_err(|_| BlobfigError::PayloadTooLarge)?;
+    Ok((nonce.into(), ciphertext))
+}
+
+#[cfg(feature = "encryption")]
+fn open(
+    cipher: Cipher,
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    data: &[u8],
+) -> Result<Vec<u8>, BlobfigError> {
+    use aes_gcm::Aes256Gcm;
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use chacha20poly1305::ChaCha20Poly1305;
+
+    let payload = Payload { msg: data, aad };
+    let plaintext = match cipher {
+        Cipher::Aes256Gcm => Aes256Gcm::new(key.into()).decrypt(nonce.into(), payload),
+        Cipher::ChaCha20Poly1305 => {
+            ChaCha20Poly1305::new(key.into()).decrypt(nonce.into(), payload)
+        }
+    };
+    plaintext.map_err(|_| BlobfigError::DecryptionFailed)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn seal(
+    cipher: Cipher,
+    _key: &[u8; KEY_SIZE],
+    _aad: &[u8],
+    _data: &[u8],
+) -> Result<([u8; NONCE_SIZE], Vec<u8>), BlobfigError> {
+    Err(BlobfigError::UnsupportedCipher(cipher))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open(
+    cipher: Cipher,
+    _key: &[u8; KEY_SIZE],
+    _nonce: &[u8; NONCE_SIZE],
+    _aad: &[u8],
+    _data: &[u8],
+) -> Result<Vec<u8>, BlobfigError> {
+    Err(BlobfigError::UnsupportedCipher(cipher))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> HashMap<String, [u8; KEY_SIZE]> {
+        HashMap::from([("prod".to_string(), [7u8; KEY_SIZE])])
+    }
+
+    #[test]
+    fn unknown_key_is_reported() {
+        let err = encrypt(Cipher::Aes256Gcm, "dev", &keys(), b"", None, b"x").unwrap_err();
+        assert_eq!(err, BlobfigError::UnknownKey("dev".into()));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn roundtrip_and_tamper_detection() {
+        for cipher in [Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305] {
+            let data = b"proprietary model";
+            let (encrypted, ciphertext) =
+                encrypt(cipher, "prod", &keys(), b"meta", None, data).unwrap();
+            assert_eq!(ciphertext.len(), data.len() + TAG_SIZE);
+
+            let plaintext = decrypt(&encrypted, &keys(), b"meta", None, &ciphertext).unwrap();
+            assert_eq!(plaintext, data);
+
+            // Metadata is authenticated
+            let err = decrypt(&encrypted, &keys(), b"mETA", None, &ciphertext).unwrap_err();
+            assert_eq!(err, BlobfigError::DecryptionFailed);
+
+            let mut tampered = ciphertext.clone();
+            tampered[0] ^= 1;
+            let err = decrypt(&encrypted, &keys(), b"meta", None, &tampered).unwrap_err();
+            assert_eq!(err, BlobfigError::DecryptionFailed);
+
+            let wrong = HashMap::from([("prod".to_string(), [8u8; KEY_SIZE])]);
+            let err = decrypt(&encrypted, &wrong, b"meta", None, &ciphertext).unwrap_err();
+            assert_eq!(err, BlobfigError::DecryptionFailed);
+        }
+    }
+
+    #[test]
+    fn fields_are_delimited() {
+        // Without length prefixes both would join to "m", 0, 1, 0, 1, "k"
+        let cipher = Cipher::Aes256Gcm;
+        assert_ne!(
+            associated_data(b"m", None, cipher, "\0\x01k"),
+            associated_data(b"m\0\x01", None, cipher, "k")
+        );
+        assert_eq!(file_metadata("text/plain")[..2], [10, 0]);
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    #[test]
+    fn missing_cipher_is_reported() {
+        let err = encrypt(Cipher::Aes256Gcm, "prod", &keys(), b"", None, b"x").unwrap_err();
+        assert_eq!(err, BlobfigError::UnsupportedCipher(Cipher::Aes256Gcm));
+    }
+}
@@ -1,6 +1,16 @@
 //! File blob types
 
-use std::io::Read;
+use super::checksum::verify_checksum;
+use super::compression::{
+    Compressed, Compression, DEFAULT_MAX_DECOMPRESSED_SIZE, compress, decompress,
+};
+use super::encryption::{
+    Cipher, Encrypted, KeyProvider, decrypt_payload, encrypt, encrypt_error, file_metadata,
+};
+use super::filter::Filters;
+use crate::error::BlobfigError;
+use std::borrow::Cow;
+use std::io::{self, Read};
 
 /// Trait for file data sources that can be read and have known size
 pub trait FileHandle: Read + Send {
@@ -33,6 +43,18 @@ impl FileData {
             FileData::Handle(h) => h.size(),
         }
     }
+
+    /// Take the data as bytes, reading a handle to the end
+    pub fn into_bytes(self) -> io::Result<Vec<u8>> {
+        match self {
+            FileData::Bytes(bytes) => Ok(bytes),
+            FileData::Handle(mut handle) => {
+                let mut bytes = Vec::new();
+                handle.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
 }
 
 /// Owned file blob (for building/writing)
@@ -40,6 +62,10 @@ impl FileData {
 pub struct File {
     pub mimetype: String,
     pub data: FileData,
+    /// How `data` is stored: None for the raw file contents
+    pub compression: Option<Compressed>,
+    /// Encryption of the (compressed) data, if any
+    pub encryption: Option<Encrypted>,
 }
 
 impl File {
@@ -48,6 +74,8 @@ impl File {
         Self {
             mimetype: mimetype.into(),
             data: FileData::Bytes(data),
+            compression: None,
+            encryption: None,
         }
     }
 
@@ -56,28 +84,129 @@ impl File {
         Self {
             mimetype: mimetype.into(),
             data: FileData::Handle(Box::new(handle)),
+            compression: None,
+            encryption: None,
         }
     }
 
-    /// Get the size of the file data
+    /// Get the size of the file data (as stored, i.e. compressed if it is)
     pub fn size(&self) -> u64 {
         self.data.size()
     }
+
+    /// Compress the data with `codec` so it is stored compressed
+    ///
+    /// Handles are read into memory first. Files that are already compressed
+    /// are returned unchanged.
+    pub fn compress(mut self, codec: Compression) -> io::Result<Self> {
+        if self.compression.is_some() {
+            return Ok(self);
+        }
+        if self.encryption.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                BlobfigError::Encrypted,
+            ));
+        }
+        let raw = self.data.into_bytes()?;
+        self.data = FileData::Bytes(compress(codec, Filters::NONE, Some(1), &raw)?);
+        self.compression = Some(Compressed {
+            codec,
+            filters: Filters::NONE,
+            raw_size: raw.len() as u64,
+        });
+        Ok(self)
+    }
+
+    /// Encrypt the (compressed) data with the key named `key_id`
+    ///
+    /// Handles are read into memory first. Compress first: encrypted data does
+    /// not compress. Files that are already encrypted are returned unchanged.
+    pub fn encrypt(
+        mut self,
+        cipher: Cipher,
+        key_id: &str,
+        keys: &impl KeyProvider,
+    ) -> io::Result<Self> {
+        if self.encryption.is_some() {
+            return Ok(self);
+        }
+        let plaintext = self.data.into_bytes()?;
+        let (encrypted, data) = encrypt(
+            cipher,
+            key_id,
+            keys,
+            &file_metadata(&self.mimetype),
+            self.compression,
+            &plaintext,
+        )
+        .map_err(encrypt_error)?;
+        self.data = FileData::Bytes(data);
+        self.encryption = Some(encrypted);
+        Ok(self)
+    }
 }
 
 /// View into a file blob stored in the blob (zero-copy)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct FileView<'a> {
     pub mimetype: &'a str,
     pub data: &'a [u8],
+    /// Checksum recorded after the data, if the file was written with checksums
+    pub checksum: Option<u32>,
+    /// How `data` is stored: None for the raw file contents
+    pub compression: Option<Compressed>,
+    /// Encryption of the (compressed) data, if any
+    pub encryption: Option<Encrypted>,
 }
 
 impl<'a> FileView<'a> {
+    /// Raw file contents, borrowed if the data is stored uncompressed
+    ///
+    /// Compressed data larger than [`DEFAULT_MAX_DECOMPRESSED_SIZE`] is
+    /// refused; see [`FileView::decompressed_with`].
+    pub fn decompressed(&self) -> Result<Cow<'a, [u8]>, BlobfigError> {
+        self.decompressed_with(DEFAULT_MAX_DECOMPRESSED_SIZE)
+    }
+
+    /// Raw file contents, refusing to decompress more than `max_size` bytes
+    ///
+    /// Fails with [`BlobfigError::Encrypted`] for encrypted files.
+    pub fn decompressed_with(&self, max_size: usize) -> Result<Cow<'a, [u8]>, BlobfigError> {
+        if self.encryption.is_some() {
+            return Err(BlobfigError::Encrypted);
+        }
+        decompress(self.data, self.compression, Some(1), max_size)
+    }
+
+    /// Raw file contents, decrypted with a key from `keys` and decompressed
+    ///
+    /// Fails with [`BlobfigError::DecryptionFailed`] for a wrong key or if the
+    /// data or mimetype were tampered with. Files that are not encrypted are
+    /// returned as by [`FileView::decompressed`].
+    pub fn decrypt(&self, keys: &impl KeyProvider) -> Result<Cow<'a, [u8]>, BlobfigError> {
+        decrypt_payload(
+            self.data,
+            self.encryption.as_ref(),
+            keys,
+            || file_metadata(self.mimetype),
+            self.compression,
+            Some(1),
+        )
+    }
+
+    /// Check the data against its recorded checksum
+    pub fn verify(&self) -> Result<(), BlobfigError> {
+        verify_checksum(self.data, self.checksum)
+    }
+
     /// Convert to owned File (in-memory)
     pub fn to_owned(&self) -> File {
         File {
             mimetype: self.mimetype.to_string(),
             data: FileData::Bytes(self.data.to_vec()),
+            compression: self.compression,
+            encryption: self.encryption.clone(),
         }
     }
 }
@@ -0,0 +1,237 @@
+//! Byte filters applied to payloads before compression
+//!
+//! Filters reorder or transform element bytes so generic codecs find more
+//! redundancy. They are lossless and recorded with the compressed payload,
+//! encoded in a fixed order: delta first, then shuffle.
+
+use super::DType;
+use std::fmt;
+
+/// Set of filters applied to a compressed payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Filters(u8);
+
+impl Filters {
+    /// No filtering
+    pub const NONE: Filters = Filters(0);
+
+    /// Group byte `i` of every element together (Blosc-style shuffle)
+    pub const SHUFFLE: Filters = Filters(1 << 0);
+
+    /// Store each element as the wrapping difference from the previous one
+    pub const DELTA: Filters = Filters(1 << 1);
+
+    const ALL: u8 = Self::SHUFFLE.0 | Self::DELTA.0;
+
+    /// Parse from the filter byte (None if unknown bits are set)
+    pub fn from_u8(bits: u8) -> Option<Self> {
+        (bits & !Self::ALL == 0).then_some(Filters(bits))
+    }
+
+    /// Filter byte
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether every filter in `other` is also in `self`
+    pub fn contains(self, other: Filters) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether no filter is set
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Filters the writer applies to arrays of `dtype`
+    ///
+    /// Floats and complex numbers are shuffled; multi-byte integers are
+    /// delta encoded and shuffled. Single-byte elements and quantized blocks
+    /// are left as they are.
+    pub fn for_dtype(dtype: DType) -> Self {
+        match dtype {
+            DType::U8 | DType::I8 | DType::Bool => Filters::NONE,
+            DType::I4 | DType::Q4_0 | DType::Q8_0 => Filters::NONE,
+            DType::F16 | DType::BF16 | DType::F32 | DType::F64 | DType::C64 | DType::C128 => {
+                Filters::SHUFFLE
+            }
+            _ => Filters::DELTA | Filters::SHUFFLE,
+        }
+    }
+
+    /// Whether the filters can be applied to elements of `element_size` bytes
+    /// (None for quantized blocks, which are never filtered)
+    ///
+    /// Delta encoding works on elements of at most 8 bytes.
+    pub(crate) fn supports(self, element_size: Option<usize>) -> bool {
+        match element_size {
+            _ if self.is_empty() => true,
+            Some(size) => !self.contains(Filters::DELTA) || size <= 8,
+            None => false,
+        }
+    }
+
+    /// Apply the filters to elements of `element_size` bytes
+    pub(crate) fn encode(self, data: &[u8], element_size: usize) -> Vec<u8> {
+        let mut data = data.to_vec();
+        if self.contains(Filters::DELTA) {
+            delta_encode(&mut data, element_size);
+        }
+        if self.contains(Filters::SHUFFLE) {
+            data = shuffle(&data, element_size);
+        }
+        data
+    }
+
+    /// Undo [`Filters::encode`]
+    pub(crate) fn decode(self, mut data: Vec<u8>, element_size: usize) -> Vec<u8> {
+        if self.contains(Filters::SHUFFLE) {
+            data = unshuffle(&data, element_size);
+        }
+        if self.contains(Filters::DELTA) {
+            delta_decode(&mut data, element_size);
+        }
+        data
+    }
+}
+
+impl std::ops::BitOr for Filters {
+    type Output = Filters;
+
+    fn bitor(self, rhs: Filters) -> Filters {
+        Filters(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Display for Filters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = [(Filters::DELTA, "delta"), (Filters::SHUFFLE, "shuffle")]
+            .into_iter()
+            .filter(|(filter, _)| self.contains(*filter))
+            .map(|(_, name)| name)
+            .collect();
+        match names.is_empty() {
+            true => write!(f, "none"),
+            false => write!(f, "{}", names.join("+")),
+        }
+    }
+}
+
+/// Transpose `data` from element-major to byte-major order
+///
+/// Trailing bytes that do not form a whole element are copied as they are.
+fn shuffle(data: &[u8], element_size: usize) -> Vec<u8> {
+    let count = data.len() / element_size;
+    let mut out = vec![0u8; data.len()];
+    for (i, element) in data.chunks_exact(element_size).enumerate() {
+        for (b, &byte) in element.iter().enumerate() {
+            out[b * count + i] = byte;
+        }
+    }
+    let whole = count * element_size;
+    out[whole..].copy_from_slice(&data[whole..]);
+    out
+}
+
+/// Inverse of [`shuffle`]
+fn unshuffle(data: &[u8], element_size: usize) -> Vec<u8> {
+    let count = data.len() / element_size;
+    let mut out = vec![0u8; data.len()];
+    for (i, element) in out.chunks_exact_mut(element_size).enumerate() {
+        for (b, byte) in element.iter_mut().enumerate() {
+            *byte = data[b * count + i];
+        }
+    }
+    let whole = count * element_size;
+    out[whole..].copy_from_slice(&data[whole..]);
+    out
+}
+
+/// Little-endian element of up to 8 bytes
+fn read_element(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+fn write_element(bytes: &mut [u8], value: u64) {
+    let len = bytes.len();
+    bytes.copy_from_slice(&value.to_le_bytes()[..len]);
+}
+
+/// Replace each element with its wrapping difference from the previous one
+fn delta_encode(data: &mut [u8], element_size: usize) {
+    let mut previous = 0u64;
+    for element in data.chunks_exact_mut(element_size) {
+        let value = read_element(element);
+        write_element(element, value.wrapping_sub(previous));
+        previous = value;
+    }
+}
+
+/// Inverse of [`delta_encode`]
+fn delta_decode(data: &mut [u8], element_size: usize) {
+    let mut previous = 0u64;
+    for element in data.chunks_exact_mut(element_size) {
+        previous = previous.wrapping_add(read_element(element));
+        write_element(element, previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shuffle_groups_bytes() {
+        let data = [1, 2, 3, 4, 5, 6, 7];
+        let shuffled = Filters::SHUFFLE.encode(&data, 2);
+        assert_eq!(shuffled, [1, 3, 5, 2, 4, 6, 7]);
+        assert_eq!(Filters::SHUFFLE.decode(shuffled, 2), data);
+    }
+
+    #[test]
+    fn delta_wraps() {
+        let values: [u16; 4] = [10, 12, 5, 65535];
+        let data: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let encoded = Filters::DELTA.encode(&data, 2);
+        let deltas: Vec<u16> = encoded
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        assert_eq!(deltas, [10, 2, 65529, 65530]);
+        assert_eq!(Filters::DELTA.decode(encoded, 2), data);
+    }
+
+    #[test]
+    fn pipeline_roundtrips_every_width() {
+        let data: Vec<u8> = (0..64u32).map(|i| (i * 37 % 251) as u8).collect();
+        let all = Filters::DELTA | Filters::SHUFFLE;
+        for element_size in [1, 2, 4, 8] {
+            let encoded = all.encode(&data, element_size);
+            assert_eq!(all.decode(encoded, element_size), data);
+        }
+    }
+
+    #[test]
+    fn delta_needs_narrow_elements() {
+        assert!(Filters::DELTA.supports(Some(8)));
+        assert!(!Filters::DELTA.supports(Some(16)));
+        assert!(Filters::SHUFFLE.supports(Some(16)));
+        assert!(!Filters::SHUFFLE.supports(None));
+        assert!(Filters::NONE.supports(None));
+    }
+
+    #[test]
+    fn unknown_bits_rejected() {
+        assert_eq!(
+            Filters::from_u8(0b11),
+            Some(Filters::DELTA | Filters::SHUFFLE)
+        );
+        assert_eq!(Filters::from_u8(0b100), None);
+        assert_eq!(
+            (Filters::DELTA | Filters::SHUFFLE).to_string(),
+            "delta+shuffle"
+        );
+    }
+}
@@ -1,10 +1,228 @@
 //! Header constants and structure
 
+use super::checksum::CHECKSUM_SIZE;
+
 /// Magic bytes identifying a blobfig file
 pub const MAGIC: &[u8; 8] = b"BLOBFIG\0";
 
 /// Current format version
-pub const VERSION: u32 = 1;
+///
+/// Version 2 prefixes Object and List bodies with their byte length so
+/// readers can skip subtrees without decoding them.
+pub const VERSION: u32 = 2;
+
+/// Oldest format version that can still be parsed
+pub const MIN_VERSION: u32 = 1;
 
 /// Header size in bytes (magic + version + flags)
 pub const HEADER_SIZE: usize = 16;
+
+/// Flag: Array and File payloads are padded to start on an aligned offset
+pub const FLAG_ALIGNED: u32 = 1 << 0;
+
+/// Flag: the root value is followed by a path index trailer
+pub const FLAG_PATH_INDEX: u32 = 1 << 1;
+
+/// Flag: payloads carry a checksum and the file ends with a checksum trailer
+pub const FLAG_CHECKSUMS: u32 = 1 << 2;
+
+/// Flag: Array and File sizes are preceded by a compression codec byte
+pub const FLAG_COMPRESSION: u32 = 1 << 3;
+
+/// Flag: Array and File sizes are preceded by a cipher byte
+pub const FLAG_ENCRYPTION: u32 = 1 << 4;
+
+/// Flag: Array and File sizes are preceded by a storage byte, so duplicate
+/// payloads can refer back to an earlier copy
+pub const FLAG_DEDUP: u32 = 1 << 5;
+
+/// Flag: Array shapes are followed by a layout byte with the element order,
+/// and optionally byte strides and axis names
+pub const FLAG_ARRAY_LAYOUT: u32 = 1 << 6;
+
+/// Storage byte of a payload written in place
+pub(crate) const STORAGE_INLINE: u8 = 0x00;
+
+/// Storage byte of a payload that refers to an earlier copy
+pub(crate) const STORAGE_REFERENCE: u8 = 0x01;
+
+/// Bit offset of the log2 payload alignment within the flags word
+const ALIGNMENT_SHIFT: u32 = 8;
+
+/// Mask of the log2 payload alignment (after shifting)
+const ALIGNMENT_MASK: u32 = 0xFF;
+
+/// Largest supported log2 payload alignment
+const MAX_ALIGNMENT_LOG2: u32 = 31;
+
+/// Default payload alignment in bytes (cache line, enough for any SIMD type)
+pub const DEFAULT_ALIGNMENT: u32 = 64;
+
+/// Payload alignment matching the common 4 KiB page size
+pub const PAGE_ALIGNMENT: u32 = 4096;
+
+/// Decoded file header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub version: u32,
+    pub flags: u32,
+}
+
+impl Header {
+    /// Create a header for the current version with the given flags
+    pub fn new(flags: u32) -> Self {
+        Self {
+            version: VERSION,
+            flags,
+        }
+    }
+
+    /// Create a header whose payloads are aligned to `alignment` bytes
+    ///
+    /// Returns None if `alignment` is not a power of two.
+    pub fn aligned(alignment: u32) -> Option<Self> {
+        if !alignment.is_power_of_two() {
+            return None;
+        }
+        let log2 = alignment.trailing_zeros();
+        Some(Self::new(FLAG_ALIGNED | (log2 << ALIGNMENT_SHIFT)))
+    }
+
+    /// Decode a header from its 16-byte representation
+    ///
+    /// Only the layout is decoded; magic and version are not validated.
+    pub fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Self {
+        Self {
+            version: u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            flags: u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+        }
+    }
+
+    /// Encode the header into its 16-byte representation
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..8].copy_from_slice(MAGIC);
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.flags.to_le_bytes());
+        bytes
+    }
+
+    /// Payload alignment in bytes (1 if payloads are unpadded)
+    ///
+    /// Returns None if the flags encode an unsupported alignment.
+    pub fn alignment(&self) -> Option<u64> {
+        if self.flags & FLAG_ALIGNED == 0 {
+            return Some(1);
+        }
+        let log2 = self.alignment_log2();
+        if log2 as u32 > MAX_ALIGNMENT_LOG2 {
+            return None;
+        }
+        Some(1u64 << log2)
+    }
+
+    /// Log2 of the payload alignment recorded in the flags, which may be
+    /// unsupported
+    pub fn alignment_log2(&self) -> u8 {
+        ((self.flags >> ALIGNMENT_SHIFT) & ALIGNMENT_MASK) as u8
+    }
+
+    /// Whether the file ends with a path index trailer
+    pub fn has_path_index(&self) -> bool {
+        self.flags & FLAG_PATH_INDEX != 0
+    }
+
+    /// Whether payloads are followed by checksums and the file ends with a
+    /// checksum trailer
+    pub fn has_checksums(&self) -> bool {
+        self.flags & FLAG_CHECKSUMS != 0
+    }
+
+    /// Whether Array and File sizes are preceded by a compression codec byte
+    pub fn has_compression(&self) -> bool {
+        self.flags & FLAG_COMPRESSION != 0
+    }
+
+    /// Whether Array and File sizes are preceded by a cipher byte
+    pub fn has_encryption(&self) -> bool {
+        self.flags & FLAG_ENCRYPTION != 0
+    }
+
+    /// Whether Array and File sizes are preceded by a storage byte
+    pub fn has_dedup(&self) -> bool {
+        self.flags & FLAG_DEDUP != 0
+    }
+
+    /// Whether Array shapes are followed by a layout byte
+    pub fn has_array_layout(&self) -> bool {
+        self.flags & FLAG_ARRAY_LAYOUT != 0
+    }
+
+    /// Decoding parameters for the value tree
+    ///
+    /// Returns None if the flags encode an unsupported alignment.
+    pub fn layout(&self) -> Option<Layout> {
+        Some(Layout {
+            version: self.version,
+            alignment: self.alignment()?,
+            checksums: self.has_checksums(),
+            compression: self.has_compression(),
+            encryption: self.has_encryption(),
+            dedup: self.has_dedup(),
+            array_layout: self.has_array_layout(),
+        })
+    }
+}
+
+/// Encoding parameters that decoders need to walk the value tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// Format version the value tree was written with
+    pub version: u32,
+    /// Payload alignment in bytes relative to the file start (1 if unpadded)
+    pub alignment: u64,
+    /// Whether every Array and File payload is followed by its checksum
+    pub checksums: bool,
+    /// Whether Array and File sizes are preceded by a compression codec byte
+    pub compression: bool,
+    /// Whether Array and File sizes are preceded by a cipher byte
+    pub encryption: bool,
+    /// Whether Array and File sizes are preceded by a storage byte
+    pub dedup: bool,
+    /// Whether Array shapes are followed by a layout byte
+    pub array_layout: bool,
+}
+
+impl Layout {
+    /// Layout of version 1 files without payload padding
+    pub const V1: Layout = Layout {
+        version: 1,
+        alignment: 1,
+        checksums: false,
+        compression: false,
+        encryption: false,
+        dedup: false,
+        array_layout: false,
+    };
+
+    /// Layout of the current version without payload padding
+    pub const UNALIGNED: Layout = Layout {
+        version: VERSION,
+        alignment: 1,
+        checksums: false,
+        compression: false,
+        encryption: false,
+        dedup: false,
+        array_layout: false,
+    };
+
+    /// Whether Object and List bodies are prefixed with their byte length
+    pub fn has_container_lengths(&self) -> bool {
+        self.version >= 2
+    }
+
+    /// Bytes following each Array and File payload
+    pub fn checksum_size(&self) -> usize {
+        if self.checksums { CHECKSUM_SIZE } else { 0 }
+    }
+}
@@ -0,0 +1,48 @@
+//! Path index trailer layout
+//!
+//! When [`FLAG_PATH_INDEX`](super::FLAG_PATH_INDEX) is set, the root value is
+//! followed by a hash table mapping full slash paths to value offsets:
+//!
+//! ```text
+//! u32 entry_count
+//! u32 bucket_count                      (power of two)
+//! u32 bucket_start[bucket_count + 1]    (prefix sums into the records)
+//! record[entry_count]                   (u64 hash, u64 value_offset, u64 path_offset)
+//! path strings                          (u16 length + UTF-8, at path_offset)
+//! u64 table_offset
+//! [u8; 8] PATH_INDEX_MAGIC
+//! ```
+//!
+//! Records are grouped by bucket (`hash & (bucket_count - 1)`), so a lookup
+//! hashes the path and compares only the records in one bucket. All offsets
+//! are absolute from the file start.
+
+/// Magic closing the path index trailer
+pub const PATH_INDEX_MAGIC: &[u8; 8] = b"BFPATHIX";
+
+/// Size of one fixed-width index record in bytes
+pub const PATH_INDEX_RECORD_SIZE: usize = 24;
+
+/// Size of the trailer footer (table offset + magic) in bytes
+pub const TRAILER_FOOTER_SIZE: usize = 16;
+
+/// Hash of a full slash path as stored in the path index (64-bit FNV-1a)
+pub fn path_hash(path: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01B3;
+
+    path.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_known_values() {
+        assert_eq!(path_hash(""), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(path_hash("a"), 0xAF63_DC4C_8601_EC8C);
+    }
+}
@@ -1,13 +1,49 @@
 //! Core types for blobfig format
 
 mod array;
+mod array_layout;
+mod checksum;
+mod chunked;
+mod compression;
 mod dtype;
+mod encryption;
 mod file;
+mod filter;
 mod header;
+mod index;
+mod quant;
+mod signature;
+mod sparse;
+mod string_array;
+mod timestamp;
 mod value;
 
 pub use array::{Array, ArrayView};
+pub(crate) use array::{check_data_size, invalid_element, raw_size};
+pub use array_layout::{ArrayLayout, Order};
+pub(crate) use array_layout::{LAYOUT_AXIS_NAMES, LAYOUT_FORTRAN, LAYOUT_STRIDES};
+pub use checksum::{CHECKSUM_MAGIC, CHECKSUM_SIZE, checksum, checksum_append};
+pub use chunked::{ChunkData, ChunkProducer, ChunkedArray, ChunkedArrayView};
+pub(crate) use chunked::{
+    check_chunk, check_grid, chunk_bounds, flatten, grid_shape, num_chunks, unravel,
+};
+pub use compression::{Compressed, Compression, DEFAULT_MAX_DECOMPRESSED_SIZE};
+pub(crate) use compression::{check_filters, compress};
 pub use dtype::DType;
+pub use encryption::{Cipher, Encrypted, KEY_SIZE, KeyProvider, NONCE_SIZE, TAG_SIZE};
 pub use file::{File, FileData, FileHandle, FileView};
-pub use header::{HEADER_SIZE, MAGIC, VERSION};
-pub use value::{Value, ValueTag, ValueView};
+pub use filter::Filters;
+pub use header::{
+    DEFAULT_ALIGNMENT, FLAG_ALIGNED, FLAG_ARRAY_LAYOUT, FLAG_CHECKSUMS, FLAG_COMPRESSION,
+    FLAG_DEDUP, FLAG_ENCRYPTION, FLAG_PATH_INDEX, HEADER_SIZE, Header, Layout, MAGIC, MIN_VERSION,
+    PAGE_ALIGNMENT, VERSION,
+};
+pub(crate) use header::{STORAGE_INLINE, STORAGE_REFERENCE};
+pub use index::{PATH_INDEX_MAGIC, PATH_INDEX_RECORD_SIZE, TRAILER_FOOTER_SIZE, path_hash};
+pub use signature::{KeyId, SIGNATURE_MAGIC, SIGNATURE_SIZE};
+pub(crate) use sparse::check_layout as check_sparse_layout;
+pub use sparse::{Sparse, SparseFormat, SparseIndices, SparseView};
+pub use string_array::{StringArray, StringArrayView};
+pub use timestamp::Timestamp;
+pub(crate) use value::{Hops, Unresolved};
+pub use value::{MAX_REFERENCE_HOPS, MAX_REFERENCE_LOOKUPS, Value, ValueTag, ValueView};
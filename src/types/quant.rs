@@ -0,0 +1,151 @@
+//! Block-quantized array elements
+//!
+//! Quantized arrays store their elements in blocks of [`DType::block_len`]
+//! elements and [`DType::block_size`] bytes, following GGML:
+//!
+//! - `I4`: one byte per two elements, the first in the low nibble
+//! - `Q4_0`: an f16 scale `d`, then 16 bytes whose low nibbles hold elements
+//!   0 to 15 and whose high nibbles hold elements 16 to 31, each `(q - 8) * d`
+//! - `Q8_0`: an f16 scale `d`, then 32 i8 values `q`, each `q * d`
+
+use super::DType;
+use super::array::check_data_size;
+use crate::error::BlobfigError;
+
+/// Dequantize the raw elements of a quantized `dtype` array of `shape`
+pub(crate) fn dequantize(
+    dtype: DType,
+    shape: &[u64],
+    data: &[u8],
+) -> Result<Vec<f32>, BlobfigError> {
+    if !dtype.is_quantized() {
+        return Err(BlobfigError::NotQuantized(dtype));
+    }
+    check_data_size(dtype, shape, data.len() as u64)?;
+    // I4 packs two elements per byte, so the element count and the f32
+    // values may not fit in memory even though the data does
+    let product = shape.iter().product::<u64>();
+    let overflow = BlobfigError::SizeOverflow(product);
+    let count = usize::try_from(product).map_err(|_| overflow.clone())?;
+    let capacity = count
+        .checked_next_multiple_of(dtype.block_len())
+        .filter(|&capacity| capacity <= isize::MAX as usize / size_of::<f32>())
+        .ok_or(overflow)?;
+
+    let mut values = Vec::with_capacity(capacity);
+    for block in data.chunks_exact(dtype.block_size()) {
+        match dtype {
+            DType::I4 => {
+                values.extend([low_nibble(block[0]), high_nibble(block[0])].map(f32::from))
+            }
+            DType::Q4_0 => {
+                let (d, qs) = scaled_block(block);
+                let low = qs.iter().map(|&q| q & 0x0F);
+                let high = qs.iter().map(|&q| q >> 4);
+                values.extend(low.chain(high).map(|q| f32::from(q as i8 - 8) * d));
+            }
+            DType::Q8_0 => {
+                let (d, qs) = scaled_block(block);
+                values.extend(qs.iter().map(|&q| f32::from(q as i8) * d));
+            }
+            _ => unreachable!("checked above"),
+        }
+    }
+    values.truncate(count);
+    Ok(values)
+}
+
+/// Scale and quantized values of a Q4_0 or Q8_0 block
+fn scaled_block(block: &[u8]) -> (f32, &[u8]) {
+    let d = f16_to_f32(u16::from_le_bytes([block[0], block[1]]));
+    (d, &block[2..])
+}
+
+/// Low 4 bits of `byte` as a two's complement integer
+fn low_nibble(byte: u8) -> i8 {
+    ((byte << 4) as i8) >> 4
+}
+
+/// High 4 bits of `byte` as a two's complement integer
+fn high_nibble(byte: u8) -> i8 {
+    (byte as i8) >> 4
+}
+
+/// Widen IEEE 754 half-precision bits to f32, which is exact
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = (bits as u32 & 0x8000) << 16;
+    let exponent = (bits as u32 >> 10) & 0x1F;
+    let mantissa = bits as u32 & 0x3FF;
+    match exponent {
+        // Subnormal: mantissa * 2^-24, which is a normal f32
+        0 => {
+            let magnitude = mantissa as f32 * f32::from_bits(0x3380_0000);
+            f32::from_bits(sign | magnitude.to_bits())
+        }
+        // Infinity and NaN
+        0x1F => f32::from_bits(sign | 0x7F80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_widening() {
+        assert_eq!(f16_to_f32(0x3C00), 1.0);
+        assert_eq!(f16_to_f32(0xC000), -2.0);
+        assert_eq!(f16_to_f32(0x3555), 1365.0 / 4096.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x8001), -(2f32.powi(-24)));
+        assert_eq!(f16_to_f32(0x7C00), f32::INFINITY);
+        assert!(f16_to_f32(0x7E00).is_nan());
+    }
+
+    #[test]
+    fn int4_nibbles() {
+        // 1, -1 | -8, 7 | 3 and a padding nibble
+        let values = dequantize(DType::I4, &[5], &[0xF1, 0x78, 0x03]).unwrap();
+        assert_eq!(values, [1.0, -1.0, -8.0, 7.0, 3.0]);
+    }
+
+    #[test]
+    fn q4_0_block() {
+        // Scale 0.5; elements 0 to 15 are q = 9 (1), elements 16 to 31 are q = 0 (-8)
+        let mut block = vec![0x00, 0x38];
+        block.extend([0x09; 16]);
+        let values = dequantize(DType::Q4_0, &[32], &block).unwrap();
+        assert_eq!(values[..16], [0.5; 16]);
+        assert_eq!(values[16..], [-4.0; 16]);
+    }
+
+    #[test]
+    fn q8_0_blocks() {
+        // Two blocks with scales 2 and -1; the second holds one element
+        let mut data = vec![0x00, 0x40];
+        data.extend((0..32).map(|q: i8| (q - 16) as u8));
+        data.extend([0x00, 0xBC, 5]);
+        data.extend([0; 31]);
+        let values = dequantize(DType::Q8_0, &[33], &data).unwrap();
+        assert_eq!(values.len(), 33);
+        assert_eq!(values[0], -32.0);
+        assert_eq!(values[31], 30.0);
+        assert_eq!(values[32], -5.0);
+    }
+
+    #[test]
+    fn other_dtypes_are_not_quantized() {
+        assert_eq!(
+            dequantize(DType::F32, &[1], &[0; 4]),
+            Err(BlobfigError::NotQuantized(DType::F32))
+        );
+        assert_eq!(
+            dequantize(DType::Q8_0, &[1], &[0; 33]),
+            Err(BlobfigError::DataSizeMismatch {
+                expected: 34,
+                actual: 33
+            })
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Signature trailer layout
+//!
+//! A signed file ends with a trailer holding an Ed25519 signature over every
+//! byte before it, including the header and any other trailers:
+//!
+//! ```text
+//! [u8; 8] key_id
+//! [u8; 64] signature
+//! u64 trailer_offset
+//! [u8; 8] SIGNATURE_MAGIC
+//! ```
+//!
+//! The signature trailer is always the last one, so nothing after it can be
+//! left unsigned. Detached signatures use the same 72-byte body on its own.
+
+use std::fmt;
+
+/// Magic closing the signature trailer
+pub const SIGNATURE_MAGIC: &[u8; 8] = b"BFSIGNED";
+
+/// Size of an encoded signature (key ID + Ed25519 signature) in bytes
+pub const SIGNATURE_SIZE: usize = 8 + 64;
+
+/// Identifies the key a document was signed with, so keys can be rotated
+///
+/// The ID is the first 8 bytes of the Ed25519 public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyId(pub [u8; 8]);
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_id_displays_as_hex() {
+        let id = KeyId([0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF]);
+        assert_eq!(id.to_string(), "0123456789abcdef");
+    }
+}
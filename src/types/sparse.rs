@@ -0,0 +1,543 @@
+//! Sparse tensors
+
+use super::array::{check_data_size, expected_size};
+use super::{Array, ArrayView, DType};
+use crate::error::BlobfigError;
+use std::borrow::Cow;
+
+/// How the coordinates of a sparse tensor's values are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SparseFormat {
+    /// Coordinate list: one row of coordinates per value
+    Coo = 0x01,
+    /// Compressed sparse rows of a matrix
+    Csr = 0x02,
+}
+
+impl SparseFormat {
+    /// Try to convert from u8 tag
+    pub fn from_u8(tag: u8) -> Option<Self> {
+        match tag {
+            0x01 => Some(SparseFormat::Coo),
+            0x02 => Some(SparseFormat::Csr),
+            _ => None,
+        }
+    }
+}
+
+/// Index arrays of a sparse tensor
+///
+/// Indices have an unsigned integer dtype. They are written before the
+/// values, in field order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SparseIndices<A> {
+    /// `[nnz, ndim]` coordinates of the values
+    Coo(A),
+    /// `[rows + 1]` offsets of each row's values, and the `[nnz]` column of
+    /// each value
+    Csr { indptr: A, indices: A },
+}
+
+impl<A> SparseIndices<A> {
+    /// The format of these indices
+    pub fn format(&self) -> SparseFormat {
+        match self {
+            SparseIndices::Coo(_) => SparseFormat::Coo,
+            SparseIndices::Csr { .. } => SparseFormat::Csr,
+        }
+    }
+
+    /// The index arrays in write order
+    pub fn iter(&self) -> impl Iterator<Item = &A> {
+        let (first, second) = match self {
+            SparseIndices::Coo(coords) => (coords, None),
+            SparseIndices::Csr { indptr, indices } => (indptr, Some(indices)),
+        };
+        std::iter::once(first).chain(second)
+    }
+
+    /// Apply `f` to every index array
+    pub fn map<'s, B>(&'s self, mut f: impl FnMut(&'s A) -> B) -> SparseIndices<B> {
+        match self {
+            SparseIndices::Coo(coords) => SparseIndices::Coo(f(coords)),
+            SparseIndices::Csr { indptr, indices } => SparseIndices::Csr {
+                indptr: f(indptr),
+                indices: f(indices),
+            },
+        }
+    }
+}
+
+/// Owned sparse tensor (for building/writing)
+///
+/// Elements without an index are zero: every byte of them is 0.
+#[derive(Debug, Clone)]
+pub struct Sparse {
+    /// Shape of the dense tensor
+    pub shape: Vec<u64>,
+    pub indices: SparseIndices<Array>,
+    /// `[nnz]` stored values; their dtype is the dtype of the tensor
+    pub values: Array,
+}
+
+impl Sparse {
+    /// Create a COO tensor from `[nnz, ndim]` coordinates and `[nnz]` values
+    pub fn coo(shape: Vec<u64>, coords: Array, values: Array) -> Self {
+        Self {
+            shape,
+            indices: SparseIndices::Coo(coords),
+            values,
+        }
+    }
+
+    /// Create a CSR matrix from `[rows + 1]` row offsets, `[nnz]` column
+    /// indices and `[nnz]` values
+    pub fn csr(shape: Vec<u64>, indptr: Array, indices: Array, values: Array) -> Self {
+        Self {
+            shape,
+            indices: SparseIndices::Csr { indptr, indices },
+            values,
+        }
+    }
+
+    /// Store the nonzero elements of a dense array
+    ///
+    /// Elements whose bytes are all zero are left out. Indices are U32 if
+    /// every dimension and the value count fit, U64 otherwise. CSR needs a
+    /// two-dimensional array, and quantized arrays cannot be stored sparse.
+    /// The dense array must be in C order.
+    pub fn from_dense(dense: &Array, format: SparseFormat) -> Result<Self, BlobfigError> {
+        let Some(element_size) = dense.dtype.element_size() else {
+            return Err(BlobfigError::InvalidSparse("values cannot be quantized"));
+        };
+        if !dense.layout.is_c_order() {
+            return Err(BlobfigError::InvalidSparse(
+                "dense arrays must be in C order",
+            ));
+        }
+        let data = dense.decompressed()?;
+        check_data_size(dense.dtype, &dense.shape, data.len() as u64)?;
+        if format == SparseFormat::Csr && dense.shape.len() != 2 {
+            return Err(BlobfigError::InvalidSparse(
+                "CSR tensors must be two-dimensional",
+            ));
+        }
+
+        let mut positions = Vec::new();
+        let mut values = Vec::new();
+        for (i, element) in data.chunks_exact(element_size).enumerate() {
+            if element.iter().any(|&b| b != 0) {
+                positions.push(i as u64);
+                values.extend_from_slice(element);
+            }
+        }
+
+        let nnz = positions.len() as u64;
+        let max_index = dense.shape.iter().copied().chain([nnz]).max().unwrap_or(0);
+        let (index_dtype, size) = match u32::try_from(max_index) {
+            Ok(_) => (DType::U32, 4),
+            Err(_) => (DType::U64, 8),
+        };
+        let index_array = |shape: Vec<u64>, indices: &[u64]| {
+            let mut data = Vec::with_capacity(indices.len() * size);
+            for &i in indices {
+                data.extend_from_slice(&i.to_le_bytes()[..size]);
+            }
+            Array::new(index_dtype, shape, data)
+        };
+
+        let values = Array::new(dense.dtype, vec![nnz], values);
+        let shape = dense.shape.clone();
+        Ok(match format {
+            SparseFormat::Coo => {
+                let ndim = shape.len();
+                let mut coords = vec![0; positions.len() * ndim];
+                for (row, &position) in coords.chunks_exact_mut(ndim.max(1)).zip(&positions) {
+                    let mut rest = position;
+                    for (coord, &dim) in row.iter_mut().zip(&shape).rev() {
+                        *coord = rest % dim;
+                        rest /= dim;
+                    }
+                }
+                let coords = index_array(vec![nnz, ndim as u64], &coords);
+                Sparse::coo(shape, coords, values)
+            }
+            SparseFormat::Csr => {
+                let (rows, cols) = (shape[0], shape[1]);
+                let mut indptr = vec![0; rows as usize + 1];
+                for &position in &positions {
+                    indptr[(position / cols) as usize + 1] += 1;
+                }
+                for row in 0..rows as usize {
+                    indptr[row + 1] += indptr[row];
+                }
+                let columns: Vec<u64> = positions.iter().map(|&p| p % cols).collect();
+                let indptr = index_array(vec![rows + 1], &indptr);
+                let columns = index_array(vec![nnz], &columns);
+                Sparse::csr(shape, indptr, columns, values)
+            }
+        })
+    }
+
+    /// Dtype of the tensor
+    pub fn dtype(&self) -> DType {
+        self.values.dtype
+    }
+
+    /// Format of the indices
+    pub fn format(&self) -> SparseFormat {
+        self.indices.format()
+    }
+
+    /// Check shapes, dtypes and every index
+    ///
+    /// Compressed indices are decompressed; encrypted ones fail with
+    /// [`BlobfigError::Encrypted`].
+    pub fn validate(&self) -> Result<(), BlobfigError> {
+        self.view().validate()
+    }
+
+    /// Expand to a dense array, with zeros where there is no value
+    ///
+    /// Values with the same index overwrite each other in order.
+    pub fn to_dense(&self) -> Result<Array, BlobfigError> {
+        self.view().to_dense()
+    }
+
+    /// Index and value arrays in write order
+    pub fn arrays(&self) -> impl Iterator<Item = &Array> {
+        self.indices.iter().chain([&self.values])
+    }
+
+    /// Check shapes and dtypes, and the indices if they are stored raw
+    pub(crate) fn check_stored(&self) -> Result<(), BlobfigError> {
+        self.view().check_stored()
+    }
+
+    fn view(&self) -> SparseView<'_> {
+        fn view(arr: &Array) -> ArrayView<'_> {
+            ArrayView {
+                dtype: arr.dtype,
+                shape: arr.shape.clone(),
+                data: &arr.data,
+                checksum: None,
+                compression: arr.compression,
+                encryption: arr.encryption.clone(),
+                layout: arr.layout.clone(),
+            }
+        }
+        SparseView {
+            shape: self.shape.clone(),
+            indices: self.indices.map(view),
+            values: view(&self.values),
+        }
+    }
+}
+
+/// View into a sparse tensor stored in the blob (zero-copy)
+#[derive(Debug, Clone)]
+pub struct SparseView<'a> {
+    /// Shape of the dense tensor
+    pub shape: Vec<u64>,
+    pub indices: SparseIndices<ArrayView<'a>>,
+    /// `[nnz]` stored values; their dtype is the dtype of the tensor
+    pub values: ArrayView<'a>,
+}
+
+impl<'a> SparseView<'a> {
+    /// Dtype of the tensor
+    pub fn dtype(&self) -> DType {
+        self.values.dtype
+    }
+
+    /// Format of the indices
+    pub fn format(&self) -> SparseFormat {
+        self.indices.format()
+    }
+
+    /// Number of stored values
+    pub fn nnz(&self) -> u64 {
+        self.values.shape.first().copied().unwrap_or(0)
+    }
+
+    /// Check shapes, dtypes and every index
+    ///
+    /// Indices stored raw were already checked when parsing. Compressed
+    /// indices are decompressed; encrypted ones fail with
+    /// [`BlobfigError::Encrypted`].
+    pub fn validate(&self) -> Result<(), BlobfigError> {
+        self.check_layout()?;
+        self.positions(|_, _| {})
+    }
+
+    /// Expand to a dense array, with zeros where there is no value
+    ///
+    /// Values with the same index overwrite each other in order.
+    pub fn to_dense(&self) -> Result<Array, BlobfigError> {
+        self.check_layout()?;
+        let size = expected_size(self.dtype(), &self.shape).ok_or(BlobfigError::ShapeOverflow)?;
+        let size = usize::try_from(size).map_err(|_| BlobfigError::SizeOverflow(size))?;
+        let values = raw_data(&self.values)?;
+        let element_size = self
+            .dtype()
+            .element_size()
+            .ok_or(BlobfigError::InvalidSparse("values cannot be quantized"))?;
+
+        let mut data = vec![0u8; size];
+        self.positions(|k, position| {
+            let (k, position) = (k as usize * element_size, position as usize * element_size);
+            data[position..position + element_size].copy_from_slice(&values[k..k + element_size]);
+        })?;
+        Ok(Array::new(self.dtype(), self.shape.clone(), data))
+    }
+
+    /// Convert to owned Sparse
+    pub fn to_owned(&self) -> Sparse {
+        Sparse {
+            shape: self.shape.clone(),
+            indices: self.indices.map(ArrayView::to_owned),
+            values: self.values.to_owned(),
+        }
+    }
+
+    /// Index and value arrays in write order
+    pub fn arrays(&self) -> impl Iterator<Item = &ArrayView<'a>> {
+        self.indices.iter().chain([&self.values])
+    }
+
+    /// Check shapes and dtypes, and the indices if they are stored raw
+    pub(crate) fn check_stored(&self) -> Result<(), BlobfigError> {
+        self.check_layout()?;
+        let raw = self
+            .indices
+            .iter()
+            .all(|arr| arr.compression.is_none() && arr.encryption.is_none());
+        if raw {
+            self.positions(|_, _| {})?;
+        }
+        Ok(())
+    }
+
+    /// Check shapes and dtypes only
+    pub(crate) fn check_layout(&self) -> Result<(), BlobfigError> {
+        if self.arrays().any(|arr| !arr.layout.is_default()) {
+            return Err(BlobfigError::InvalidSparse(
+                "arrays must have the default array layout",
+            ));
+        }
+        let indices = self.indices.map(|arr| (arr.dtype, arr.shape.as_slice()));
+        let values = (self.values.dtype, self.values.shape.as_slice());
+        check_layout(&self.shape, &indices, values)
+    }
+
+    /// Call `visit` with the number and dense position of every value,
+    /// checking the indices on the way
+    fn positions(&self, mut visit: impl FnMut(u64, u64)) -> Result<(), BlobfigError> {
+        match &self.indices {
+            SparseIndices::Coo(coords) => {
+                let data = raw_data(coords)?;
+                let indices = index_values(coords.dtype, &data);
+                let ndim = self.shape.len();
+                let mut position = 0;
+                for (i, index) in indices.enumerate() {
+                    let dim = self.shape[i % ndim];
+                    if index >= dim {
+                        return Err(BlobfigError::InvalidSparse("coordinate out of bounds"));
+                    }
+                    position = position * dim + index;
+                    if i % ndim == ndim - 1 {
+                        visit((i / ndim) as u64, position);
+                        position = 0;
+                    }
+                }
+                if ndim == 0 {
+                    // Scalars have one element at position 0
+                    (0..self.nnz()).for_each(|k| visit(k, 0));
+                }
+            }
+            SparseIndices::Csr { indptr, indices } => {
+                let indptr_data = raw_data(indptr)?;
+                let indices_data = raw_data(indices)?;
+                let columns: Vec<u64> = index_values(indices.dtype, &indices_data).collect();
+                let cols = self.shape[1];
+                let mut start = 0;
+                for (row, end) in index_values(indptr.dtype, &indptr_data).enumerate() {
+                    let first = row == 0;
+                    if (first && end != 0) || end < start || end > self.nnz() {
+                        return Err(BlobfigError::InvalidSparse("row offsets out of order"));
+                    }
+                    for k in start..end {
+                        let col = columns[k as usize];
+                        if col >= cols {
+                            return Err(BlobfigError::InvalidSparse("column out of bounds"));
+                        }
+                        visit(k, (row as u64 - 1) * cols + col);
+                    }
+                    start = end;
+                }
+                if start != self.nnz() {
+                    return Err(BlobfigError::InvalidSparse("row offsets out of order"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check the dtypes and shapes of a sparse tensor's arrays against its
+/// dense `shape`
+pub(crate) fn check_layout(
+    shape: &[u64],
+    indices: &SparseIndices<(DType, &[u64])>,
+    (values_dtype, values_shape): (DType, &[u64]),
+) -> Result<(), BlobfigError> {
+    let invalid = |reason| Err(BlobfigError::InvalidSparse(reason));
+    expected_size(values_dtype, shape).ok_or(BlobfigError::ShapeOverflow)?;
+    let &[nnz] = values_shape else {
+        return invalid("values must be one-dimensional");
+    };
+    if values_dtype.is_quantized() {
+        return invalid("values cannot be quantized");
+    }
+    if indices.iter().any(|&(dtype, _)| !is_index_dtype(dtype)) {
+        return invalid("indices must be unsigned integers");
+    }
+    match *indices {
+        SparseIndices::Coo((_, coords)) => {
+            if coords != [nnz, shape.len() as u64] {
+                return invalid("COO coordinates must have shape [nnz, ndim]");
+            }
+        }
+        SparseIndices::Csr {
+            indptr: (_, indptr),
+            indices: (_, columns),
+        } => {
+            let &[rows, _] = shape else {
+                return invalid("CSR tensors must be two-dimensional");
+            };
+            if indptr != [rows.saturating_add(1)] || columns != [nnz] {
+                return invalid("CSR indices must have shapes [rows + 1] and [nnz]");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_index_dtype(dtype: DType) -> bool {
+    matches!(dtype, DType::U8 | DType::U16 | DType::U32 | DType::U64)
+}
+
+/// Little-endian unsigned integers of `dtype` in `data`
+fn index_values(dtype: DType, data: &[u8]) -> impl Iterator<Item = u64> + '_ {
+    let Some(size) = dtype.element_size() else {
+        unreachable!("checked by check_layout")
+    };
+    data.chunks_exact(size).map(|bytes| {
+        let mut value = [0u8; 8];
+        value[..bytes.len()].copy_from_slice(bytes);
+        u64::from_le_bytes(value)
+    })
+}
+
+/// Decompressed element bytes of `arr`, checked against its shape
+fn raw_data<'a>(arr: &ArrayView<'a>) -> Result<Cow<'a, [u8]>, BlobfigError> {
+    let data = arr.decompressed()?;
+    check_data_size(arr.dtype, &arr.shape, data.len() as u64)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_array(shape: Vec<u64>, values: &[u32]) -> Array {
+        let data = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        Array::new(DType::U32, shape, data)
+    }
+
+    fn dense() -> Array {
+        // [[0, 5, 0], [0, 0, 0], [7, 0, 9]]
+        u32_array(vec![3, 3], &[0, 5, 0, 0, 0, 0, 7, 0, 9])
+    }
+
+    #[test]
+    fn coo_roundtrips_through_dense() {
+        let sparse = Sparse::from_dense(&dense(), SparseFormat::Coo).unwrap();
+        assert_eq!(sparse.format(), SparseFormat::Coo);
+        let SparseIndices::Coo(coords) = &sparse.indices else {
+            unreachable!()
+        };
+        assert_eq!(coords.dtype, DType::U32);
+        assert_eq!(coords.shape, vec![3, 2]);
+        assert_eq!(coords.data, u32_array(vec![], &[0, 1, 2, 0, 2, 2]).data);
+        assert_eq!(sparse.values.data, u32_array(vec![], &[5, 7, 9]).data);
+        assert_eq!(sparse.to_dense().unwrap().data, dense().data);
+    }
+
+    #[test]
+    fn csr_roundtrips_through_dense() {
+        let sparse = Sparse::from_dense(&dense(), SparseFormat::Csr).unwrap();
+        let SparseIndices::Csr { indptr, indices } = &sparse.indices else {
+            unreachable!()
+        };
+        assert_eq!(indptr.data, u32_array(vec![], &[0, 1, 1, 3]).data);
+        assert_eq!(indices.data, u32_array(vec![], &[1, 0, 2]).data);
+        assert_eq!(sparse.to_dense().unwrap().data, dense().data);
+    }
+
+    #[test]
+    fn scalars_and_empty_tensors() {
+        let scalar = u32_array(vec![], &[3]);
+        let sparse = Sparse::from_dense(&scalar, SparseFormat::Coo).unwrap();
+        assert_eq!(sparse.to_dense().unwrap().data, scalar.data);
+
+        let empty = u32_array(vec![2, 0], &[]);
+        let sparse = Sparse::from_dense(&empty, SparseFormat::Csr).unwrap();
+        assert_eq!(sparse.to_dense().unwrap().data, empty.data);
+    }
+
+    #[test]
+    fn invalid_indices_are_rejected() {
+        let values = || u32_array(vec![2], &[1, 2]);
+
+        let coords = u32_array(vec![2, 2], &[0, 0, 3, 0]);
+        let sparse = Sparse::coo(vec![3, 3], coords, values());
+        assert_eq!(
+            sparse.validate(),
+            Err(BlobfigError::InvalidSparse("coordinate out of bounds"))
+        );
+
+        let coords = u32_array(vec![2, 3], &[0; 6]);
+        let sparse = Sparse::coo(vec![3, 3], coords, values());
+        assert!(matches!(
+            sparse.validate(),
+            Err(BlobfigError::InvalidSparse(_))
+        ));
+
+        let indptr = u32_array(vec![3], &[0, 2, 1]);
+        let columns = u32_array(vec![2], &[0, 1]);
+        let sparse = Sparse::csr(vec![2, 2], indptr, columns, values());
+        assert_eq!(
+            sparse.validate(),
+            Err(BlobfigError::InvalidSparse("row offsets out of order"))
+        );
+
+        let indptr = u32_array(vec![3], &[0, 1, 2]);
+        let columns = u32_array(vec![2], &[0, 2]);
+        let sparse = Sparse::csr(vec![2, 2], indptr, columns, values());
+        assert_eq!(
+            sparse.validate(),
+            Err(BlobfigError::InvalidSparse("column out of bounds"))
+        );
+
+        let coords = Array::new(DType::I32, vec![2, 2], vec![0; 16]);
+        let sparse = Sparse::coo(vec![3, 3], coords, values());
+        assert_eq!(
+            sparse.validate(),
+            Err(BlobfigError::InvalidSparse(
+                "indices must be unsigned integers"
+            ))
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Packed string arrays
+
+use crate::error::BlobfigError;
+
+/// Owned list of strings packed into one buffer (for building/writing)
+///
+/// Encoded as a u32 string count, the u32 byte length of the buffer, one u32
+/// end offset per string and the UTF-8 buffer itself, so a vocabulary costs
+/// four bytes per entry on top of its text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StringArray {
+    data: String,
+    ends: Vec<usize>,
+}
+
+impl StringArray {
+    /// Create an empty string array
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a string
+    pub fn push(&mut self, s: &str) {
+        self.data.push_str(s);
+        self.ends.push(self.data.len());
+    }
+
+    /// Number of strings
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Whether there are no strings
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// The string at `index`, or None if out of bounds
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let end = *self.ends.get(index)?;
+        let start = index.checked_sub(1).map_or(0, |i| self.ends[i]);
+        Some(&self.data[start..end])
+    }
+
+    /// Iterate over the strings in order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &str> + DoubleEndedIterator {
+        (0..self.len()).map(|i| self.get(i).unwrap_or_default())
+    }
+
+    /// All strings concatenated
+    pub(crate) fn data(&self) -> &str {
+        &self.data
+    }
+
+    /// End offset of every string in the concatenated data
+    pub(crate) fn ends(&self) -> &[usize] {
+        &self.ends
+    }
+}
+
+impl From<Vec<String>> for StringArray {
+    fn from(strings: Vec<String>) -> Self {
+        strings.iter().collect()
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for StringArray {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut array = StringArray::new();
+        for s in iter {
+            array.push(s.as_ref());
+        }
+        array
+    }
+}
+
+/// View into a string array stored in the blob (zero-copy)
+///
+/// The end offsets are read from the file on every access, so looking up a
+/// string neither allocates nor decodes the others.
+#[derive(Debug, Clone, Copy)]
+pub struct StringArrayView<'a> {
+    ends: &'a [u8],
+    data: &'a str,
+}
+
+impl<'a> StringArrayView<'a> {
+    /// Check an encoded end offset table against the data it indexes
+    ///
+    /// On failure, also returns the position of the bad bytes relative to
+    /// the start of `ends` (the data follows the table).
+    pub(crate) fn new(ends: &'a [u8], data: &'a [u8]) -> Result<Self, (BlobfigError, usize)> {
+        let data = std::str::from_utf8(data)
+            .map_err(|e| (BlobfigError::InvalidUtf8, ends.len() + e.valid_up_to()))?;
+        let mut start = 0;
+        for (i, end) in ends.chunks_exact(4).enumerate() {
+            let end = u32::from_le_bytes(end.try_into().unwrap()) as usize;
+            if end < start || end > data.len() {
+                return Err((BlobfigError::InvalidStringOffset(end as u32), i * 4));
+            }
+            if !data.is_char_boundary(end) {
+                return Err((BlobfigError::InvalidUtf8, ends.len() + end));
+            }
+            start = end;
+        }
+        if start != data.len() {
+            return Err((BlobfigError::InvalidStringOffset(start as u32), ends.len()));
+        }
+        Ok(Self { ends, data })
+    }
+
+    /// Number of strings
+    pub fn len(&self) -> usize {
+        self.ends.len() / 4
+    }
+
+    /// Whether there are no strings
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// The string at `index`, or None if out of bounds
+    pub fn get(&self, index: usize) -> Option<&'a str> {
+        if index >= self.len() {
+            return None;
+        }
+        let start = index.checked_sub(1).map_or(0, |i| self.end(i));
+        Some(&self.data[start..self.end(index)])
+    }
+
+    /// Iterate over the strings in order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &'a str> + DoubleEndedIterator + use<'a> {
+        let view = *self;
+        (0..self.len()).map(move |i| view.get(i).unwrap_or_default())
+    }
+
+    /// Convert to owned StringArray
+    pub fn to_owned(&self) -> StringArray {
+        StringArray {
+            data: self.data.to_string(),
+            ends: (0..self.len()).map(|i| self.end(i)).collect(),
+        }
+    }
+
+    fn end(&self, index: usize) -> usize {
+        let bytes = &self.ends[index * 4..index * 4 + 4];
+        u32::from_le_bytes(bytes.try_into().unwrap()) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(array: &StringArray) -> Vec<u8> {
+        array
+            .ends()
+            .iter()
+            .flat_map(|&end| (end as u32).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn owned_and_view_agree() {
+        let array = StringArray::from(vec![
+            "<pad>".to_string(),
+            String::new(),
+            "héllo".to_string(),
+        ]);
+        assert_eq!(array.len(), 3);
+        assert_eq!(array.get(2), Some("héllo"));
+        assert_eq!(array.get(3), None);
+
+        let ends = encode(&array);
+        let view = StringArrayView::new(&ends, array.data().as_bytes()).unwrap();
+        assert_eq!(view.len(), 3);
+        assert_eq!(view.get(0), Some("<pad>"));
+        assert_eq!(view.get(1), Some(""));
+        assert_eq!(
+            view.iter().rev().collect::<Vec<_>>(),
+            ["héllo", "", "<pad>"]
+        );
+        assert_eq!(view.to_owned(), array);
+    }
+
+    #[test]
+    fn bad_offsets_are_rejected() {
+        let data = "abcé".as_bytes();
+        let table =
+            |ends: &[u32]| -> Vec<u8> { ends.iter().flat_map(|e| e.to_le_bytes()).collect() };
+
+        let ends = table(&[2, 1, 5]);
+        let err = StringArrayView::new(&ends, data).unwrap_err();
+        assert_eq!(err, (BlobfigError::InvalidStringOffset(1), 4));
+
+        // Splits 'é' in two
+        let ends = table(&[4, 5]);
+        let err = StringArrayView::new(&ends, data).unwrap_err();
+        assert_eq!(err, (BlobfigError::InvalidUtf8, 8 + 4));
+
+        // Leaves bytes after the last string
+        let ends = table(&[3]);
+        let err = StringArrayView::new(&ends, data).unwrap_err();
+        assert_eq!(err, (BlobfigError::InvalidStringOffset(3), 4));
+    }
+}
@@ -0,0 +1,113 @@
+//! Points in time
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Nanoseconds in one second
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+/// Point in time as seconds and nanoseconds since the Unix epoch (UTC)
+///
+/// Encoded as an i64 second count followed by a u32 nanosecond count.
+/// Times before the epoch have negative seconds; the nanoseconds always
+/// count forward from them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    seconds: i64,
+    nanos: u32,
+}
+
+impl Timestamp {
+    /// 1970-01-01T00:00:00Z
+    pub const UNIX_EPOCH: Timestamp = Timestamp {
+        seconds: 0,
+        nanos: 0,
+    };
+
+    /// Create a timestamp, or None if `nanos` is a second or more
+    pub fn new(seconds: i64, nanos: u32) -> Option<Self> {
+        (nanos < NANOS_PER_SEC).then_some(Self { seconds, nanos })
+    }
+
+    /// Create a timestamp from whole seconds since the epoch
+    pub fn from_seconds(seconds: i64) -> Self {
+        Self { seconds, nanos: 0 }
+    }
+
+    /// The current system time
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    /// Whole seconds since the epoch
+    pub fn seconds(self) -> i64 {
+        self.seconds
+    }
+
+    /// Nanoseconds past [`seconds`](Self::seconds), below one second
+    pub fn nanos(self) -> u32 {
+        self.nanos
+    }
+
+    /// Convert to a `SystemTime`, or None if it cannot represent this time
+    pub fn to_system_time(self) -> Option<SystemTime> {
+        let base = match self.seconds {
+            s if s >= 0 => UNIX_EPOCH.checked_add(Duration::from_secs(s as u64))?,
+            s => UNIX_EPOCH.checked_sub(Duration::from_secs(s.unsigned_abs()))?,
+        };
+        base.checked_add(Duration::from_nanos(self.nanos as u64))
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    /// Saturates at the i64 second range
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => Self {
+                seconds: i64::try_from(after.as_secs()).unwrap_or(i64::MAX),
+                nanos: after.subsec_nanos(),
+            },
+            Err(err) => {
+                let before = err.duration();
+                let seconds = i64::try_from(before.as_secs()).map_or(i64::MIN, |s| -s);
+                match before.subsec_nanos() {
+                    0 => Self::from_seconds(seconds),
+                    nanos => Self {
+                        seconds: seconds.saturating_sub(1),
+                        nanos: NANOS_PER_SEC - nanos,
+                    },
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_out_of_range_nanos() {
+        assert!(Timestamp::new(1, NANOS_PER_SEC - 1).is_some());
+        assert!(Timestamp::new(1, NANOS_PER_SEC).is_none());
+    }
+
+    #[test]
+    fn system_time_roundtrip() {
+        for (seconds, nanos) in [
+            (0, 0),
+            (1_700_000_000, 123),
+            (-1, 999_999_999),
+            (-86_400, 0),
+        ] {
+            let timestamp = Timestamp::new(seconds, nanos).unwrap();
+            let time = timestamp.to_system_time().unwrap();
+            assert_eq!(Timestamp::from(time), timestamp);
+        }
+
+        let before = UNIX_EPOCH - Duration::from_millis(1500);
+        assert_eq!(
+            Timestamp::from(before),
+            Timestamp::new(-2, 500_000_000).unwrap()
+        );
+    }
+}
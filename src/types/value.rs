@@ -1,7 +1,18 @@
 //! Value types - owned and view variants
 
-use super::{Array, ArrayView, File, FileView};
+use super::{
+    Array, ArrayView, ChunkedArray, ChunkedArrayView, File, FileView, Sparse, SparseView,
+    StringArray, StringArrayView, Timestamp,
+};
 use crate::error::AccessError;
+use std::time::SystemTime;
+
+/// Maximum length of a chain of references, each of whose targets passes
+/// through the next
+pub const MAX_REFERENCE_HOPS: usize = 16;
+
+/// Maximum number of references followed while resolving one path
+pub const MAX_REFERENCE_LOOKUPS: usize = 256;
 
 /// Value type tags
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,6 +26,14 @@ pub enum ValueTag {
     File = 0x06,
     Object = 0x07,
     List = 0x08,
+    Ref = 0x09,
+    Null = 0x0A,
+    UInt = 0x0B,
+    Bytes = 0x0C,
+    Timestamp = 0x0D,
+    StringArray = 0x0E,
+    Sparse = 0x0F,
+    ChunkedArray = 0x10,
 }
 
 impl ValueTag {
@@ -28,6 +47,14 @@ impl ValueTag {
             0x06 => Some(ValueTag::File),
             0x07 => Some(ValueTag::Object),
             0x08 => Some(ValueTag::List),
+            0x09 => Some(ValueTag::Ref),
+            0x0A => Some(ValueTag::Null),
+            0x0B => Some(ValueTag::UInt),
+            0x0C => Some(ValueTag::Bytes),
+            0x0D => Some(ValueTag::Timestamp),
+            0x0E => Some(ValueTag::StringArray),
+            0x0F => Some(ValueTag::Sparse),
+            0x10 => Some(ValueTag::ChunkedArray),
             _ => None,
         }
     }
@@ -44,6 +71,20 @@ pub enum Value {
     File(File),
     Object(Vec<(String, Value)>),
     List(Vec<Value>),
+    /// Slash path of another value in the same document
+    Ref(String),
+    /// Explicitly unset value
+    Null,
+    UInt(u64),
+    /// Opaque bytes, stored inline like a String
+    Bytes(Vec<u8>),
+    Timestamp(Timestamp),
+    /// Strings packed into one buffer, e.g. a tokenizer vocabulary
+    StringArray(StringArray),
+    /// Tensor storing only its nonzero elements
+    Sparse(Box<Sparse>),
+    /// Array split into a grid of chunks that are read one by one
+    ChunkedArray(ChunkedArray),
 }
 
 impl Value {
@@ -58,6 +99,14 @@ impl Value {
             Value::File(_) => ValueTag::File,
             Value::Object(_) => ValueTag::Object,
             Value::List(_) => ValueTag::List,
+            Value::Ref(_) => ValueTag::Ref,
+            Value::Null => ValueTag::Null,
+            Value::UInt(_) => ValueTag::UInt,
+            Value::Bytes(_) => ValueTag::Bytes,
+            Value::Timestamp(_) => ValueTag::Timestamp,
+            Value::StringArray(_) => ValueTag::StringArray,
+            Value::Sparse(_) => ValueTag::Sparse,
+            Value::ChunkedArray(_) => ValueTag::ChunkedArray,
         }
     }
 }
@@ -75,6 +124,12 @@ impl From<i64> for Value {
     }
 }
 
+impl From<u64> for Value {
+    fn from(v: u64) -> Self {
+        Value::UInt(v)
+    }
+}
+
 impl From<f64> for Value {
     fn from(v: f64) -> Self {
         Value::Float(v)
@@ -93,6 +148,54 @@ impl From<&str> for Value {
     }
 }
 
+impl From<Vec<u8>> for Value {
+    fn from(v: Vec<u8>) -> Self {
+        Value::Bytes(v)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(v: &[u8]) -> Self {
+        Value::Bytes(v.to_vec())
+    }
+}
+
+impl From<Timestamp> for Value {
+    fn from(v: Timestamp) -> Self {
+        Value::Timestamp(v)
+    }
+}
+
+impl From<SystemTime> for Value {
+    fn from(v: SystemTime) -> Self {
+        Value::Timestamp(v.into())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Null, Into::into)
+    }
+}
+
+impl From<StringArray> for Value {
+    fn from(v: StringArray) -> Self {
+        Value::StringArray(v)
+    }
+}
+
+impl From<Sparse> for Value {
+    fn from(v: Sparse) -> Self {
+        Value::Sparse(Box::new(v))
+    }
+}
+
+impl From<ChunkedArray> for Value {
+    fn from(v: ChunkedArray) -> Self {
+        Value::ChunkedArray(v)
+    }
+}
+
 impl From<Array> for Value {
     fn from(v: Array) -> Self {
         Value::Array(v)
@@ -116,6 +219,16 @@ pub enum ValueView<'a> {
     File(FileView<'a>),
     Object(Vec<(&'a str, ValueView<'a>)>),
     List(Vec<ValueView<'a>>),
+    /// Slash path of another value in the same document
+    Ref(&'a str),
+    /// Explicitly unset value
+    Null,
+    UInt(u64),
+    Bytes(&'a [u8]),
+    Timestamp(Timestamp),
+    StringArray(StringArrayView<'a>),
+    Sparse(Box<SparseView<'a>>),
+    ChunkedArray(ChunkedArrayView<'a>),
 }
 
 impl<'a> ValueView<'a> {
@@ -135,6 +248,14 @@ impl<'a> ValueView<'a> {
                     .collect(),
             ),
             ValueView::List(items) => Value::List(items.iter().map(|v| v.to_owned()).collect()),
+            ValueView::Ref(path) => Value::Ref((*path).to_string()),
+            ValueView::Null => Value::Null,
+            ValueView::UInt(u) => Value::UInt(*u),
+            ValueView::Bytes(b) => Value::Bytes(b.to_vec()),
+            ValueView::Timestamp(t) => Value::Timestamp(*t),
+            ValueView::StringArray(s) => Value::StringArray(s.to_owned()),
+            ValueView::Sparse(s) => Value::from(SparseView::to_owned(s)),
+            ValueView::ChunkedArray(c) => Value::ChunkedArray(c.to_owned()),
         }
     }
 
@@ -154,6 +275,14 @@ impl<'a> ValueView<'a> {
         }
     }
 
+    /// Try to get as u64
+    pub fn as_uint(&self) -> Option<u64> {
+        match self {
+            ValueView::UInt(u) => Some(*u),
+            _ => None,
+        }
+    }
+
     /// Try to get as f64
     pub fn as_float(&self) -> Option<f64> {
         match self {
@@ -170,6 +299,35 @@ impl<'a> ValueView<'a> {
         }
     }
 
+    /// Try to get as raw bytes
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            ValueView::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Try to get as timestamp
+    pub fn as_timestamp(&self) -> Option<Timestamp> {
+        match self {
+            ValueView::Timestamp(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    /// Whether this is Null
+    pub fn is_null(&self) -> bool {
+        matches!(self, ValueView::Null)
+    }
+
+    /// Try to get as string array
+    pub fn as_string_array(&self) -> Option<&StringArrayView<'a>> {
+        match self {
+            ValueView::StringArray(s) => Some(s),
+            _ => None,
+        }
+    }
+
     /// Try to get as array
     pub fn as_array(&self) -> Option<&ArrayView<'a>> {
         match self {
@@ -178,6 +336,22 @@ impl<'a> ValueView<'a> {
         }
     }
 
+    /// Try to get as sparse tensor
+    pub fn as_sparse(&self) -> Option<&SparseView<'a>> {
+        match self {
+            ValueView::Sparse(s) => Some(s.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Try to get as chunked array
+    pub fn as_chunked_array(&self) -> Option<&ChunkedArrayView<'a>> {
+        match self {
+            ValueView::ChunkedArray(c) => Some(c),
+            _ => None,
+        }
+    }
+
     /// Try to get as file
     pub fn as_file(&self) -> Option<&FileView<'a>> {
         match self {
@@ -202,29 +376,66 @@ impl<'a> ValueView<'a> {
         }
     }
 
+    /// Try to get the target path of a reference
+    pub fn as_ref_path(&self) -> Option<&'a str> {
+        match self {
+            ValueView::Ref(path) => Some(path),
+            _ => None,
+        }
+    }
+
     /// Get a nested value by path (e.g., "audio/sample_rate")
+    ///
+    /// References met along the path are followed from `self`, which must be
+    /// the document root; see [`resolve`](Self::resolve). Returns None if the
+    /// path is missing or a reference cannot be resolved.
     pub fn get(&self, path: &str) -> Option<&ValueView<'a>> {
-        let mut current = self;
-        for key in path.split('/') {
-            match current {
-                ValueView::Object(entries) => {
-                    current = entries.iter().find(|(k, _)| *k == key).map(|(_, v)| v)?;
-                }
-                _ => return None,
-            }
-        }
-        Some(current)
+        self.resolve(path).ok()
+    }
+
+    /// Get a value by path below `self`, following references from `root`
+    ///
+    /// Use this below nested views; see [`resolve_in`](Self::resolve_in).
+    pub fn get_in<'s>(&'s self, root: &'s ValueView<'a>, path: &str) -> Option<&'s ValueView<'a>> {
+        self.resolve_in(root, path).ok()
+    }
+
+    /// Get a nested value by path, following references
+    ///
+    /// Reference targets are paths from the document root, and are resolved
+    /// from `self`, so `self` must be the root. Below a nested view, use
+    /// [`resolve_in`](Self::resolve_in) instead.
+    ///
+    /// A chain of references, each of whose targets passes through the next,
+    /// is followed for at most [`MAX_REFERENCE_HOPS`] references, and meeting
+    /// a reference of the chain again is reported as a cycle. At most
+    /// [`MAX_REFERENCE_LOOKUPS`] references are followed in all.
+    pub fn resolve(&self, path: &str) -> Result<&ValueView<'a>, AccessError> {
+        self.resolve_in(self, path)
+    }
+
+    /// Get a value by path below `self`, following references from `root`
+    ///
+    /// `root` is the document root that `self` was taken from. Fails like
+    /// [`resolve`](Self::resolve).
+    pub fn resolve_in<'s>(
+        &'s self,
+        root: &'s ValueView<'a>,
+        path: &str,
+    ) -> Result<&'s ValueView<'a>, AccessError> {
+        let mut hops = Hops::default();
+        walk(root, self, path, &mut hops).map_err(|e| e.at(path))
     }
 
     // =========================================================================
     // Convenience accessors that return Result with path context
+    //
+    // Like `resolve`, these follow references from `self`, the document root.
     // =========================================================================
 
     /// Get a bool at path
     pub fn bool(&self, path: &str) -> Result<bool, AccessError> {
-        let value = self.get(path).ok_or_else(|| AccessError::NotFound {
-            path: path.to_string(),
-        })?;
+        let value = self.resolve(path)?;
         value.as_bool().ok_or_else(|| AccessError::TypeMismatch {
             path: path.to_string(),
             expected: "bool",
@@ -234,9 +445,7 @@ impl<'a> ValueView<'a> {
 
     /// Get an i64 at path
     pub fn int(&self, path: &str) -> Result<i64, AccessError> {
-        let value = self.get(path).ok_or_else(|| AccessError::NotFound {
-            path: path.to_string(),
-        })?;
+        let value = self.resolve(path)?;
         value.as_int().ok_or_else(|| AccessError::TypeMismatch {
             path: path.to_string(),
             expected: "int",
@@ -244,11 +453,19 @@ impl<'a> ValueView<'a> {
         })
     }
 
+    /// Get a u64 at path
+    pub fn uint(&self, path: &str) -> Result<u64, AccessError> {
+        let value = self.resolve(path)?;
+        value.as_uint().ok_or_else(|| AccessError::TypeMismatch {
+            path: path.to_string(),
+            expected: "uint",
+            actual: value.tag(),
+        })
+    }
+
     /// Get an f64 at path
     pub fn float(&self, path: &str) -> Result<f64, AccessError> {
-        let value = self.get(path).ok_or_else(|| AccessError::NotFound {
-            path: path.to_string(),
-        })?;
+        let value = self.resolve(path)?;
         value.as_float().ok_or_else(|| AccessError::TypeMismatch {
             path: path.to_string(),
             expected: "float",
@@ -258,9 +475,7 @@ impl<'a> ValueView<'a> {
 
     /// Get a string at path
     pub fn string(&self, path: &str) -> Result<&'a str, AccessError> {
-        let value = self.get(path).ok_or_else(|| AccessError::NotFound {
-            path: path.to_string(),
-        })?;
+        let value = self.resolve(path)?;
         value.as_str().ok_or_else(|| AccessError::TypeMismatch {
             path: path.to_string(),
             expected: "string",
@@ -268,11 +483,43 @@ impl<'a> ValueView<'a> {
         })
     }
 
+    /// Get raw bytes at path
+    pub fn bytes(&self, path: &str) -> Result<&'a [u8], AccessError> {
+        let value = self.resolve(path)?;
+        value.as_bytes().ok_or_else(|| AccessError::TypeMismatch {
+            path: path.to_string(),
+            expected: "bytes",
+            actual: value.tag(),
+        })
+    }
+
+    /// Get a timestamp at path
+    pub fn timestamp(&self, path: &str) -> Result<Timestamp, AccessError> {
+        let value = self.resolve(path)?;
+        value
+            .as_timestamp()
+            .ok_or_else(|| AccessError::TypeMismatch {
+                path: path.to_string(),
+                expected: "timestamp",
+                actual: value.tag(),
+            })
+    }
+
+    /// Get a string array at path
+    pub fn string_array(&self, path: &str) -> Result<&StringArrayView<'a>, AccessError> {
+        let value = self.resolve(path)?;
+        value
+            .as_string_array()
+            .ok_or_else(|| AccessError::TypeMismatch {
+                path: path.to_string(),
+                expected: "string array",
+                actual: value.tag(),
+            })
+    }
+
     /// Get an array at path
     pub fn array(&self, path: &str) -> Result<&ArrayView<'a>, AccessError> {
-        let value = self.get(path).ok_or_else(|| AccessError::NotFound {
-            path: path.to_string(),
-        })?;
+        let value = self.resolve(path)?;
         value.as_array().ok_or_else(|| AccessError::TypeMismatch {
             path: path.to_string(),
             expected: "array",
@@ -280,11 +527,31 @@ impl<'a> ValueView<'a> {
         })
     }
 
+    /// Get a sparse tensor at path
+    pub fn sparse(&self, path: &str) -> Result<&SparseView<'a>, AccessError> {
+        let value = self.resolve(path)?;
+        value.as_sparse().ok_or_else(|| AccessError::TypeMismatch {
+            path: path.to_string(),
+            expected: "sparse",
+            actual: value.tag(),
+        })
+    }
+
+    /// Get a chunked array at path
+    pub fn chunked_array(&self, path: &str) -> Result<&ChunkedArrayView<'a>, AccessError> {
+        let value = self.resolve(path)?;
+        value
+            .as_chunked_array()
+            .ok_or_else(|| AccessError::TypeMismatch {
+                path: path.to_string(),
+                expected: "chunked array",
+                actual: value.tag(),
+            })
+    }
+
     /// Get a file at path
     pub fn file(&self, path: &str) -> Result<&FileView<'a>, AccessError> {
-        let value = self.get(path).ok_or_else(|| AccessError::NotFound {
-            path: path.to_string(),
-        })?;
+        let value = self.resolve(path)?;
         value.as_file().ok_or_else(|| AccessError::TypeMismatch {
             path: path.to_string(),
             expected: "file",
@@ -303,6 +570,107 @@ impl<'a> ValueView<'a> {
             ValueView::File(_) => ValueTag::File,
             ValueView::Object(_) => ValueTag::Object,
             ValueView::List(_) => ValueTag::List,
+            ValueView::Ref(_) => ValueTag::Ref,
+            ValueView::Null => ValueTag::Null,
+            ValueView::UInt(_) => ValueTag::UInt,
+            ValueView::Bytes(_) => ValueTag::Bytes,
+            ValueView::Timestamp(_) => ValueTag::Timestamp,
+            ValueView::StringArray(_) => ValueTag::StringArray,
+            ValueView::Sparse(_) => ValueTag::Sparse,
+            ValueView::ChunkedArray(_) => ValueTag::ChunkedArray,
+        }
+    }
+}
+
+/// Walk `path` from `from`, following references from `root`
+fn walk<'s, 'a>(
+    root: &'s ValueView<'a>,
+    from: &'s ValueView<'a>,
+    path: &str,
+    hops: &mut Hops<'a>,
+) -> Result<&'s ValueView<'a>, Unresolved> {
+    let mut current = from;
+    for key in path.split('/') {
+        current = match follow(root, current, hops)? {
+            ValueView::Object(entries) => entries
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v)
+                .ok_or(Unresolved::NotFound)?,
+            _ => return Err(Unresolved::NotFound),
+        };
+    }
+    follow(root, current, hops)
+}
+
+/// Follow `value` to the first value that is not a reference
+fn follow<'s, 'a>(
+    root: &'s ValueView<'a>,
+    mut value: &'s ValueView<'a>,
+    hops: &mut Hops<'a>,
+) -> Result<&'s ValueView<'a>, Unresolved> {
+    while let &ValueView::Ref(target) = value {
+        hops.push(target)?;
+        value = walk(root, root, target, hops)?;
+        hops.pop();
+    }
+    Ok(value)
+}
+
+/// References being followed while resolving one path
+#[derive(Default)]
+pub(crate) struct Hops<'a> {
+    /// Targets of the chain being followed, innermost last
+    chain: Vec<&'a str>,
+    /// References followed so far
+    total: usize,
+}
+
+impl<'a> Hops<'a> {
+    /// Start following a reference to `target`
+    pub(crate) fn push(&mut self, target: &'a str) -> Result<(), Unresolved> {
+        if self.chain.contains(&target) {
+            return Err(Unresolved::Cycle);
+        }
+        if self.chain.len() == MAX_REFERENCE_HOPS {
+            return Err(Unresolved::TooManyHops);
+        }
+        if self.total == MAX_REFERENCE_LOOKUPS {
+            return Err(Unresolved::TooManyLookups);
+        }
+        self.chain.push(target);
+        self.total += 1;
+        Ok(())
+    }
+
+    /// Finish following the innermost reference
+    pub(crate) fn pop(&mut self) {
+        self.chain.pop();
+    }
+}
+
+/// Reason a path could not be resolved
+pub(crate) enum Unresolved {
+    NotFound,
+    Cycle,
+    TooManyHops,
+    TooManyLookups,
+}
+
+impl Unresolved {
+    fn at(self, path: &str) -> AccessError {
+        let path = path.to_string();
+        match self {
+            Unresolved::NotFound => AccessError::NotFound { path },
+            Unresolved::Cycle => AccessError::ReferenceCycle { path },
+            Unresolved::TooManyHops => AccessError::TooManyReferences {
+                path,
+                max: MAX_REFERENCE_HOPS,
+            },
+            Unresolved::TooManyLookups => AccessError::TooManyReferences {
+                path,
+                max: MAX_REFERENCE_LOOKUPS,
+            },
         }
     }
 }
@@ -1,18 +1,93 @@
 //! Array serialization
 
-use crate::types::{Array, ValueTag};
+use super::encoder::Encoder;
+use super::measure::chunk_lens;
+use crate::types::{
+    Array, ChunkData, ChunkedArray, ValueTag, checksum, chunk_bounds, num_chunks, unravel,
+};
 use std::io::{self, Write};
 
-/// Write an array value
-pub fn write_array<W: Write>(writer: &mut W, arr: &Array) -> io::Result<()> {
+/// Write an array value (data is padded to the encoder's alignment)
+///
+/// Data identical to an earlier payload is written as a reference if the
+/// encoder deduplicates payloads.
+///
+/// Fails with `InvalidInput` if the data size does not match shape and dtype,
+/// or if there are more than 255 dimensions.
+pub fn write_array<W: Write>(writer: &mut Encoder<W>, arr: &Array) -> io::Result<()> {
+    arr.validate().map_err(|e| writer.invalid_input(e))?;
+    writer.check_len("array dimension count", arr.shape.len(), u8::MAX as u64)?;
+
     writer.write_all(&[ValueTag::Array as u8])?;
     writer.write_all(&[arr.dtype as u8])?;
     writer.write_all(&[arr.shape.len() as u8])?;
     for dim in &arr.shape {
         writer.write_all(&dim.to_le_bytes())?;
     }
-    writer.write_all(&(arr.data.len() as u64).to_le_bytes())?;
+    writer.write_array_layout(&arr.layout)?;
+    writer.write_compression(arr.compression)?;
+    writer.write_encryption(arr.encryption.as_ref())?;
+    let size = arr.data.len() as u64;
+    if writer.write_reference(size)? {
+        return Ok(());
+    }
+    writer.write_all(&size.to_le_bytes())?;
+    writer.align()?;
+    let data_offset = writer.position();
     writer.write_all(&arr.data)?;
+    let checksum = writer.layout().checksums.then(|| checksum(&arr.data));
+    if let Some(checksum) = checksum {
+        writer.write_all(&checksum.to_le_bytes())?;
+    }
+    writer.record_payload(data_offset, checksum, size);
+    Ok(())
+}
+
+/// Write a chunked array, asking its producer for each chunk in turn
+///
+/// The chunk offset table is computed from the chunk sizes before the first
+/// chunk is written. Fails with `InvalidInput` if the chunks do not match the
+/// grid, including a produced chunk of the wrong size.
+pub fn write_chunked_array<W: Write>(
+    writer: &mut Encoder<W>,
+    mut chunked: ChunkedArray,
+) -> io::Result<()> {
+    chunked.validate().map_err(|e| writer.invalid_input(e))?;
+    writer.check_len("array dimension count", chunked.shape.len(), u8::MAX as u64)?;
+
+    writer.write_all(&[ValueTag::ChunkedArray as u8])?;
+    writer.write_all(&[chunked.dtype as u8])?;
+    writer.write_all(&[chunked.shape.len() as u8])?;
+    for dim in chunked.shape.iter().chain(&chunked.chunk_shape) {
+        writer.write_all(&dim.to_le_bytes())?;
+    }
+
+    let grid = chunked.grid();
+    let count = num_chunks(&grid);
+    let chunks_start = writer.position() + 8 * count;
+    let mut pending = writer.pending_payloads();
+    let lens = chunk_lens(&chunked, chunks_start, writer.layout(), &mut pending);
+    let mut offset = 0u64;
+    for len in lens {
+        writer.write_all(&offset.to_le_bytes())?;
+        offset += len;
+    }
+
+    match &mut chunked.chunks {
+        ChunkData::Arrays(chunks) => {
+            for chunk in chunks.iter() {
+                write_array(writer, chunk)?;
+            }
+        }
+        ChunkData::Producer(producer) => {
+            for flat in 0..count {
+                let index = unravel(&grid, flat);
+                let shape = chunk_bounds(&chunked.shape, &chunked.chunk_shape, &index).1;
+                let data = producer.chunk(&index, &shape)?;
+                write_array(writer, &Array::new(chunked.dtype, shape, data))?;
+            }
+        }
+    }
     Ok(())
 }
 
@@ -20,7 +95,7 @@ pub fn write_array<W: Write>(writer: &mut W, arr: &Array) -> io::Result<()> {
 mod tests {
     use super::*;
     use crate::parser::parse_value;
-    use crate::types::DType;
+    use crate::types::{DType, Layout, VERSION};
     use parsicomb::{ByteCursor, Parser};
 
     #[test]
@@ -29,10 +104,10 @@ mod tests {
         let arr = Array::new(DType::U8, vec![4], data.clone());
 
         let mut buf = Vec::new();
-        write_array(&mut buf, &arr).unwrap();
+        write_array(&mut Encoder::new(&mut buf, 1), &arr).unwrap();
 
         let cursor = ByteCursor::new(&buf);
-        let (parsed, _) = parse_value().parse(cursor).unwrap();
+        let (parsed, _) = parse_value(Layout::UNALIGNED).parse(cursor).unwrap();
 
         let a = parsed.as_array().unwrap();
         assert_eq!(a.dtype, DType::U8);
@@ -47,10 +122,10 @@ mod tests {
         let arr = Array::new(DType::F32, vec![2, 3], data.clone());
 
         let mut buf = Vec::new();
-        write_array(&mut buf, &arr).unwrap();
+        write_array(&mut Encoder::new(&mut buf, 1), &arr).unwrap();
 
         let cursor = ByteCursor::new(&buf);
-        let (parsed, _) = parse_value().parse(cursor).unwrap();
+        let (parsed, _) = parse_value(Layout::UNALIGNED).parse(cursor).unwrap();
 
         let a = parsed.as_array().unwrap();
         assert_eq!(a.dtype, DType::F32);
@@ -64,10 +139,10 @@ mod tests {
         let arr = Array::new(DType::U8, vec![2, 3, 4], data.clone());
 
         let mut buf = Vec::new();
-        write_array(&mut buf, &arr).unwrap();
+        write_array(&mut Encoder::new(&mut buf, 1), &arr).unwrap();
 
         let cursor = ByteCursor::new(&buf);
-        let (parsed, _) = parse_value().parse(cursor).unwrap();
+        let (parsed, _) = parse_value(Layout::UNALIGNED).parse(cursor).unwrap();
 
         let a = parsed.as_array().unwrap();
         assert_eq!(a.dtype, DType::U8);
@@ -80,10 +155,10 @@ mod tests {
         let arr = Array::new(DType::F64, vec![0], vec![]);
 
         let mut buf = Vec::new();
-        write_array(&mut buf, &arr).unwrap();
+        write_array(&mut Encoder::new(&mut buf, 1), &arr).unwrap();
 
         let cursor = ByteCursor::new(&buf);
-        let (parsed, _) = parse_value().parse(cursor).unwrap();
+        let (parsed, _) = parse_value(Layout::UNALIGNED).parse(cursor).unwrap();
 
         let a = parsed.as_array().unwrap();
         assert_eq!(a.dtype, DType::F64);
@@ -105,17 +180,72 @@ mod tests {
             DType::F32,
             DType::F64,
         ] {
-            let data = vec![0u8; 8];
+            let data = vec![0u8; 8 * dtype.block_size()];
             let arr = Array::new(dtype, vec![8], data.clone());
 
             let mut buf = Vec::new();
-            write_array(&mut buf, &arr).unwrap();
+            write_array(&mut Encoder::new(&mut buf, 1), &arr).unwrap();
 
             let cursor = ByteCursor::new(&buf);
-            let (parsed, _) = parse_value().parse(cursor).unwrap();
+            let (parsed, _) = parse_value(Layout::UNALIGNED).parse(cursor).unwrap();
 
             let a = parsed.as_array().unwrap();
             assert_eq!(a.dtype, dtype);
         }
     }
+
+    #[test]
+    fn roundtrip_array_aligned() {
+        let arr = Array::new(DType::F64, vec![2], vec![7u8; 16]);
+
+        let mut buf = Vec::new();
+        write_array(&mut Encoder::new(&mut buf, 64), &arr).unwrap();
+
+        // dtype + ndim + shape + data_len = 19 bytes of header, padded to 64
+        assert_eq!(buf.len(), 64 + 16);
+
+        let cursor = ByteCursor::new(&buf);
+        let (parsed, _) = parse_value(Layout {
+            version: VERSION,
+            alignment: 64,
+            checksums: false,
+            compression: false,
+            encryption: false,
+            dedup: false,
+            array_layout: false,
+        })
+        .parse(cursor)
+        .unwrap();
+
+        let a = parsed.as_array().unwrap();
+        assert!(std::ptr::eq(a.data.as_ptr(), buf[64..].as_ptr()));
+    }
+
+    #[test]
+    fn rejects_inconsistent_array() {
+        let arr = Array::new(DType::F32, vec![2, 3], vec![0u8; 20]);
+
+        let mut buf = Vec::new();
+        let err = write_array(&mut Encoder::new(&mut buf, 1), &arr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("expected 24 bytes, got 20"));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn dimension_count_boundary() {
+        let arr = Array::new(DType::U8, vec![1; 255], vec![7]);
+        let mut buf = Vec::new();
+        write_array(&mut Encoder::new(&mut buf, 1), &arr).unwrap();
+        let (parsed, _) = parse_value(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&buf))
+            .unwrap();
+        assert_eq!(parsed.as_array().unwrap().shape.len(), 255);
+
+        let arr = Array::new(DType::U8, vec![1; 256], vec![7]);
+        let mut buf = Vec::new();
+        let err = write_array(&mut Encoder::new(&mut buf, 1), &arr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("dimension count 256"));
+    }
 }
@@ -0,0 +1,165 @@
+//! Size-threshold compression applied before writing
+
+use crate::types::{
+    Array, ChunkData, ChunkedArray, Compressed, Compression, File, FileData, Filters, Sparse,
+    SparseIndices, Value, compress,
+};
+use std::io;
+
+/// Compress every raw Array and File payload of at least `min_size` bytes
+///
+/// Arrays are filtered with [`Filters::for_dtype`] first. Payloads that do
+/// not get smaller are written raw, and encrypted payloads are left alone.
+/// Files streamed from handles are left alone so they stay streamed;
+/// compress them up front with [`File::compress`] instead. Chunks from a
+/// producer are always written raw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    pub codec: Compression,
+    /// Smallest payload size (in bytes) worth compressing
+    pub min_size: u64,
+}
+
+impl CompressionPolicy {
+    /// Apply the policy to every payload in `value`
+    pub(crate) fn apply(&self, value: Value) -> io::Result<Value> {
+        Ok(match value {
+            Value::Array(arr) => Value::Array(self.apply_array(arr)?),
+            Value::Sparse(sparse) => Value::Sparse(Box::new(self.apply_sparse(*sparse)?)),
+            Value::ChunkedArray(chunked) => Value::ChunkedArray(self.apply_chunked(chunked)?),
+            Value::File(file) => Value::File(self.apply_file(file)?),
+            Value::Object(entries) => Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, self.apply(value)?)))
+                    .collect::<io::Result<_>>()?,
+            ),
+            Value::List(items) => Value::List(
+                items
+                    .into_iter()
+                    .map(|item| self.apply(item))
+                    .collect::<io::Result<_>>()?,
+            ),
+            scalar => scalar,
+        })
+    }
+
+    fn apply_array(&self, mut arr: Array) -> io::Result<Array> {
+        let filters = Filters::for_dtype(arr.dtype);
+        if arr.compression.is_none()
+            && arr.encryption.is_none()
+            && arr.data.len() as u64 >= self.min_size
+            && let Some(stored) =
+                self.compress_smaller(filters, arr.dtype.element_size(), &arr.data)?
+        {
+            arr.compression = Some(Compressed {
+                codec: self.codec,
+                filters,
+                raw_size: arr.data.len() as u64,
+            });
+            arr.data = stored;
+        }
+        Ok(arr)
+    }
+
+    fn apply_sparse(&self, sparse: Sparse) -> io::Result<Sparse> {
+        let indices = match sparse.indices {
+            SparseIndices::Coo(coords) => SparseIndices::Coo(self.apply_array(coords)?),
+            SparseIndices::Csr { indptr, indices } => SparseIndices::Csr {
+                indptr: self.apply_array(indptr)?,
+                indices: self.apply_array(indices)?,
+            },
+        };
+        Ok(Sparse {
+            shape: sparse.shape,
+            indices,
+            values: self.apply_array(sparse.values)?,
+        })
+    }
+
+    fn apply_chunked(&self, mut chunked: ChunkedArray) -> io::Result<ChunkedArray> {
+        if let ChunkData::Arrays(chunks) = chunked.chunks {
+            let chunks = chunks
+                .into_iter()
+                .map(|chunk| self.apply_array(chunk))
+                .collect::<io::Result<_>>()?;
+            chunked.chunks = ChunkData::Arrays(chunks);
+        }
+        Ok(chunked)
+    }
+
+    fn apply_file(&self, mut file: File) -> io::Result<File> {
+        if let (None, None, FileData::Bytes(bytes)) =
+            (file.compression, &file.encryption, &file.data)
+            && bytes.len() as u64 >= self.min_size
+            && let Some(stored) = self.compress_smaller(Filters::NONE, Some(1), bytes)?
+        {
+            file.compression = Some(Compressed {
+                codec: self.codec,
+                filters: Filters::NONE,
+                raw_size: bytes.len() as u64,
+            });
+            file.data = FileData::Bytes(stored);
+        }
+        Ok(file)
+    }
+
+    /// Compressed `data`, or None if compressing does not save space
+    fn compress_smaller(
+        &self,
+        filters: Filters,
+        element_size: Option<usize>,
+        data: &[u8],
+    ) -> io::Result<Option<Vec<u8>>> {
+        let stored = compress(self.codec, filters, element_size, data)?;
+        Ok((stored.len() < data.len()).then_some(stored))
+    }
+}
+
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use super::*;
+    use crate::types::DType;
+
+    #[test]
+    fn policy_respects_threshold_and_gain() {
+        let policy = CompressionPolicy {
+            codec: Compression::Lz4,
+            min_size: 64,
+        };
+        let compressible = Array::new(DType::U8, vec![1000], vec![0; 1000]);
+        let small = Array::new(DType::U8, vec![32], vec![0; 32]);
+        let noise: Vec<u8> = (0..256u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect();
+        let incompressible = File::from_bytes("application/octet-stream", noise.clone());
+
+        let value = policy
+            .apply(Value::List(vec![
+                Value::Array(compressible),
+                Value::Array(small),
+                Value::File(incompressible),
+            ]))
+            .unwrap();
+        let Value::List(items) = value else {
+            unreachable!()
+        };
+
+        let Value::Array(arr) = &items[0] else {
+            unreachable!()
+        };
+        assert_eq!(arr.compression.unwrap().raw_size, 1000);
+        assert!(arr.data.len() < 1000);
+        assert_eq!(arr.decompressed().unwrap(), vec![0; 1000]);
+
+        let Value::Array(arr) = &items[1] else {
+            unreachable!()
+        };
+        assert!(arr.compression.is_none());
+
+        let Value::File(file) = &items[2] else {
+            unreachable!()
+        };
+        assert!(file.compression.is_none());
+    }
+}
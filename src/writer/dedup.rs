@@ -0,0 +1,183 @@
+//! Deduplication of identical payloads
+//!
+//! Before writing, every in-memory Array and File payload is hashed and
+//! compared with the payloads before it. A payload identical to an earlier
+//! one is written as a reference to the earlier copy's data instead.
+
+use crate::types::{ChunkData, FileData, Value, checksum, num_chunks};
+use std::collections::HashMap;
+use std::slice;
+
+/// Payloads this small are always written in place, since a reference is
+/// no smaller than the data it replaces
+const MIN_SIZE: usize = 9;
+
+/// Counts of the payloads written as references to earlier copies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Array and File payloads written in total
+    pub payloads: usize,
+    /// Payloads written as references to an earlier copy
+    pub duplicates: usize,
+    /// Payload bytes not written because they were duplicates
+    pub bytes_saved: u64,
+}
+
+/// Duplicate payloads of one value tree and the locations of those written
+pub(crate) struct Dedup {
+    /// For each payload in write order, the earlier payload it duplicates
+    duplicates: Vec<Option<usize>>,
+    /// Data offset and checksum of each payload written so far
+    written: Vec<(u64, Option<u32>)>,
+    stats: DedupStats,
+}
+
+impl Dedup {
+    /// Find the payloads of `value` that duplicate an earlier payload
+    ///
+    /// Streamed File handles and produced chunks are never deduplicated.
+    pub fn plan(value: &Value) -> Self {
+        let mut payloads = Vec::new();
+        collect(value, &mut payloads);
+
+        // Payloads are keyed by checksum and length, then compared in full
+        let mut originals: HashMap<(u32, usize), Vec<usize>> = HashMap::new();
+        let mut duplicates = Vec::with_capacity(payloads.len());
+        for (i, data) in payloads.iter().enumerate() {
+            let Some(data) = data.filter(|data| data.len() >= MIN_SIZE) else {
+                duplicates.push(None);
+                continue;
+            };
+            let candidates = originals.entry((checksum(data), data.len())).or_default();
+            let original = candidates
+                .iter()
+                .copied()
+                .find(|&j| payloads[j] == Some(data));
+            if original.is_none() {
+                candidates.push(i);
+            }
+            duplicates.push(original);
+        }
+
+        Self {
+            duplicates,
+            written: Vec::new(),
+            stats: DedupStats::default(),
+        }
+    }
+
+    /// For each payload not written yet, whether it is a duplicate
+    pub fn pending(&self) -> Pending<'_> {
+        self.duplicates[self.written.len()..].iter()
+    }
+
+    /// Data offset and checksum of the earlier copy of the next payload
+    pub fn original(&self) -> Option<(u64, Option<u32>)> {
+        let original = self.duplicates.get(self.written.len()).copied().flatten()?;
+        Some(self.written[original])
+    }
+
+    /// Record that the next payload was written, in place or as a reference
+    pub fn record(&mut self, data_offset: u64, checksum: Option<u32>, size: u64) {
+        if self.original().is_some() {
+            self.stats.duplicates += 1;
+            self.stats.bytes_saved += size;
+        }
+        self.stats.payloads += 1;
+        self.written.push((data_offset, checksum));
+    }
+
+    pub fn stats(&self) -> DedupStats {
+        self.stats
+    }
+}
+
+/// Whether each of the remaining payloads is written as a reference
+pub(crate) type Pending<'a> = slice::Iter<'a, Option<usize>>;
+
+/// Payload bytes of `value` in write order (None for streamed handles and
+/// produced chunks)
+fn collect<'a>(value: &'a Value, payloads: &mut Vec<Option<&'a [u8]>>) {
+    match value {
+        Value::Array(arr) => payloads.push(Some(&arr.data)),
+        Value::Sparse(sparse) => payloads.extend(sparse.arrays().map(|arr| Some(&arr.data[..]))),
+        Value::ChunkedArray(chunked) => match &chunked.chunks {
+            ChunkData::Arrays(chunks) => payloads.extend(chunks.iter().map(|c| Some(&c.data[..]))),
+            ChunkData::Producer(_) => {
+                let count = num_chunks(&chunked.grid());
+                payloads.extend((0..count).map(|_| None));
+            }
+        },
+        Value::File(file) => payloads.push(match &file.data {
+            FileData::Bytes(bytes) => Some(bytes),
+            FileData::Handle(_) => None,
+        }),
+        Value::Object(entries) => {
+            for (_, value) in entries {
+                collect(value, payloads);
+            }
+        }
+        Value::List(items) => {
+            for item in items {
+                collect(item, payloads);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Array, DType, File};
+
+    #[test]
+    fn finds_earlier_copies() {
+        let weights = vec![7u8; 32];
+        let value = Value::Object(vec![
+            (
+                "a".into(),
+                Value::Array(Array::new(DType::U8, vec![32], weights.clone())),
+            ),
+            (
+                "b".into(),
+                Value::List(vec![
+                    Value::File(File::from_bytes("text/plain", vec![1; 32])),
+                    Value::File(File::from_bytes("text/plain", weights.clone())),
+                    Value::Array(Array::new(DType::U8, vec![4], vec![0; 4])),
+                    Value::Array(Array::new(DType::U8, vec![4], vec![0; 4])),
+                ]),
+            ),
+            (
+                "c".into(),
+                Value::Array(Array::new(DType::U32, vec![8], weights)),
+            ),
+        ]);
+
+        let dedup = Dedup::plan(&value);
+        // Payloads below the minimum size are kept in place
+        assert_eq!(dedup.duplicates, [None, None, Some(0), None, None, Some(0)]);
+        assert_eq!(dedup.pending().filter(|d| d.is_some()).count(), 2);
+    }
+
+    #[test]
+    fn stats_count_references() {
+        let arr = || Value::Array(Array::new(DType::U8, vec![16], vec![3; 16]));
+        let mut dedup = Dedup::plan(&Value::List(vec![arr(), arr(), arr()]));
+
+        assert_eq!(dedup.original(), None);
+        dedup.record(64, Some(9), 16);
+        for _ in 0..2 {
+            assert_eq!(dedup.original(), Some((64, Some(9))));
+            dedup.record(64, Some(9), 16);
+        }
+        assert_eq!(
+            dedup.stats(),
+            DedupStats {
+                payloads: 3,
+                duplicates: 2,
+                bytes_saved: 32,
+            }
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Position-tracking output stream
+
+use super::dedup::{Dedup, DedupStats, Pending};
+use super::index::PathIndexBuilder;
+use super::measure::{encoded_len, padding};
+use crate::types::{
+    ArrayLayout, CHECKSUM_MAGIC, Compressed, Encrypted, Layout, STORAGE_INLINE, STORAGE_REFERENCE,
+    VERSION, Value, checksum_append,
+};
+use std::fmt;
+use std::io::{self, Write};
+use std::vec;
+
+/// Writer wrapper that tracks the absolute output position so payloads can
+/// be padded to the configured alignment and indexed by path
+pub struct Encoder<W> {
+    writer: W,
+    position: u64,
+    layout: Layout,
+    /// Keys and list indices leading to the value being written
+    path: Vec<String>,
+    index: Option<PathIndexBuilder>,
+    /// Checksum of every byte written so far, if checksums are enabled
+    digest: Option<u32>,
+    dedup: Option<Dedup>,
+    /// Body lengths of the Objects and Lists still to be written
+    body_lens: vec::IntoIter<u64>,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Wrap a writer positioned at the start of the file
+    pub fn new(writer: W, alignment: u64) -> Self {
+        Self {
+            writer,
+            position: 0,
+            layout: Layout {
+                version: VERSION,
+                alignment,
+                checksums: false,
+                compression: false,
+                encryption: false,
+                dedup: false,
+                array_layout: false,
+            },
+            path: Vec::new(),
+            index: None,
+            digest: None,
+            dedup: None,
+            body_lens: Vec::new().into_iter(),
+        }
+    }
+
+    /// Precede Array and File sizes with a compression codec byte
+    pub fn with_compression(mut self) -> Self {
+        self.layout.compression = true;
+        self
+    }
+
+    /// Precede Array and File sizes with a cipher byte
+    pub fn with_encryption(mut self) -> Self {
+        self.layout.encryption = true;
+        self
+    }
+
+    /// Follow Array shapes with a layout byte
+    pub fn with_array_layout(mut self) -> Self {
+        self.layout.array_layout = true;
+        self
+    }
+
+    /// Precede Array and File sizes with a storage byte and write the
+    /// duplicates found by `dedup` as references
+    pub fn with_dedup(mut self, dedup: Dedup) -> Self {
+        self.layout.dedup = true;
+        self.dedup = Some(dedup);
+        self
+    }
+
+    /// Follow payloads with their checksum and end with a checksum trailer
+    pub fn with_checksums(mut self) -> Self {
+        self.layout.checksums = true;
+        self.digest = Some(0);
+        self
+    }
+
+    /// Record value offsets for a path index trailer
+    pub fn with_path_index(mut self) -> Self {
+        self.index = Some(PathIndexBuilder::default());
+        self
+    }
+
+    /// Number of bytes written so far
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Layout the value tree is written with
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Measure the Objects and Lists of `value`, which is written next
+    ///
+    /// Their body lengths depend on the position and on which payloads are
+    /// duplicates, so they are measured in one pass over the whole tree.
+    pub fn measure(&mut self, value: &Value) {
+        let mut pending = self.pending_payloads();
+        let mut bodies = Vec::new();
+        encoded_len(value, self.position, self.layout, &mut pending, &mut bodies);
+        self.body_lens = bodies.into_iter();
+    }
+
+    /// Body length of the next Object or List, as measured by
+    /// [`Encoder::measure`]
+    pub fn next_body_len(&mut self) -> io::Result<u64> {
+        self.body_lens
+            .next()
+            .ok_or_else(|| io::Error::other("container written without being measured"))
+    }
+
+    /// Write zero padding up to the next multiple of the alignment
+    pub fn align(&mut self) -> io::Result<()> {
+        const ZEROS: [u8; 64] = [0u8; 64];
+
+        let mut padding = padding(self.position, self.layout.alignment);
+        while padding > 0 {
+            let n = std::cmp::min(padding, ZEROS.len() as u64) as usize;
+            self.write_all(&ZEROS[..n])?;
+            padding -= n as u64;
+        }
+        Ok(())
+    }
+
+    /// Slash path of the value being written (empty for the root)
+    pub fn path(&self) -> String {
+        self.path.join("/")
+    }
+
+    /// Error for a value that cannot be encoded, naming the current path
+    pub fn invalid_input(&self, message: impl fmt::Display) -> io::Error {
+        let message = match self.path.is_empty() {
+            true => format!("{} at root", message),
+            false => format!("{} at '{}'", message, self.path()),
+        };
+        io::Error::new(io::ErrorKind::InvalidInput, message)
+    }
+
+    /// Check that `len` fits in a length field whose maximum is `max`
+    pub fn check_len(&self, field: &str, len: usize, max: u64) -> io::Result<()> {
+        if len as u64 > max {
+            return Err(
+                self.invalid_input(format!("{} {} exceeds the maximum of {}", field, len, max))
+            );
+        }
+        Ok(())
+    }
+
+    /// Write the codec fields preceding an Array or File data size
+    ///
+    /// Fails with `InvalidInput` for compressed payloads if the layout has no
+    /// codec fields.
+    pub fn write_compression(&mut self, compression: Option<Compressed>) -> io::Result<()> {
+        if !self.layout.compression {
+            return match compression {
+                Some(compressed) => Err(self.invalid_input(format!(
+                    "{} payload requires a compression layout",
+                    compressed.codec
+                ))),
+                None => Ok(()),
+            };
+        }
+        match compression {
+            None => self.write_all(&[0]),
+            Some(compressed) => {
+                self.write_all(&[compressed.codec as u8, compressed.filters.bits()])?;
+                self.write_all(&compressed.raw_size.to_le_bytes())
+            }
+        }
+    }
+
+    /// Write the layout fields following an Array shape
+    ///
+    /// Fails with `InvalidInput` for layouts other than the default if the
+    /// file layout has no array layout fields.
+    pub fn write_array_layout(&mut self, array_layout: &ArrayLayout) -> io::Result<()> {
+        if !self.layout.array_layout {
+            return match array_layout.is_default() {
+                true => Ok(()),
+                false => Err(self.invalid_input("array layout requires an array layout flag")),
+            };
+        }
+        self.write_all(&[array_layout.flags()])?;
+        for stride in array_layout.strides.iter().flatten() {
+            self.write_all(&stride.to_le_bytes())?;
+        }
+        for name in array_layout.axis_names.iter().flatten() {
+            self.write_all(&[name.len() as u8])?;
+            self.write_all(name.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Write the cipher fields preceding an Array or File data size
+    ///
+    /// Fails with `InvalidInput` for encrypted payloads if the layout has no
+    /// cipher fields, or if the key name is longer than 255 bytes.
+    pub fn write_encryption(&mut self, encryption: Option<&Encrypted>) -> io::Result<()> {
+        if !self.layout.encryption {
+            return match encryption {
+                Some(encrypted) => Err(self.invalid_input(format!(
+                    "{} payload requires an encryption layout",
+                    encrypted.cipher
+                ))),
+                None => Ok(()),
+            };
+        }
+        match encryption {
+            None => self.write_all(&[0]),
+            Some(encrypted) => {
+                let key_id = encrypted.key_id.as_bytes();
+                self.check_len("key id length", key_id.len(), u8::MAX as u64)?;
+                self.write_all(&[encrypted.cipher as u8, key_id.len() as u8])?;
+                self.write_all(key_id)?;
+                self.write_all(&encrypted.nonce)
+            }
+        }
+    }
+
+    /// For each payload not written yet, whether it is written as a reference
+    pub fn pending_payloads(&self) -> Pending<'_> {
+        match &self.dedup {
+            Some(dedup) => dedup.pending(),
+            None => [].iter(),
+        }
+    }
+
+    /// Write the storage fields preceding an Array or File data size
+    ///
+    /// If the payload duplicates an earlier one, the rest of it is written
+    /// as a reference and true is returned; the caller writes nothing more.
+    pub fn write_reference(&mut self, size: u64) -> io::Result<bool> {
+        let Some(dedup) = &self.dedup else {
+            return Ok(false);
+        };
+        let Some((data_offset, checksum)) = dedup.original() else {
+            self.write_all(&[STORAGE_INLINE])?;
+            return Ok(false);
+        };
+        self.write_all(&[STORAGE_REFERENCE])?;
+        self.write_all(&data_offset.to_le_bytes())?;
+        self.write_all(&size.to_le_bytes())?;
+        if let Some(checksum) = checksum {
+            self.write_all(&checksum.to_le_bytes())?;
+        }
+        self.record_payload(data_offset, checksum, size);
+        Ok(true)
+    }
+
+    /// Record where a payload's data was written, so later duplicates can
+    /// refer to it
+    pub fn record_payload(&mut self, data_offset: u64, checksum: Option<u32>, size: u64) {
+        if let Some(dedup) = &mut self.dedup {
+            dedup.record(data_offset, checksum, size);
+        }
+    }
+
+    /// Enter the Object entry `key`; its value is written next
+    pub fn enter_key(&mut self, key: &str) {
+        self.path.push(key.to_string());
+        let position = self.position;
+        if let Some(index) = &mut self.index {
+            index.enter_key(key, position);
+        }
+    }
+
+    /// Leave the Object entry entered by `enter_key`
+    pub fn leave_key(&mut self) {
+        self.path.pop();
+        if let Some(index) = &mut self.index {
+            index.leave_key();
+        }
+    }
+
+    /// Enter List item `index`; it is written next
+    pub fn enter_item(&mut self, index: usize) {
+        self.path.push(index.to_string());
+    }
+
+    /// Leave the List item entered by `enter_item`
+    pub fn leave_item(&mut self) {
+        self.path.pop();
+    }
+
+    /// Enter a List body
+    pub fn enter_list(&mut self) {
+        if let Some(index) = &mut self.index {
+            index.enter_list();
+        }
+    }
+
+    /// Leave a List body entered by `enter_list`
+    pub fn leave_list(&mut self) {
+        if let Some(index) = &mut self.index {
+            index.leave_list();
+        }
+    }
+
+    /// Write trailers after the root value
+    pub fn finish(mut self) -> io::Result<DedupStats> {
+        if let Some(index) = self.index.take() {
+            let position = self.position;
+            index.finish(&mut self, position)?;
+        }
+        // Written after the path index so the digest covers it
+        if let Some(digest) = self.digest {
+            let position = self.position;
+            self.write_all(&digest.to_le_bytes())?;
+            self.write_all(&position.to_le_bytes())?;
+            self.write_all(CHECKSUM_MAGIC)?;
+        }
+        Ok(self.dedup.map(|dedup| dedup.stats()).unwrap_or_default())
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.writer.write(buf)?;
+        self.position += n as u64;
+        if let Some(digest) = &mut self.digest {
+            *digest = checksum_append(*digest, &buf[..n]);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_to_alignment() {
+        let mut buf = Vec::new();
+        let mut enc = Encoder::new(&mut buf, 128);
+        enc.write_all(b"abc").unwrap();
+        enc.align().unwrap();
+
+        // Already aligned: no padding
+        enc.align().unwrap();
+        assert_eq!(buf.len(), 128);
+        assert!(buf[3..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn check_len_boundaries() {
+        let mut buf = Vec::new();
+        let mut enc = Encoder::new(&mut buf, 1);
+        assert!(
+            enc.check_len("count", u32::MAX as usize, u32::MAX as u64)
+                .is_ok()
+        );
+
+        enc.enter_key("items");
+        enc.enter_item(3);
+        let err = enc
+            .check_len("count", u32::MAX as usize + 1, u32::MAX as u64)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("'items/3'"));
+
+        enc.leave_item();
+        enc.leave_key();
+        let err = enc.check_len("count", 2, 1).unwrap_err();
+        assert!(err.to_string().ends_with("at root"));
+    }
+}
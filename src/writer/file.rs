@@ -1,39 +1,57 @@
 //! File serialization with streaming support
 
-use crate::types::{File, FileData, ValueTag};
+use super::encoder::Encoder;
+use crate::types::{File, FileData, ValueTag, checksum, checksum_append};
 use std::io::{self, Read, Write};
 
 /// Write a file value (handles streaming from handle)
-pub fn write_file<W: Write>(writer: &mut W, mut file: File) -> io::Result<()> {
-    writer.write_all(&[ValueTag::File as u8])?;
-
+///
+/// Data is padded to the encoder's alignment. Data identical to an earlier
+/// payload is written as a reference if the encoder deduplicates payloads.
+pub fn write_file<W: Write>(writer: &mut Encoder<W>, mut file: File) -> io::Result<()> {
     let mimetype_bytes = file.mimetype.as_bytes();
+    writer.check_len("mimetype length", mimetype_bytes.len(), u16::MAX as u64)?;
+
+    writer.write_all(&[ValueTag::File as u8])?;
     writer.write_all(&(mimetype_bytes.len() as u16).to_le_bytes())?;
     writer.write_all(mimetype_bytes)?;
 
+    writer.write_compression(file.compression)?;
+    writer.write_encryption(file.encryption.as_ref())?;
     let size = file.size();
+    if writer.write_reference(size)? {
+        return Ok(());
+    }
     writer.write_all(&size.to_le_bytes())?;
+    writer.align()?;
+    let data_offset = writer.position();
 
-    match &mut file.data {
+    let checksums = writer.layout().checksums;
+    let checksum = match &mut file.data {
         FileData::Bytes(bytes) => {
             writer.write_all(bytes)?;
+            checksums.then(|| checksum(bytes))
         }
-        FileData::Handle(handle) => {
-            stream_from_handle(writer, handle.as_mut(), size)?;
-        }
+        FileData::Handle(handle) => stream_from_handle(writer, handle.as_mut(), size, checksums)?,
+    };
+    if let Some(checksum) = checksum {
+        writer.write_all(&checksum.to_le_bytes())?;
     }
+    writer.record_payload(data_offset, checksum, size);
 
     Ok(())
 }
 
-/// Stream data from a handle to writer
+/// Stream data from a handle to writer, hashing it if `checksums` is set
 fn stream_from_handle<W: Write, R: Read + ?Sized>(
     writer: &mut W,
     reader: &mut R,
     size: u64,
-) -> io::Result<()> {
+    checksums: bool,
+) -> io::Result<Option<u32>> {
     let mut remaining = size;
     let mut buf = [0u8; 8192];
+    let mut checksum = checksums.then_some(0);
 
     while remaining > 0 {
         let to_read = std::cmp::min(remaining as usize, buf.len());
@@ -45,16 +63,20 @@ fn stream_from_handle<W: Write, R: Read + ?Sized>(
             ));
         }
         writer.write_all(&buf[..n])?;
+        if let Some(checksum) = &mut checksum {
+            *checksum = checksum_append(*checksum, &buf[..n]);
+        }
         remaining -= n as u64;
     }
 
-    Ok(())
+    Ok(checksum)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::parser::parse_value;
+    use crate::types::Layout;
     use parsicomb::{ByteCursor, Parser};
 
     #[test]
@@ -63,10 +85,10 @@ mod tests {
         let file = File::from_bytes("text/plain", content.to_vec());
 
         let mut buf = Vec::new();
-        write_file(&mut buf, file).unwrap();
+        write_file(&mut Encoder::new(&mut buf, 1), file).unwrap();
 
         let cursor = ByteCursor::new(&buf);
-        let (parsed, _) = parse_value().parse(cursor).unwrap();
+        let (parsed, _) = parse_value(Layout::UNALIGNED).parse(cursor).unwrap();
 
         let f = parsed.as_file().unwrap();
         assert_eq!(f.mimetype, "text/plain");
@@ -78,10 +100,10 @@ mod tests {
         let file = File::from_bytes("application/octet-stream", vec![]);
 
         let mut buf = Vec::new();
-        write_file(&mut buf, file).unwrap();
+        write_file(&mut Encoder::new(&mut buf, 1), file).unwrap();
 
         let cursor = ByteCursor::new(&buf);
-        let (parsed, _) = parse_value().parse(cursor).unwrap();
+        let (parsed, _) = parse_value(Layout::UNALIGNED).parse(cursor).unwrap();
 
         let f = parsed.as_file().unwrap();
         assert_eq!(f.mimetype, "application/octet-stream");
@@ -94,13 +116,31 @@ mod tests {
         let file = File::from_bytes("application/x-tflite", content.clone());
 
         let mut buf = Vec::new();
-        write_file(&mut buf, file).unwrap();
+        write_file(&mut Encoder::new(&mut buf, 1), file).unwrap();
 
         let cursor = ByteCursor::new(&buf);
-        let (parsed, _) = parse_value().parse(cursor).unwrap();
+        let (parsed, _) = parse_value(Layout::UNALIGNED).parse(cursor).unwrap();
 
         let f = parsed.as_file().unwrap();
         assert_eq!(f.mimetype, "application/x-tflite");
         assert_eq!(f.data, content.as_slice());
     }
+
+    #[test]
+    fn mimetype_length_boundary() {
+        let mimetype = "a".repeat(u16::MAX as usize);
+        let file = File::from_bytes(mimetype.clone(), vec![1]);
+        let mut buf = Vec::new();
+        write_file(&mut Encoder::new(&mut buf, 1), file).unwrap();
+        let (parsed, _) = parse_value(Layout::UNALIGNED)
+            .parse(ByteCursor::new(&buf))
+            .unwrap();
+        assert_eq!(parsed.as_file().unwrap().mimetype, mimetype);
+
+        let file = File::from_bytes("a".repeat(u16::MAX as usize + 1), vec![1]);
+        let mut buf = Vec::new();
+        let err = write_file(&mut Encoder::new(&mut buf, 1), file).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
 }
@@ -0,0 +1,143 @@
+//! Path index construction
+
+use crate::types::{PATH_INDEX_MAGIC, PATH_INDEX_RECORD_SIZE, path_hash};
+use std::io::{self, Write};
+
+/// Collects the offset of every value reachable through Objects while the
+/// tree is written, then emits the path index trailer
+#[derive(Debug, Default)]
+pub struct PathIndexBuilder {
+    path: String,
+    /// Path length before each entered key, for unwinding
+    stack: Vec<usize>,
+    /// Values inside Lists cannot be addressed by path and are not recorded
+    list_depth: usize,
+    entries: Vec<(String, u64)>,
+}
+
+impl PathIndexBuilder {
+    /// Enter the value stored under `key`, whose tag is written at `offset`
+    pub fn enter_key(&mut self, key: &str, offset: u64) {
+        self.stack.push(self.path.len());
+        if !self.path.is_empty() {
+            self.path.push('/');
+        }
+        self.path.push_str(key);
+        if self.list_depth == 0 {
+            self.entries.push((self.path.clone(), offset));
+        }
+    }
+
+    /// Leave the value entered by the matching `enter_key`
+    pub fn leave_key(&mut self) {
+        if let Some(len) = self.stack.pop() {
+            self.path.truncate(len);
+        }
+    }
+
+    /// Enter a List; nothing below it is recorded
+    pub fn enter_list(&mut self) {
+        self.list_depth += 1;
+    }
+
+    /// Leave a List entered by `enter_list`
+    pub fn leave_list(&mut self) {
+        self.list_depth -= 1;
+    }
+
+    /// Write the trailer; `position` is the absolute offset it starts at
+    pub fn finish<W: Write>(self, writer: &mut W, position: u64) -> io::Result<()> {
+        let entry_count = u32::try_from(self.entries.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many paths for the path index",
+            )
+        })?;
+        let bucket_count = entry_count.max(1).next_power_of_two();
+        let mask = (bucket_count - 1) as u64;
+
+        // Group records by bucket; the sort is stable so duplicate keys keep
+        // write order and lookups find the first one, like ValueView::get
+        let mut records: Vec<(u64, u64, &str)> = self
+            .entries
+            .iter()
+            .map(|(path, offset)| (path_hash(path), *offset, path.as_str()))
+            .collect();
+        records.sort_by_key(|&(hash, _, _)| hash & mask);
+
+        let mut bucket_start = vec![0u32; bucket_count as usize + 1];
+        for &(hash, _, _) in &records {
+            bucket_start[(hash & mask) as usize + 1] += 1;
+        }
+        for i in 1..bucket_start.len() {
+            bucket_start[i] += bucket_start[i - 1];
+        }
+
+        if let Some(&(_, _, path)) = records.iter().find(|(_, _, p)| p.len() > u16::MAX as usize) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("path too long for the path index: {:?}", path),
+            ));
+        }
+
+        let records_len = (records.len() * PATH_INDEX_RECORD_SIZE) as u64;
+        let mut path_offset = position + 4 + 4 + 4 * bucket_start.len() as u64 + records_len;
+
+        writer.write_all(&entry_count.to_le_bytes())?;
+        writer.write_all(&bucket_count.to_le_bytes())?;
+        for start in &bucket_start {
+            writer.write_all(&start.to_le_bytes())?;
+        }
+        for &(hash, offset, path) in &records {
+            writer.write_all(&hash.to_le_bytes())?;
+            writer.write_all(&offset.to_le_bytes())?;
+            writer.write_all(&path_offset.to_le_bytes())?;
+            path_offset += 2 + path.len() as u64;
+        }
+        for &(_, _, path) in &records {
+            writer.write_all(&(path.len() as u16).to_le_bytes())?;
+            writer.write_all(path.as_bytes())?;
+        }
+
+        writer.write_all(&position.to_le_bytes())?;
+        writer.write_all(PATH_INDEX_MAGIC)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_nested_paths_but_not_list_items() {
+        let mut builder = PathIndexBuilder::default();
+        builder.enter_key("model", 10);
+        builder.enter_key("layers", 20);
+        builder.leave_key();
+        builder.enter_key("heads", 30);
+        builder.enter_list();
+        builder.enter_key("inner", 40);
+        builder.leave_key();
+        builder.leave_list();
+        builder.leave_key();
+        builder.leave_key();
+        builder.enter_key("version", 50);
+        builder.leave_key();
+
+        let paths: Vec<(&str, u64)> = builder
+            .entries
+            .iter()
+            .map(|(p, o)| (p.as_str(), *o))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("model", 10),
+                ("model/layers", 20),
+                ("model/heads", 30),
+                ("version", 50),
+            ]
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! Encoded size computation
+//!
+//! Version 2 prefixes Objects and Lists with their body length, which has to
+//! be known before the body is streamed. Payload padding depends on the
+//! absolute position, so sizes are computed for a given start offset.
+//! Duplicate payloads are written as references, so sizes also depend on
+//! which of the payloads still to be written are duplicates.
+//!
+//! The writer measures the tree once before writing it, recording the body
+//! length of every Object and List in the order they are written.
+
+use super::dedup::Pending;
+use crate::types::{
+    Array, ArrayLayout, ChunkData, ChunkedArray, Compressed, Encrypted, File, Layout, NONCE_SIZE,
+    Value, chunk_bounds, num_chunks, unravel,
+};
+
+/// Padding needed to bring `position` up to a multiple of `alignment`
+pub fn padding(position: u64, alignment: u64) -> u64 {
+    (alignment - position % alignment) % alignment
+}
+
+/// Encoded size of `value` (including its tag) when written at `position`
+///
+/// `pending` is advanced past the payloads of `value`. The body lengths of
+/// the Objects and Lists in `value` are appended to `bodies` in pre-order.
+pub fn encoded_len(
+    value: &Value,
+    position: u64,
+    layout: Layout,
+    pending: &mut Pending,
+    bodies: &mut Vec<u64>,
+) -> u64 {
+    match value {
+        Value::Null => 1,
+        Value::Bool(_) => 1 + 1,
+        Value::Int(_) | Value::UInt(_) | Value::Float(_) => 1 + 8,
+        Value::Timestamp(_) => 1 + 8 + 4,
+        Value::String(s) | Value::Ref(s) => 1 + 4 + s.len() as u64,
+        Value::Bytes(b) => 1 + 4 + b.len() as u64,
+        Value::StringArray(s) => 1 + 4 + 4 + 4 * s.len() as u64 + s.data().len() as u64,
+        Value::Array(arr) => array_len(arr, position, layout, is_reference(pending)),
+        Value::Sparse(sparse) => {
+            let mut end = position + 1 + 1 + 1 + 8 * sparse.shape.len() as u64;
+            for arr in sparse.arrays() {
+                end += array_len(arr, end, layout, is_reference(pending));
+            }
+            end - position
+        }
+        Value::ChunkedArray(chunked) => {
+            let header = 1 + 1 + 1 + 16 * chunked.shape.len() as u64;
+            let table = 8 * num_chunks(&chunked.grid());
+            let chunks_start = position + header + table;
+            let chunks = chunk_lens(chunked, chunks_start, layout, pending);
+            header + table + chunks.iter().sum::<u64>()
+        }
+        Value::File(file) => file_len(file, position, layout, is_reference(pending)),
+        Value::Object(entries) => {
+            let body_start = position + 1 + 8;
+            let slot = reserve(bodies);
+            bodies[slot] = object_body_len(entries, body_start, layout, pending, bodies);
+            1 + 8 + bodies[slot]
+        }
+        Value::List(items) => {
+            let body_start = position + 1 + 8;
+            let slot = reserve(bodies);
+            bodies[slot] = list_body_len(items, body_start, layout, pending, bodies);
+            1 + 8 + bodies[slot]
+        }
+    }
+}
+
+/// Reserve the body length of a container ahead of its children's
+fn reserve(bodies: &mut Vec<u64>) -> usize {
+    bodies.push(0);
+    bodies.len() - 1
+}
+
+/// Size of an Object body (entry count and entries) written at `position`
+fn object_body_len(
+    entries: &[(String, Value)],
+    position: u64,
+    layout: Layout,
+    pending: &mut Pending,
+    bodies: &mut Vec<u64>,
+) -> u64 {
+    let mut end = position + 4;
+    for (key, value) in entries {
+        end += 2 + key.len() as u64;
+        end += encoded_len(value, end, layout, pending, bodies);
+    }
+    end - position
+}
+
+/// Size of each chunk of `chunked` when the first is written at `position`
+///
+/// Produced chunks are written raw with the size of their shape.
+pub fn chunk_lens(
+    chunked: &ChunkedArray,
+    position: u64,
+    layout: Layout,
+    pending: &mut Pending,
+) -> Vec<u64> {
+    let mut end = position;
+    let mut lens = Vec::new();
+    match &chunked.chunks {
+        ChunkData::Arrays(chunks) => {
+            for chunk in chunks {
+                let len = array_len(chunk, end, layout, is_reference(pending));
+                lens.push(len);
+                end += len;
+            }
+        }
+        ChunkData::Producer(_) => {
+            let grid = chunked.grid();
+            for flat in 0..num_chunks(&grid) {
+                let index = unravel(&grid, flat);
+                let shape = chunk_bounds(&chunked.shape, &chunked.chunk_shape, &index).1;
+                let count = shape.iter().product::<u64>();
+                let size = chunked.dtype.data_size(count).unwrap_or(u64::MAX);
+                let reference = is_reference(pending);
+                let fields = array_layout_len(&ArrayLayout::default(), layout)
+                    + payload_fields_len(None, None, reference, layout);
+                let len = array_parts_len(shape.len(), fields, size, end, layout, reference);
+                lens.push(len);
+                end += len;
+            }
+        }
+    }
+    lens
+}
+
+/// Size of a List body (item count and items) written at `position`
+fn list_body_len(
+    items: &[Value],
+    position: u64,
+    layout: Layout,
+    pending: &mut Pending,
+    bodies: &mut Vec<u64>,
+) -> u64 {
+    let mut end = position + 4;
+    for item in items {
+        end += encoded_len(item, end, layout, pending, bodies);
+    }
+    end - position
+}
+
+/// Size of the codec fields preceding an Array or File data size
+fn compression_len(compression: Option<Compressed>, layout: Layout) -> u64 {
+    match (layout.compression, compression) {
+        (false, _) => 0,
+        (true, None) => 1,
+        (true, Some(_)) => 1 + 1 + 8,
+    }
+}
+
+/// Size of the cipher fields preceding an Array or File data size
+fn encryption_len(encryption: Option<&Encrypted>, layout: Layout) -> u64 {
+    match (layout.encryption, encryption) {
+        (false, _) => 0,
+        (true, None) => 1,
+        (true, Some(encrypted)) => 1 + 1 + encrypted.key_id.len() as u64 + NONCE_SIZE as u64,
+    }
+}
+
+/// Whether the next payload is written as a reference to an earlier copy
+fn is_reference(pending: &mut Pending) -> bool {
+    pending.next().is_some_and(Option::is_some)
+}
+
+/// Size of the storage fields preceding an Array or File data size
+fn storage_len(reference: bool, layout: Layout) -> u64 {
+    match (layout.dedup, reference) {
+        (false, _) => 0,
+        (true, false) => 1,
+        (true, true) => 1 + 8,
+    }
+}
+
+/// Size of the layout fields following an Array shape
+fn array_layout_len(array_layout: &ArrayLayout, layout: Layout) -> u64 {
+    if !layout.array_layout {
+        return 0;
+    }
+    let strides = array_layout
+        .strides
+        .as_ref()
+        .map_or(0, |s| 8 * s.len() as u64);
+    let names = array_layout.axis_names.iter().flatten();
+    1 + strides + names.map(|name| 1 + name.len() as u64).sum::<u64>()
+}
+
+/// Size of the codec, cipher and storage fields of a payload
+fn payload_fields_len(
+    compression: Option<Compressed>,
+    encryption: Option<&Encrypted>,
+    reference: bool,
+    layout: Layout,
+) -> u64 {
+    compression_len(compression, layout)
+        + encryption_len(encryption, layout)
+        + storage_len(reference, layout)
+}
+
+/// Size of a payload's padding, data and checksum after its header
+fn payload_len(header_end: u64, size: u64, reference: bool, layout: Layout) -> u64 {
+    let checksum = layout.checksum_size() as u64;
+    match reference {
+        true => checksum,
+        false => padding(header_end, layout.alignment) + size + checksum,
+    }
+}
+
+fn array_len(arr: &Array, position: u64, layout: Layout, reference: bool) -> u64 {
+    let encryption = arr.encryption.as_ref();
+    let fields = array_layout_len(&arr.layout, layout)
+        + payload_fields_len(arr.compression, encryption, reference, layout);
+    let size = arr.data.len() as u64;
+    array_parts_len(arr.shape.len(), fields, size, position, layout, reference)
+}
+
+/// Size of an Array of `ndim` dimensions whose layout and payload fields
+/// take `fields` bytes and whose data takes `size` bytes
+fn array_parts_len(
+    ndim: usize,
+    fields: u64,
+    size: u64,
+    position: u64,
+    layout: Layout,
+    reference: bool,
+) -> u64 {
+    let header = 1 + 1 + 1 + 8 * ndim as u64 + fields + 8;
+    header + payload_len(position + header, size, reference, layout)
+}
+
+fn file_len(file: &File, position: u64, layout: Layout, reference: bool) -> u64 {
+    let encryption = file.encryption.as_ref();
+    let fields = payload_fields_len(file.compression, encryption, reference, layout);
+    let header = 1 + 2 + file.mimetype.len() as u64 + fields + 8;
+    header + payload_len(position + header, file.size(), reference, layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{CHECKSUM_SIZE, Compression, DType, Order, TRAILER_FOOTER_SIZE, Timestamp};
+    use crate::writer::dedup::Dedup;
+    use crate::writer::{WriteOptions, payload_flags, to_bytes_with};
+
+    fn assert_measured(make: impl Fn() -> Value, options: WriteOptions) {
+        let value = make();
+        let mut header = options.header().unwrap();
+        header.flags |= payload_flags(&value);
+        let layout = header.layout().unwrap();
+        let dedup = Dedup::plan(&value);
+        let mut pending = match options.dedup {
+            true => dedup.pending(),
+            false => [].iter(),
+        };
+        let mut expected = encoded_len(&value, 16, layout, &mut pending, &mut Vec::new());
+        if layout.checksums {
+            expected += (CHECKSUM_SIZE + TRAILER_FOOTER_SIZE) as u64;
+        }
+        let bytes = to_bytes_with(make(), options).unwrap();
+        assert_eq!(bytes.len() as u64 - 16, expected);
+    }
+
+    #[test]
+    fn matches_written_size() {
+        let make = || {
+            Value::Object(vec![
+                ("a".into(), Value::Bool(true)),
+                ("b".into(), Value::String("xyz".into())),
+                (
+                    "scalars".into(),
+                    Value::List(vec![
+                        Value::Null,
+                        Value::UInt(u64::MAX),
+                        Value::Bytes(vec![0, 1, 2]),
+                        Value::Timestamp(Timestamp::from_seconds(1)),
+                        Value::Ref("b".into()),
+                        Value::StringArray(["<s>", "", "é"].into_iter().collect()),
+                    ]),
+                ),
+                (
+                    "c".into(),
+                    Value::List(vec![
+                        Value::Float(1.0),
+                        Value::Array(Array::new(DType::U8, vec![3], vec![1, 2, 3])),
+                    ]),
+                ),
+                (
+                    "d".into(),
+                    Value::File(File::from_bytes("text/plain", b"hello".to_vec())),
+                ),
+                (
+                    "e".into(),
+                    Value::List(vec![
+                        Value::File(File::from_bytes("text/plain", vec![7; 40])),
+                        Value::Array(Array::new(DType::U64, vec![5], vec![7; 40])),
+                    ]),
+                ),
+                (
+                    "f".into(),
+                    Value::List(vec![
+                        ChunkedArray::from_array(
+                            &Array::new(DType::U16, vec![3, 4], vec![7; 24]),
+                            vec![2, 2],
+                        )
+                        .unwrap()
+                        .into(),
+                        ChunkedArray::from_producer(
+                            DType::U8,
+                            vec![5],
+                            vec![2],
+                            |_: &[u64], shape: &[u64]| Ok(vec![7; shape[0] as usize]),
+                        )
+                        .into(),
+                    ]),
+                ),
+            ])
+        };
+
+        assert_measured(make, WriteOptions::unaligned());
+        assert_measured(make, WriteOptions::aligned(64));
+        assert_measured(make, WriteOptions::aligned(4096));
+        assert_measured(make, WriteOptions::aligned(64).with_checksums());
+        // Codec bytes are written even if no payload reaches the threshold
+        let raw = WriteOptions::aligned(64).with_compression(Compression::Zstd, u64::MAX);
+        assert_measured(make, raw);
+        assert_measured(make, WriteOptions::aligned(64).with_dedup());
+        assert_measured(
+            make,
+            WriteOptions::unaligned().with_checksums().with_dedup(),
+        );
+
+        // One array with a layout adds layout fields to every array and chunk
+        let with_layout = || {
+            let named = Array::new(DType::U16, vec![2, 3], vec![7; 12])
+                .with_order(Order::F)
+                .with_axis_names(["time", "mel"]);
+            let strided = Array::new(DType::U8, vec![2, 2], vec![7; 4]).with_strides(vec![1, 2]);
+            let Value::Object(mut entries) = make() else {
+                unreachable!()
+            };
+            entries.push(("g".into(), Value::List(vec![named.into(), strided.into()])));
+            Value::Object(entries)
+        };
+        assert_measured(with_layout, WriteOptions::unaligned());
+        assert_measured(with_layout, WriteOptions::aligned(64).with_dedup());
+    }
+
+    #[test]
+    fn padding_rounds_up() {
+        assert_eq!(padding(0, 64), 0);
+        assert_eq!(padding(1, 64), 63);
+        assert_eq!(padding(64, 64), 0);
+        assert_eq!(padding(5, 1), 0);
+    }
+}
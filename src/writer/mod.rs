@@ -1,38 +1,255 @@
 //! Serialization of blobfig values
 
 mod array;
+mod compress;
+mod dedup;
+mod encoder;
 mod file;
+mod index;
+mod measure;
+mod reference;
 mod value;
 
-use crate::types::{MAGIC, VERSION, Value};
+use crate::types::{
+    Array, ChunkData, Compression, DEFAULT_ALIGNMENT, FLAG_ARRAY_LAYOUT, FLAG_CHECKSUMS,
+    FLAG_COMPRESSION, FLAG_DEDUP, FLAG_ENCRYPTION, FLAG_PATH_INDEX, Header, Value,
+};
 use std::io::{self, Write};
 
+use dedup::Dedup;
+use encoder::Encoder;
+use reference::check_references;
 use value::write_value;
 
+pub use compress::CompressionPolicy;
+pub use dedup::DedupStats;
+
+#[cfg(feature = "signing")]
+pub use crate::signing::{sign, sign_detached};
+
+/// Options controlling the on-disk layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Boundary (relative to the file start) that every Array and File
+    /// payload is padded to, or None to write payloads without padding.
+    /// Must be a power of two.
+    pub alignment: Option<u32>,
+    /// Append a path index trailer mapping every Object path to the offset
+    /// of its value, for direct lookup with [`crate::parser::parse_index`]
+    pub path_index: bool,
+    /// Follow every Array and File payload with its checksum and end the
+    /// file with a whole-document digest, for [`crate::parser::parse_verified`]
+    pub checksums: bool,
+    /// Compress Array and File payloads above a size threshold. Payloads
+    /// compressed up front are written compressed either way.
+    pub compression: Option<CompressionPolicy>,
+    /// Write Array and File payloads identical to an earlier one as a
+    /// reference to that copy
+    pub dedup: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            alignment: Some(DEFAULT_ALIGNMENT),
+            path_index: false,
+            checksums: false,
+            compression: None,
+            dedup: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Write payloads without padding (smallest output)
+    pub fn unaligned() -> Self {
+        Self {
+            alignment: None,
+            ..Self::default()
+        }
+    }
+
+    /// Align payloads to the given power-of-two boundary
+    pub fn aligned(alignment: u32) -> Self {
+        Self {
+            alignment: Some(alignment),
+            ..Self::default()
+        }
+    }
+
+    /// Also write a path index trailer
+    pub fn with_path_index(mut self) -> Self {
+        self.path_index = true;
+        self
+    }
+
+    /// Also write payload checksums and a document digest
+    pub fn with_checksums(mut self) -> Self {
+        self.checksums = true;
+        self
+    }
+
+    /// Compress payloads of at least `min_size` bytes with `codec`
+    pub fn with_compression(mut self, codec: Compression, min_size: u64) -> Self {
+        self.compression = Some(CompressionPolicy { codec, min_size });
+        self
+    }
+
+    /// Also write duplicate payloads as references to their first copy
+    pub fn with_dedup(mut self) -> Self {
+        self.dedup = true;
+        self
+    }
+
+    fn header(&self) -> io::Result<Header> {
+        let mut header = match self.alignment {
+            None => Header::new(0),
+            Some(alignment) => Header::aligned(alignment).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("alignment must be a power of two, got {}", alignment),
+                )
+            })?,
+        };
+        if self.path_index {
+            header.flags |= FLAG_PATH_INDEX;
+        }
+        if self.checksums {
+            header.flags |= FLAG_CHECKSUMS;
+        }
+        if self.compression.is_some() {
+            header.flags |= FLAG_COMPRESSION;
+        }
+        if self.dedup {
+            header.flags |= FLAG_DEDUP;
+        }
+        Ok(header)
+    }
+}
+
 /// Write a blobfig value to a writer (consumes the value to handle streaming)
+///
+/// Payloads are aligned to [`DEFAULT_ALIGNMENT`]; see [`write_with`].
 pub fn write<W: Write>(writer: &mut W, value: Value) -> io::Result<()> {
+    write_with(writer, value, WriteOptions::default())
+}
+
+/// Write a blobfig value to a writer with explicit layout options
+pub fn write_with<W: Write>(writer: &mut W, value: Value, options: WriteOptions) -> io::Result<()> {
+    write_with_stats(writer, value, options).map(|_| ())
+}
+
+/// Write a blobfig value like [`write_with`], reporting how many payloads
+/// were deduplicated
+///
+/// The stats are all zero unless [`WriteOptions::dedup`] is set.
+pub fn write_with_stats<W: Write>(
+    writer: &mut W,
+    value: Value,
+    options: WriteOptions,
+) -> io::Result<DedupStats> {
+    let mut header = options.header()?;
+    check_references(&value)?;
+    let value = match options.compression {
+        Some(policy) => policy.apply(value)?,
+        None => value,
+    };
+    header.flags |= payload_flags(&value);
+
+    let alignment = options.alignment.map_or(1, u64::from);
+    let mut writer = Encoder::new(writer, alignment);
+    if header.has_compression() {
+        writer = writer.with_compression();
+    }
+    if header.has_encryption() {
+        writer = writer.with_encryption();
+    }
+    if header.has_array_layout() {
+        writer = writer.with_array_layout();
+    }
+    if options.path_index {
+        writer = writer.with_path_index();
+    }
+    if options.checksums {
+        writer = writer.with_checksums();
+    }
+    if options.dedup {
+        writer = writer.with_dedup(Dedup::plan(&value));
+    }
+
     // Write header
-    writer.write_all(MAGIC)?;
-    writer.write_all(&VERSION.to_le_bytes())?;
-    writer.write_all(&0u32.to_le_bytes())?; // flags (reserved)
+    writer.write_all(&header.to_bytes())?;
 
     // Write the value
-    write_value(writer, value)?;
+    writer.measure(&value);
+    write_value(&mut writer, value)?;
+
+    // Write trailers
+    writer.finish()
+}
 
-    Ok(())
+/// Header flags needed by the compressed and encrypted payloads and the
+/// array layouts in `value`
+fn payload_flags(value: &Value) -> u32 {
+    match value {
+        Value::Array(arr) => array_flags(arr),
+        Value::File(file) => codec_flags(file.compression.is_some(), file.encryption.is_some()),
+        Value::Sparse(sparse) => sparse
+            .arrays()
+            .fold(0, |flags, arr| flags | array_flags(arr)),
+        Value::ChunkedArray(chunked) => match &chunked.chunks {
+            ChunkData::Arrays(chunks) => chunks
+                .iter()
+                .fold(0, |flags, chunk| flags | array_flags(chunk)),
+            ChunkData::Producer(_) => 0,
+        },
+        Value::Object(entries) => entries
+            .iter()
+            .fold(0, |flags, (_, value)| flags | payload_flags(value)),
+        Value::List(items) => items
+            .iter()
+            .fold(0, |flags, item| flags | payload_flags(item)),
+        _ => 0,
+    }
+}
+
+/// Header flags needed by `arr`
+fn array_flags(arr: &Array) -> u32 {
+    let mut flags = codec_flags(arr.compression.is_some(), arr.encryption.is_some());
+    if !arr.layout.is_default() {
+        flags |= FLAG_ARRAY_LAYOUT;
+    }
+    flags
+}
+
+/// Header flags needed by a compressed or encrypted payload
+fn codec_flags(compression: bool, encryption: bool) -> u32 {
+    let mut flags = 0;
+    if compression {
+        flags |= FLAG_COMPRESSION;
+    }
+    if encryption {
+        flags |= FLAG_ENCRYPTION;
+    }
+    flags
 }
 
 /// Write a blobfig value to bytes
 pub fn to_bytes(value: Value) -> io::Result<Vec<u8>> {
+    to_bytes_with(value, WriteOptions::default())
+}
+
+/// Write a blobfig value to bytes with explicit layout options
+pub fn to_bytes_with(value: Value, options: WriteOptions) -> io::Result<Vec<u8>> {
     let mut buf = Vec::new();
-    write(&mut buf, value)?;
+    write_with(&mut buf, value, options)?;
     Ok(buf)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{Array, DType, File};
+    use crate::types::{Array, DType, FLAG_ALIGNED, File, MAGIC, VERSION};
 
     #[test]
     fn test_write_primitives() {
@@ -124,6 +341,154 @@ mod tests {
         assert!(!bytes.is_empty());
     }
 
+    #[test]
+    fn test_payload_flags() {
+        use crate::types::{Cipher, Compressed, Compression, Encrypted, Filters};
+
+        let mut arr = Array::new(DType::U8, vec![4], vec![0; 4]);
+        let value = Value::List(vec![Value::Array(arr.clone())]);
+        assert_eq!(payload_flags(&value), 0);
+
+        arr.compression = Some(Compressed {
+            codec: Compression::Zstd,
+            filters: Filters::NONE,
+            raw_size: 4,
+        });
+        let mut file = File::from_bytes("text/plain", vec![0; 16]);
+        file.encryption = Some(Encrypted {
+            cipher: Cipher::Aes256Gcm,
+            key_id: "k".into(),
+            nonce: [0; 12],
+        });
+        let value = Value::Object(vec![
+            ("a".into(), Value::Array(arr)),
+            ("b".into(), Value::List(vec![Value::File(file)])),
+        ]);
+        assert_eq!(payload_flags(&value), FLAG_COMPRESSION | FLAG_ENCRYPTION);
+    }
+
+    #[test]
+    fn test_dedup_writes_references() {
+        let weights = || Array::new(DType::F32, vec![16], vec![3; 64]);
+        let tokenizer = || File::from_bytes("application/json", vec![b'{'; 100]);
+        let make = || {
+            Value::Object(vec![
+                ("a".into(), Value::Array(weights())),
+                ("b".into(), Value::File(tokenizer())),
+                (
+                    "heads".into(),
+                    Value::List(vec![Value::Array(weights()), Value::File(tokenizer())]),
+                ),
+            ])
+        };
+
+        let plain = to_bytes(make()).unwrap();
+        let mut deduped = Vec::new();
+        let stats =
+            write_with_stats(&mut deduped, make(), WriteOptions::default().with_dedup()).unwrap();
+        assert_eq!(
+            stats,
+            DedupStats {
+                payloads: 4,
+                duplicates: 2,
+                bytes_saved: 164,
+            }
+        );
+        assert!(deduped.len() < plain.len());
+
+        let header = Header::from_bytes(deduped[..16].try_into().unwrap());
+        assert!(header.has_dedup());
+        let stats = write_with_stats(&mut Vec::new(), make(), WriteOptions::default()).unwrap();
+        assert_eq!(stats.duplicates, 0);
+    }
+
+    #[test]
+    fn test_default_alignment_flag() {
+        let bytes = to_bytes(Value::Bool(true)).unwrap();
+        let header = Header::from_bytes(bytes[..16].try_into().unwrap());
+        assert_ne!(header.flags & FLAG_ALIGNED, 0);
+        assert_eq!(header.alignment(), Some(DEFAULT_ALIGNMENT as u64));
+    }
+
+    #[test]
+    fn test_unaligned_has_no_flags() {
+        let bytes = to_bytes_with(Value::Bool(true), WriteOptions::unaligned()).unwrap();
+        let header = Header::from_bytes(bytes[..16].try_into().unwrap());
+        assert_eq!(header.flags, 0);
+    }
+
+    #[test]
+    fn test_payloads_aligned() {
+        let make_value = || {
+            Value::Object(vec![
+                ("name".into(), Value::String("odd length".into())),
+                (
+                    "weights".into(),
+                    Value::Array(Array::new(DType::F32, vec![3], vec![0u8; 12])),
+                ),
+                (
+                    "model".into(),
+                    Value::File(File::from_bytes("application/x-tflite", vec![1, 2, 3])),
+                ),
+            ])
+        };
+
+        for alignment in [8, 64, 4096] {
+            let bytes = to_bytes_with(make_value(), WriteOptions::aligned(alignment)).unwrap();
+            let parsed = crate::parser::parse(&bytes).unwrap();
+
+            let weights = parsed.get("weights").unwrap().as_array().unwrap();
+            let offset = weights.data.as_ptr() as usize - bytes.as_ptr() as usize;
+            assert_eq!(offset % alignment as usize, 0);
+
+            let model = parsed.get("model").unwrap().as_file().unwrap();
+            let offset = model.data.as_ptr() as usize - bytes.as_ptr() as usize;
+            assert_eq!(offset % alignment as usize, 0);
+            assert_eq!(model.data, &[1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn test_alignment_must_be_power_of_two() {
+        let result = to_bytes_with(Value::Int(1), WriteOptions::aligned(48));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_key_length_boundary() {
+        let key = "k".repeat(u16::MAX as usize);
+        let value = Value::Object(vec![(key.clone(), Value::Int(1))]);
+        let bytes = to_bytes(value).unwrap();
+        let parsed = crate::parse(&bytes).unwrap();
+        assert_eq!(parsed.get(&key).unwrap().as_int(), Some(1));
+
+        let value = Value::Object(vec![(
+            "models".into(),
+            Value::List(vec![Value::Object(vec![(
+                "k".repeat(u16::MAX as usize + 1),
+                Value::Int(1),
+            )])]),
+        )]);
+        let err = to_bytes(value).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("key length 65536"));
+        assert!(err.to_string().contains("'models/0'"));
+    }
+
+    #[test]
+    fn test_invalid_input_names_path() {
+        let value = Value::Object(vec![(
+            "models".into(),
+            Value::Object(vec![(
+                "encoder".into(),
+                Value::File(File::from_bytes("x".repeat(70_000), vec![])),
+            )]),
+        )]);
+        let err = to_bytes(value).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("'models/encoder'"));
+    }
+
     #[test]
     fn test_key_with_slash_rejected() {
         let value = Value::Object(vec![("invalid/key".into(), Value::Int(1))]);
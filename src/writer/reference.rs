@@ -0,0 +1,153 @@
+//! Write-time validation of references between paths
+//!
+//! Every Ref must resolve to a value of the tree being written, following
+//! the same rules as [`ValueView::resolve`](crate::types::ValueView::resolve).
+
+use crate::types::{Hops, MAX_REFERENCE_HOPS, MAX_REFERENCE_LOOKUPS, Unresolved, Value};
+use std::io;
+
+/// Check that every Ref in `root` resolves from `root`
+pub fn check_references(root: &Value) -> io::Result<()> {
+    check(root, root, &mut Vec::new())
+}
+
+/// Check the Refs in `value`, which sits at `path` below `root`
+fn check(root: &Value, value: &Value, path: &mut Vec<String>) -> io::Result<()> {
+    match value {
+        Value::Ref(target) => {
+            if let Err(message) = follow(root, value, &mut Hops::default()) {
+                let at = match path.is_empty() {
+                    true => "at root".to_string(),
+                    false => format!("at '{}'", path.join("/")),
+                };
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("reference to '{}' {}: {}", target, at, message),
+                ));
+            }
+        }
+        Value::Object(entries) => {
+            for (key, value) in entries {
+                path.push(key.clone());
+                check(root, value, path)?;
+                path.pop();
+            }
+        }
+        Value::List(items) => {
+            for (index, item) in items.iter().enumerate() {
+                path.push(index.to_string());
+                check(root, item, path)?;
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Walk `path` from `root`, following references through `hops`
+fn walk<'v>(root: &'v Value, path: &'v str, hops: &mut Hops<'v>) -> Result<&'v Value, String> {
+    let mut current = root;
+    for key in path.split('/') {
+        current = match follow(root, current, hops)? {
+            Value::Object(entries) => entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .ok_or_else(|| format!("'{}' does not exist", path))?,
+            _ => return Err(format!("'{}' does not exist", path)),
+        };
+    }
+    follow(root, current, hops)
+}
+
+/// Follow `value` to the first value that is not a reference
+fn follow<'v>(
+    root: &'v Value,
+    mut value: &'v Value,
+    hops: &mut Hops<'v>,
+) -> Result<&'v Value, String> {
+    while let Value::Ref(target) = value {
+        hops.push(target).map_err(|e| match e {
+            Unresolved::Cycle => format!("reference cycle through '{}'", target),
+            Unresolved::TooManyHops => {
+                format!("more than {} chained references", MAX_REFERENCE_HOPS)
+            }
+            _ => format!("more than {} references in all", MAX_REFERENCE_LOOKUPS),
+        })?;
+        value = walk(root, target, hops)?;
+        hops.pop();
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(target: &str) -> Value {
+        Value::Object(vec![
+            (
+                "defaults".into(),
+                Value::Object(vec![("audio".into(), Value::Int(16000))]),
+            ),
+            ("alias".into(), Value::Ref("defaults".into())),
+            ("heads".into(), Value::List(vec![Value::Ref(target.into())])),
+        ])
+    }
+
+    #[test]
+    fn accepts_existing_targets() {
+        assert!(check_references(&config("defaults/audio")).is_ok());
+        // Targets may pass through other references
+        assert!(check_references(&config("alias/audio")).is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_targets() {
+        let err = check_references(&config("defaults/video")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("at 'heads/0'"));
+        assert!(err.to_string().contains("'defaults/video' does not exist"));
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let value = Value::Object(vec![
+            ("a".into(), Value::Ref("b".into())),
+            ("b".into(), Value::Ref("a".into())),
+        ]);
+        let err = check_references(&value).unwrap_err();
+        assert!(err.to_string().contains("cycle"));
+
+        let value = Value::Object(vec![("a".into(), Value::Ref("a/x".into()))]);
+        assert!(check_references(&value).is_err());
+    }
+
+    #[test]
+    fn aliases_may_repeat_along_a_path() {
+        // "loop/self/self/end" passes through the same alias twice
+        let value = Value::Object(vec![
+            (
+                "loop".into(),
+                Value::Object(vec![
+                    ("self".into(), Value::Ref("loop".into())),
+                    ("end".into(), Value::Int(1)),
+                ]),
+            ),
+            ("alias".into(), Value::Ref("loop/self/self/end".into())),
+        ]);
+        assert!(check_references(&value).is_ok());
+    }
+
+    #[test]
+    fn rejects_long_chains() {
+        let hops = MAX_REFERENCE_HOPS + 1;
+        let mut entries: Vec<(String, Value)> = (0..hops)
+            .map(|i| (format!("r{}", i), Value::Ref(format!("r{}", i + 1))))
+            .collect();
+        entries.push((format!("r{}", hops), Value::Int(1)));
+        let err = check_references(&Value::Object(entries)).unwrap_err();
+        assert!(err.to_string().contains("more than"));
+    }
+}
@@ -3,11 +3,14 @@
 use crate::types::{Value, ValueTag};
 use std::io::{self, Write};
 
-use super::array::write_array;
+use super::array::{write_array, write_chunked_array};
+use super::encoder::Encoder;
 use super::file::write_file;
 
 /// Write a value (consumes it to handle file handles)
-pub fn write_value<W: Write>(writer: &mut W, value: Value) -> io::Result<()> {
+///
+/// The value must have been measured with [`Encoder::measure`].
+pub fn write_value<W: Write>(writer: &mut Encoder<W>, value: Value) -> io::Result<()> {
     match value {
         Value::Bool(b) => {
             writer.write_all(&[ValueTag::Bool as u8])?;
@@ -21,40 +24,104 @@ pub fn write_value<W: Write>(writer: &mut W, value: Value) -> io::Result<()> {
             writer.write_all(&[ValueTag::Float as u8])?;
             writer.write_all(&f.to_le_bytes())?;
         }
+        Value::Null => {
+            writer.write_all(&[ValueTag::Null as u8])?;
+        }
+        Value::UInt(u) => {
+            writer.write_all(&[ValueTag::UInt as u8])?;
+            writer.write_all(&u.to_le_bytes())?;
+        }
+        Value::Bytes(b) => {
+            writer.check_len("bytes length", b.len(), u32::MAX as u64)?;
+            writer.write_all(&[ValueTag::Bytes as u8])?;
+            writer.write_all(&(b.len() as u32).to_le_bytes())?;
+            writer.write_all(&b)?;
+        }
+        Value::Timestamp(t) => {
+            writer.write_all(&[ValueTag::Timestamp as u8])?;
+            writer.write_all(&t.seconds().to_le_bytes())?;
+            writer.write_all(&t.nanos().to_le_bytes())?;
+        }
         Value::String(s) => {
-            writer.write_all(&[ValueTag::String as u8])?;
             let bytes = s.as_bytes();
+            writer.check_len("string length", bytes.len(), u32::MAX as u64)?;
+            writer.write_all(&[ValueTag::String as u8])?;
             writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
             writer.write_all(bytes)?;
         }
+        Value::Ref(path) => {
+            let bytes = path.as_bytes();
+            writer.check_len("reference length", bytes.len(), u32::MAX as u64)?;
+            writer.write_all(&[ValueTag::Ref as u8])?;
+            writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+            writer.write_all(bytes)?;
+        }
+        Value::StringArray(strings) => {
+            let data = strings.data().as_bytes();
+            writer.check_len("string array length", strings.len(), u32::MAX as u64)?;
+            writer.check_len("string array data length", data.len(), u32::MAX as u64)?;
+            writer.write_all(&[ValueTag::StringArray as u8])?;
+            writer.write_all(&(strings.len() as u32).to_le_bytes())?;
+            writer.write_all(&(data.len() as u32).to_le_bytes())?;
+            for &end in strings.ends() {
+                writer.write_all(&(end as u32).to_le_bytes())?;
+            }
+            writer.write_all(data)?;
+        }
         Value::Array(arr) => {
             write_array(writer, &arr)?;
         }
+        Value::Sparse(sparse) => {
+            sparse.check_stored().map_err(|e| writer.invalid_input(e))?;
+            writer.check_len("sparse dimension count", sparse.shape.len(), u8::MAX as u64)?;
+            writer.write_all(&[ValueTag::Sparse as u8])?;
+            writer.write_all(&[sparse.format() as u8])?;
+            writer.write_all(&[sparse.shape.len() as u8])?;
+            for dim in &sparse.shape {
+                writer.write_all(&dim.to_le_bytes())?;
+            }
+            for arr in sparse.arrays() {
+                write_array(writer, arr)?;
+            }
+        }
+        Value::ChunkedArray(chunked) => {
+            write_chunked_array(writer, chunked)?;
+        }
         Value::File(file) => {
             write_file(writer, file)?;
         }
         Value::Object(entries) => {
+            writer.check_len("object entry count", entries.len(), u32::MAX as u64)?;
             writer.write_all(&[ValueTag::Object as u8])?;
+            let body_len = writer.next_body_len()?;
+            writer.write_all(&body_len.to_le_bytes())?;
             writer.write_all(&(entries.len() as u32).to_le_bytes())?;
             for (key, val) in entries {
                 if key.contains('/') {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        format!("key contains '/': {:?}", key),
-                    ));
+                    return Err(writer.invalid_input(format!("key contains '/': {:?}", key)));
                 }
                 let key_bytes = key.as_bytes();
+                writer.check_len("key length", key_bytes.len(), u16::MAX as u64)?;
                 writer.write_all(&(key_bytes.len() as u16).to_le_bytes())?;
                 writer.write_all(key_bytes)?;
+                writer.enter_key(&key);
                 write_value(writer, val)?;
+                writer.leave_key();
             }
         }
         Value::List(items) => {
+            writer.check_len("list item count", items.len(), u32::MAX as u64)?;
             writer.write_all(&[ValueTag::List as u8])?;
+            let body_len = writer.next_body_len()?;
+            writer.write_all(&body_len.to_le_bytes())?;
             writer.write_all(&(items.len() as u32).to_le_bytes())?;
-            for item in items {
+            writer.enter_list();
+            for (index, item) in items.into_iter().enumerate() {
+                writer.enter_item(index);
                 write_value(writer, item)?;
+                writer.leave_item();
             }
+            writer.leave_list();
         }
     }
     Ok(())
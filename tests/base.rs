@@ -2,7 +2,12 @@
 //!
 //! These tests demonstrate the main use cases for the blobfig format.
 
-use blobfig::{Array, DType, File, FileHandle, HEADER_SIZE, MAGIC, VERSION, Value, parse, writer};
+use blobfig::{
+    AccessError, Array, BlobfigError, ChunkedArray, DType, File, FileHandle, HEADER_SIZE, Header,
+    Limit, MAGIC, MAX_REFERENCE_HOPS, MAX_REFERENCE_LOOKUPS, Order, ParseError, ParseOptions,
+    Reader, Sparse, SparseFormat, StringArray, Timestamp, VERSION, Value, ValueTag, ValueView,
+    parse, parse_index, parse_lazy, parse_verified, parse_with, writer,
+};
 use std::io::{self, Read};
 
 // =============================================================================
@@ -35,6 +40,89 @@ fn roundtrip_primitives() {
     assert_eq!(parse(&bytes).unwrap().as_str(), Some("hello 世界"));
 }
 
+#[test]
+fn null_uint_bytes_and_timestamp() {
+    let created = Timestamp::new(1_700_000_000, 250_000_000).unwrap();
+    let value = Value::Object(vec![
+        ("seed".into(), Value::from(u64::MAX)),
+        ("hash".into(), Value::from(&[0xDE, 0xAD, 0xBE, 0xEF][..])),
+        ("created".into(), Value::from(created)),
+        ("parent".into(), Value::from(None::<String>)),
+    ]);
+    let bytes = writer::to_bytes(value).unwrap();
+    let parsed = parse(&bytes).unwrap();
+
+    assert_eq!(parsed.uint("seed"), Ok(u64::MAX));
+    assert_eq!(parsed.bytes("hash"), Ok(&[0xDE, 0xAD, 0xBE, 0xEF][..]));
+    assert_eq!(parsed.timestamp("created"), Ok(created));
+    assert!(parsed.get("parent").unwrap().is_null());
+    assert_eq!(
+        parsed.int("seed"),
+        Err(AccessError::TypeMismatch {
+            path: "seed".into(),
+            expected: "int",
+            actual: ValueTag::UInt,
+        })
+    );
+
+    let lazy = parse_lazy(&bytes).unwrap().get("created").unwrap().unwrap();
+    assert_eq!(lazy.decode().unwrap().as_timestamp(), Some(created));
+    let mut reader = Reader::new(io::Cursor::new(&bytes)).unwrap();
+    let hash = reader.get("hash").unwrap().unwrap();
+    assert_eq!(hash.as_bytes(), Some(&[0xDE, 0xAD, 0xBE, 0xEF][..]));
+    assert!(reader.get("parent").unwrap().unwrap().is_null());
+
+    // Nanoseconds must stay below one second
+    let mut bytes = bytes;
+    let nanos = 250_000_000u32.to_le_bytes();
+    let at = bytes.windows(4).position(|w| w == nanos).unwrap();
+    bytes[at..at + 4].copy_from_slice(&1_000_000_000u32.to_le_bytes());
+    let err = parse(&bytes).unwrap_err();
+    assert_eq!(err.kind, BlobfigError::InvalidTimestamp(1_000_000_000));
+    assert_eq!(err.path, "created");
+}
+
+#[test]
+fn string_array_vocabulary() {
+    let vocab: Vec<String> = ["<pad>", "<unk>", "the", "", "naïve"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let value = Value::Object(vec![(
+        "vocab".into(),
+        Value::from(StringArray::from(vocab.clone())),
+    )]);
+    let bytes = writer::to_bytes(value).unwrap();
+    let parsed = parse(&bytes).unwrap();
+
+    let strings = parsed.string_array("vocab").unwrap();
+    assert_eq!(strings.len(), 5);
+    assert_eq!(strings.get(4), Some("naïve"));
+    assert_eq!(strings.get(5), None);
+    assert_eq!(strings.iter().collect::<Vec<_>>(), vocab);
+
+    let lazy = parse_lazy(&bytes).unwrap().get("vocab").unwrap().unwrap();
+    let lazy = lazy.decode().unwrap();
+    assert_eq!(lazy.as_string_array().unwrap().get(2), Some("the"));
+    let mut reader = Reader::new(io::Cursor::new(&bytes)).unwrap();
+    let node = reader.get("vocab").unwrap().unwrap();
+    assert_eq!(node.as_string_array(), Some(&StringArray::from(vocab)));
+
+    // Element count and length count against the limits
+    let options = ParseOptions {
+        max_container_len: 4,
+        ..ParseOptions::default()
+    };
+    let err = parse_with(&bytes, options).unwrap_err();
+    assert!(matches!(err.kind, BlobfigError::LimitExceeded { .. }));
+    let options = ParseOptions {
+        max_string_len: 5,
+        ..ParseOptions::default()
+    };
+    let err = parse_with(&bytes, options).unwrap_err();
+    assert!(matches!(err.kind, BlobfigError::LimitExceeded { .. }));
+}
+
 // =============================================================================
 // Arrays (typed tensors)
 // =============================================================================
@@ -82,6 +170,306 @@ fn roundtrip_array_2d() {
     }
 }
 
+// =============================================================================
+// Sparse tensors
+// =============================================================================
+
+#[test]
+fn sparse_embedding_table() {
+    // 4x3 f32 table with two nonzero elements
+    let mut floats = [0.0f32; 12];
+    floats[1] = 1.5;
+    floats[11] = -2.0;
+    let data = floats.iter().flat_map(|f| f.to_le_bytes()).collect();
+    let dense = Array::new(DType::F32, vec![4, 3], data);
+
+    let sparse = Sparse::from_dense(&dense, SparseFormat::Csr).unwrap();
+    assert_eq!(sparse.values.shape, vec![2]);
+    let value = Value::Object(vec![("table".into(), sparse.into())]);
+    let bytes = writer::to_bytes(value).unwrap();
+
+    let parsed = parse(&bytes).unwrap();
+    let table = parsed.sparse("table").unwrap();
+    assert_eq!(table.format(), SparseFormat::Csr);
+    assert_eq!(table.shape, vec![4, 3]);
+    assert_eq!(table.nnz(), 2);
+    assert_eq!(table.to_dense().unwrap().data, dense.data);
+
+    let lazy = parse_lazy(&bytes).unwrap().get("table").unwrap().unwrap();
+    let lazy = lazy.decode().unwrap();
+    assert_eq!(lazy.as_sparse().unwrap().nnz(), 2);
+
+    let mut reader = Reader::new(io::Cursor::new(&bytes)).unwrap();
+    let node = reader.get("table").unwrap().unwrap();
+    let node = node.as_sparse().unwrap();
+    assert_eq!(node.format(), SparseFormat::Csr);
+    assert_eq!(node.dtype(), DType::F32);
+    let mut values = Vec::new();
+    let mut payload = reader.array_payload(&node.values).unwrap();
+    payload.read_to_end(&mut values).unwrap();
+    assert_eq!(values, table.values.data);
+}
+
+#[test]
+fn sparse_indices_are_checked() {
+    let coords = [0u32, 1, 2, 0]
+        .iter()
+        .flat_map(|c| c.to_le_bytes())
+        .collect();
+    let sparse = Sparse::coo(
+        vec![2, 2],
+        Array::new(DType::U32, vec![2, 2], coords),
+        Array::new(DType::U8, vec![2], vec![1, 2]),
+    );
+    assert_eq!(
+        sparse.validate(),
+        Err(BlobfigError::InvalidSparse("coordinate out of bounds"))
+    );
+    let err = writer::to_bytes(sparse.into()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // Values must match the number of coordinates
+    let sparse = Sparse::coo(
+        vec![2, 2],
+        Array::new(DType::U32, vec![1, 2], vec![0; 8]),
+        Array::new(DType::U8, vec![2], vec![1, 2]),
+    );
+    assert!(matches!(
+        sparse.validate(),
+        Err(BlobfigError::InvalidSparse(_))
+    ));
+}
+
+// =============================================================================
+// Quantized arrays
+// =============================================================================
+
+#[test]
+fn quantized_weights() {
+    // 40 Q4_0 elements take two blocks: scale 1.0 (f16 0x3C00), q = 8 + (i % 8)
+    let block = |scale: [u8; 2]| {
+        let mut block = scale.to_vec();
+        block.extend((0..16).map(|i| (8 + i % 8) | ((8 + (i + 16) % 8) << 4)));
+        block
+    };
+    let data = [block([0x00, 0x3C]), block([0x00, 0x3C])].concat();
+    let q4 = Array::try_new(DType::Q4_0, vec![5, 8], data).unwrap();
+    assert!(Array::try_new(DType::Q4_0, vec![5, 8], vec![0; 18]).is_err());
+    assert_eq!(DType::Q4_0.element_size(), None);
+    assert_eq!(
+        (DType::Q4_0.block_size(), DType::Q4_0.block_len()),
+        (18, 32)
+    );
+
+    // Int4 values are packed two per byte without a scale
+    let i4 = Array::try_new(DType::I4, vec![3], vec![0x9F, 0x07]).unwrap();
+
+    let value = Value::Object(vec![("q4".into(), q4.into()), ("i4".into(), i4.into())]);
+    let bytes = writer::to_bytes(value).unwrap();
+    let parsed = parse(&bytes).unwrap();
+
+    let q4 = parsed.array("q4").unwrap();
+    let expected: Vec<f32> = (0..40).map(|i| (i % 8) as f32).collect();
+    assert_eq!(q4.dequantize_f32().unwrap(), expected);
+    let i4 = parsed.array("i4").unwrap();
+    assert_eq!(i4.dequantize_f32().unwrap(), [-1.0, -7.0, 7.0]);
+
+    let floats = Array::new(DType::F32, vec![1], vec![0; 4]);
+    assert_eq!(
+        floats.dequantize_f32(),
+        Err(BlobfigError::NotQuantized(DType::F32))
+    );
+}
+
+// =============================================================================
+// Array layout
+// =============================================================================
+
+#[test]
+fn array_layout_roundtrip() {
+    // A [2, 3] spectrogram stored column by column: element [t, m] is 10 * t + m
+    let data: Vec<u8> = [0u16, 10, 1, 11, 2, 12]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let mel = Array::new(DType::U16, vec![2, 3], data)
+        .with_order(Order::F)
+        .with_axis_names(["time", "mel"]);
+    // A [2, 2] u8 array with explicit strides, column by column
+    let strided = Array::new(DType::U8, vec![2, 2], vec![1, 2, 3, 4]).with_strides(vec![1, 2]);
+    let plain = Array::new(DType::U8, vec![2], vec![5, 6]);
+
+    let value = Value::Object(vec![
+        ("mel".into(), mel.into()),
+        ("strided".into(), strided.into()),
+        ("plain".into(), plain.clone().into()),
+    ]);
+    let bytes = writer::to_bytes(value).unwrap();
+
+    let parsed = parse(&bytes).unwrap();
+    let mel = parsed.array("mel").unwrap();
+    assert_eq!(mel.layout.order, Order::F);
+    assert_eq!(mel.axis_index("mel"), Some(1));
+    assert_eq!(
+        mel.layout.byte_strides(mel.dtype, &mel.shape).unwrap(),
+        [2, 4]
+    );
+    let strided = parsed.array("strided").unwrap();
+    assert_eq!(strided.layout.strides, Some(vec![1, 2]));
+    assert!(parsed.array("plain").unwrap().layout.is_default());
+
+    let lazy = parse_lazy(&bytes).unwrap().get("mel").unwrap().unwrap();
+    let lazy = lazy.decode().unwrap();
+    assert_eq!(lazy.as_array().unwrap().axis_index("time"), Some(0));
+    let lazy = parse_lazy(&bytes).unwrap().get("plain").unwrap().unwrap();
+    assert_eq!(lazy.decode().unwrap().as_array().unwrap().data, [5, 6]);
+
+    let mut reader = Reader::new(io::Cursor::new(&bytes)).unwrap();
+    let node = reader.get("mel").unwrap().unwrap();
+    let node = node.as_array().unwrap();
+    assert_eq!(node.layout.order, Order::F);
+    assert_eq!(node.layout.axis_index("mel"), Some(1));
+    let node = reader.get("plain").unwrap().unwrap();
+    assert!(node.as_array().unwrap().layout.is_default());
+
+    // Files whose arrays are all in the default layout are unchanged
+    let with_plain = writer::to_bytes(Value::List(vec![plain.into()])).unwrap();
+    let header = Header::from_bytes(with_plain[..HEADER_SIZE].try_into().unwrap());
+    assert!(!header.has_array_layout());
+    let header = Header::from_bytes(bytes[..HEADER_SIZE].try_into().unwrap());
+    assert!(header.has_array_layout());
+}
+
+#[test]
+fn array_layout_is_validated() {
+    let write = |arr: Array| writer::to_bytes(arr.into());
+    let arr = || Array::new(DType::F32, vec![2, 3], vec![0; 24]);
+
+    assert!(write(arr().with_axis_names(["x", "x"])).is_err());
+    assert!(write(arr().with_axis_names(["x"])).is_err());
+    assert!(write(arr().with_strides(vec![12, 8])).is_err());
+    assert_eq!(
+        arr().with_strides(vec![4, 2]).validate(),
+        Err(BlobfigError::InvalidArrayLayout(
+            "strides must be multiples of the element size"
+        ))
+    );
+    let q8 = Array::new(DType::Q8_0, vec![32], vec![0; 34]).with_order(Order::F);
+    assert!(q8.validate().is_err());
+
+    // Sparse tensors and chunks are built from C-order arrays only
+    let fortran = arr().with_order(Order::F);
+    assert!(Sparse::from_dense(&fortran, SparseFormat::Coo).is_err());
+    assert!(ChunkedArray::from_array(&fortran, vec![1, 3]).is_err());
+}
+
+// =============================================================================
+// Chunked arrays
+// =============================================================================
+
+#[test]
+fn chunked_array_from_producer() {
+    // 5x4 u16 array in 2x3 chunks, each element holding its flat index
+    let element = |row: u64, col: u64| ((row * 4 + col) as u16).to_le_bytes();
+    let producer = move |index: &[u64], shape: &[u64]| {
+        let rows = (0..shape[0]).map(|r| index[0] * 2 + r);
+        let data = rows
+            .flat_map(|row| (0..shape[1]).flat_map(move |c| element(row, index[1] * 3 + c)))
+            .collect();
+        Ok(data)
+    };
+    let chunked = ChunkedArray::from_producer(DType::U16, vec![5, 4], vec![2, 3], producer);
+    assert_eq!(chunked.grid(), vec![3, 2]);
+    let value = Value::Object(vec![("table".into(), chunked.into())]);
+    let bytes = writer::to_bytes(value).unwrap();
+
+    let parsed = parse(&bytes).unwrap();
+    let table = parsed.chunked_array("table").unwrap();
+    assert_eq!(table.num_chunks(), 6);
+    let edge = table.chunk(&[2, 1]).unwrap();
+    assert_eq!(edge.shape, vec![1, 1]);
+    assert_eq!(edge.data, &element(4, 3));
+    assert!(table.chunk(&[3, 0]).is_none());
+    let dense = table.to_dense().unwrap();
+    let expected: Vec<u8> = (0..20u16).flat_map(u16::to_le_bytes).collect();
+    assert_eq!(dense.shape, vec![5, 4]);
+    assert_eq!(dense.data, expected);
+
+    let lazy = parse_lazy(&bytes).unwrap().get("table").unwrap().unwrap();
+    let lazy = lazy.decode().unwrap();
+    assert_eq!(
+        lazy.as_chunked_array().unwrap().to_dense().unwrap().data,
+        dense.data
+    );
+
+    // The reader seeks to a single chunk through the offset table
+    let mut reader = Reader::new(io::Cursor::new(&bytes)).unwrap();
+    let node = reader.get("table").unwrap().unwrap();
+    let node = node.as_chunked_array().unwrap();
+    assert_eq!(node.grid(), vec![3, 2]);
+    let chunk = reader.chunk(node, &[1, 0]).unwrap().unwrap();
+    assert_eq!(chunk.shape, vec![2, 3]);
+    let mut data = Vec::new();
+    reader
+        .array_payload(&chunk)
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    assert_eq!(data, table.chunk(&[1, 0]).unwrap().data);
+    assert!(reader.chunk(node, &[0, 2]).unwrap().is_none());
+}
+
+#[test]
+fn chunked_array_chunks_are_payloads() {
+    // Identical chunks are stored once
+    let dense = Array::new(DType::U8, vec![4, 64], vec![7; 256]);
+    let chunked = ChunkedArray::from_array(&dense, vec![1, 64]).unwrap();
+    let plain = writer::to_bytes(chunked.into()).unwrap();
+    let chunked = ChunkedArray::from_array(&dense, vec![1, 64]).unwrap();
+    let options = writer::WriteOptions::default().with_dedup();
+    let deduped = writer::to_bytes_with(chunked.into(), options).unwrap();
+    assert!(deduped.len() < plain.len() - 3 * 64);
+
+    let parsed = parse(&deduped).unwrap();
+    let parsed = parsed.as_chunked_array().unwrap();
+    assert_eq!(parsed.to_dense().unwrap().data, dense.data);
+}
+
+#[test]
+fn quantized_chunks_are_read_one_at_a_time() {
+    // Rows of 32 Q8_0 elements, each one block whose values are the row number
+    let producer = |index: &[u64], shape: &[u64]| {
+        let mut data = Vec::new();
+        for row in index[0] * 2..index[0] * 2 + shape[0] {
+            data.extend([0x00, 0x3C]);
+            data.extend([row as u8; 32]);
+        }
+        Ok(data)
+    };
+    let chunked = ChunkedArray::from_producer(DType::Q8_0, vec![3, 32], vec![2, 32], producer);
+    let bytes = writer::to_bytes(chunked.into()).unwrap();
+
+    let parsed = parse(&bytes).unwrap();
+    let parsed = parsed.as_chunked_array().unwrap();
+    let last = parsed.chunk(&[1, 0]).unwrap();
+    assert_eq!(last.dequantize_f32().unwrap(), [2.0; 32]);
+    assert!(parsed.to_dense().is_err());
+}
+
+#[test]
+fn produced_chunks_must_match_their_shape() {
+    let producer = |_: &[u64], shape: &[u64]| Ok(vec![0; shape[0] as usize + 1]);
+    let chunked = ChunkedArray::from_producer(DType::U8, vec![4], vec![2], producer);
+    let err = writer::to_bytes(chunked.into()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // Errors from the producer are passed through
+    let producer = |_: &[u64], _: &[u64]| Err(io::Error::other("tile store offline"));
+    let chunked = ChunkedArray::from_producer(DType::U8, vec![4], vec![2], producer);
+    let err = writer::to_bytes(chunked.into()).unwrap_err();
+    assert_eq!(err.to_string(), "tile store offline");
+}
+
 // =============================================================================
 // Files (embedded blobs)
 // =============================================================================
@@ -449,3 +837,636 @@ fn deeply_nested() {
 
     assert_eq!(parsed.get("a/b/c/d").unwrap().as_int(), Some(42));
 }
+
+// =============================================================================
+// Lazy access
+// =============================================================================
+
+#[test]
+fn lazy_get_skips_unrelated_subtrees() {
+    let value = Value::Object(vec![
+        (
+            "big".into(),
+            Value::List((0..1000).map(Value::Int).collect()),
+        ),
+        (
+            "models".into(),
+            Value::Object(vec![(
+                "encoder".into(),
+                Value::Object(vec![("layers".into(), Value::Int(12))]),
+            )]),
+        ),
+    ]);
+    let mut encoded = writer::to_bytes(value).unwrap();
+
+    // Corrupt an item inside "big": the eager parser fails, the lazy path
+    // lookup never decodes it
+    let first_item = encoded
+        .windows(9)
+        .position(|w| w[0] == ValueTag::Int as u8 && w[1..] == 0i64.to_le_bytes())
+        .unwrap();
+    encoded[first_item] = 0xEE;
+    assert!(parse(&encoded).is_err());
+
+    let root = parse_lazy(&encoded).unwrap();
+    let layers = root.get("models/encoder/layers").unwrap().unwrap();
+    assert_eq!(layers.decode().unwrap().as_int(), Some(12));
+}
+
+#[test]
+fn version_1_files_still_parse() {
+    // Version 1 containers have no byte-length prefix
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.push(ValueTag::Object as u8);
+    bytes.extend_from_slice(&2u32.to_le_bytes());
+    bytes.extend_from_slice(&4u16.to_le_bytes());
+    bytes.extend_from_slice(b"list");
+    bytes.push(ValueTag::List as u8);
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.push(ValueTag::Bool as u8);
+    bytes.push(1);
+    bytes.extend_from_slice(&4u16.to_le_bytes());
+    bytes.extend_from_slice(b"rate");
+    bytes.push(ValueTag::Int as u8);
+    bytes.extend_from_slice(&16000i64.to_le_bytes());
+
+    let parsed = parse(&bytes).unwrap();
+    assert_eq!(parsed.get("rate").unwrap().as_int(), Some(16000));
+    assert_eq!(parsed.get("list").unwrap().as_list().unwrap().len(), 1);
+
+    let lazy = parse_lazy(&bytes).unwrap();
+    let rate = lazy.get("rate").unwrap().unwrap();
+    assert_eq!(rate.decode().unwrap().as_int(), Some(16000));
+
+    let mut reader = Reader::new(io::Cursor::new(bytes)).unwrap();
+    let rate = reader.get("rate").unwrap().unwrap();
+    assert_eq!(rate.as_int(), Some(16000));
+}
+
+#[test]
+fn path_index_lookup() {
+    let value = Value::Object(vec![
+        ("sample_rate".into(), Value::Int(16000)),
+        (
+            "models".into(),
+            Value::Object(vec![(
+                "encoder".into(),
+                Value::Object(vec![(
+                    "weights".into(),
+                    Value::Array(Array::new(DType::F32, vec![2], vec![0u8; 8])),
+                )]),
+            )]),
+        ),
+    ]);
+    let options = writer::WriteOptions::default().with_path_index();
+    let bytes = writer::to_bytes_with(value, options).unwrap();
+
+    // The trailer does not disturb regular parsing
+    let parsed = parse(&bytes).unwrap();
+    assert_eq!(parsed.get("sample_rate").unwrap().as_int(), Some(16000));
+
+    let index = parse_index(&bytes).unwrap();
+    let weights = index.get("models/encoder/weights").unwrap().unwrap();
+    let weights = weights.as_array().unwrap();
+    assert_eq!(weights.shape, vec![2]);
+    assert_eq!(weights.data.len(), 8);
+    assert!(index.get("models/decoder").unwrap().is_none());
+}
+
+#[test]
+fn checksums_detect_bit_rot() {
+    let value = Value::Object(vec![
+        (
+            "encoder".into(),
+            Value::Array(Array::new(DType::U8, vec![64], vec![1u8; 64])),
+        ),
+        (
+            "decoder".into(),
+            Value::Array(Array::new(DType::U8, vec![64], vec![2u8; 64])),
+        ),
+    ]);
+    let options = writer::WriteOptions::default()
+        .with_path_index()
+        .with_checksums();
+    let mut bytes = writer::to_bytes_with(value, options).unwrap();
+    assert!(parse_verified(&bytes).is_ok());
+
+    // Flip one bit in the decoder payload
+    let decoder = bytes.windows(64).position(|w| w == [2u8; 64]).unwrap();
+    bytes[decoder + 10] ^= 0x01;
+
+    let err = parse_verified(&bytes).unwrap_err();
+    assert!(matches!(err.kind, BlobfigError::ChecksumMismatch { .. }));
+    assert_eq!(err.path, "decoder");
+
+    // Only the blobs that are read need to be hashed
+    let index = parse_index(&bytes).unwrap();
+    let encoder = index.get("encoder").unwrap().unwrap();
+    assert!(encoder.as_array().unwrap().verify().is_ok());
+    let decoder = index.get("decoder").unwrap().unwrap();
+    assert!(decoder.as_array().unwrap().verify().is_err());
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn compressed_payloads_roundtrip() {
+    use blobfig::Compression;
+
+    let tokenizer = br#"{"vocab": ["a", "b", "c"]}"#.repeat(200);
+    let value = Value::Object(vec![
+        (
+            "tokenizer".into(),
+            Value::File(File::from_bytes("application/json", tokenizer.clone())),
+        ),
+        (
+            "bias".into(),
+            Value::Array(Array::new(DType::U8, vec![4], vec![1, 2, 3, 4])),
+        ),
+    ]);
+    let options = writer::WriteOptions::default()
+        .with_checksums()
+        .with_compression(Compression::Zstd, 64);
+    let bytes = writer::to_bytes_with(value, options).unwrap();
+    assert!(bytes.len() < tokenizer.len());
+
+    let parsed = parse_verified(&bytes).unwrap();
+    let file = parsed.get("tokenizer").unwrap().as_file().unwrap();
+    assert_eq!(file.compression.unwrap().codec, Compression::Zstd);
+    assert_eq!(file.decompressed().unwrap(), tokenizer);
+
+    // Below the threshold the payload stays raw and is borrowed
+    let bias = parsed.get("bias").unwrap().as_array().unwrap();
+    assert!(bias.compression.is_none());
+    assert!(matches!(
+        bias.decompressed().unwrap(),
+        std::borrow::Cow::Borrowed(&[1, 2, 3, 4])
+    ));
+
+    // A cap below the recorded size refuses to decompress
+    let err = file.decompressed_with(1024).unwrap_err();
+    assert!(matches!(
+        err,
+        BlobfigError::LimitExceeded {
+            limit: Limit::DecompressedSize,
+            ..
+        }
+    ));
+
+    // The streaming reader exposes the codec and the stored bytes
+    let mut reader = Reader::new(io::Cursor::new(&bytes)).unwrap();
+    let node = reader.get("tokenizer").unwrap().unwrap();
+    let node = node.as_file().unwrap();
+    assert_eq!(node.compression, file.compression);
+    assert_eq!(node.size, file.data.len() as u64);
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn delta_needs_narrow_elements() {
+    use blobfig::{Compression, Filters};
+
+    // Writing: complex128 elements are too wide to delta encode
+    let array = Array::new(DType::C128, vec![2], vec![1; 32]);
+    let err = array
+        .clone()
+        .compress_with(Compression::Zstd, Filters::DELTA)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let array = array
+        .compress_with(Compression::Zstd, Filters::SHUFFLE)
+        .unwrap();
+    let bytes = writer::to_bytes(Value::Array(array)).unwrap();
+
+    // Quantized blocks are not filtered at all
+    let q8 = Array::new(DType::Q8_0, vec![32], vec![0; 34]);
+    let err = q8
+        .compress_with(Compression::Zstd, Filters::SHUFFLE)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // Decoding: a view claiming delta encoding is refused
+    let parsed = parse(&bytes).unwrap();
+    let mut view = parsed.as_array().unwrap().clone();
+    view.compression.as_mut().unwrap().filters = Filters::DELTA;
+    let err = view.decompressed().unwrap_err();
+    assert_eq!(err, BlobfigError::InvalidFilters(Filters::DELTA.bits()));
+
+    // Parsing: so is a file whose filter byte was changed to delta
+    let mut fields = vec![Compression::Zstd as u8, Filters::SHUFFLE.bits()];
+    fields.extend_from_slice(&32u64.to_le_bytes());
+    let at = bytes
+        .windows(fields.len())
+        .position(|w| w == fields)
+        .unwrap();
+    let mut crafted = bytes.clone();
+    crafted[at + 1] = Filters::DELTA.bits();
+    let err = parse(&crafted).unwrap_err();
+    assert_eq!(
+        err.kind,
+        BlobfigError::InvalidFilters(Filters::DELTA.bits())
+    );
+    assert_eq!(err.offset, at + 1);
+    assert!(
+        Reader::new(io::Cursor::new(crafted))
+            .unwrap()
+            .root()
+            .is_err()
+    );
+}
+
+#[test]
+fn duplicate_payloads_share_bytes() {
+    let tokenizer = br#"{"vocab": ["a", "b", "c"]}"#.to_vec();
+    let embedding: Vec<u8> = (0..256u32).map(|i| (i % 251) as u8).collect();
+    let head = || {
+        Value::Object(vec![
+            (
+                "tokenizer".into(),
+                Value::File(File::from_bytes("application/json", tokenizer.clone())),
+            ),
+            (
+                "embedding".into(),
+                Value::Array(Array::new(DType::F32, vec![64], embedding.clone())),
+            ),
+        ])
+    };
+    let value = Value::Object(vec![("intent".into(), head()), ("slots".into(), head())]);
+    let options = writer::WriteOptions::default()
+        .with_checksums()
+        .with_dedup();
+    let mut bytes = Vec::new();
+    let stats = writer::write_with_stats(&mut bytes, value, options).unwrap();
+    assert_eq!(stats.payloads, 4);
+    assert_eq!(stats.duplicates, 2);
+    assert_eq!(
+        stats.bytes_saved,
+        (tokenizer.len() + embedding.len()) as u64
+    );
+
+    // Both paths are views over the same bytes
+    let parsed = parse_verified(&bytes).unwrap();
+    let first = parsed.get("intent/embedding").unwrap().as_array().unwrap();
+    let second = parsed.get("slots/embedding").unwrap().as_array().unwrap();
+    assert_eq!(second.data, embedding.as_slice());
+    assert!(std::ptr::eq(first.data, second.data));
+    let file = parsed.get("slots/tokenizer").unwrap().as_file().unwrap();
+    assert_eq!(file.data, tokenizer.as_slice());
+
+    let lazy = parse_lazy(&bytes).unwrap();
+    let lazy = lazy.get("slots/embedding").unwrap().unwrap();
+    assert!(std::ptr::eq(
+        lazy.to_view().unwrap().as_array().unwrap().data,
+        first.data
+    ));
+
+    // The streaming reader points both nodes at the same payload
+    let mut reader = Reader::new(io::Cursor::new(&bytes)).unwrap();
+    let intent = reader.get("intent/embedding").unwrap().unwrap();
+    let slots = reader.get("slots/embedding").unwrap().unwrap();
+    assert_eq!(intent.payload_range(), slots.payload_range());
+    let mut data = Vec::new();
+    let mut payload = reader.payload(&slots).unwrap().unwrap();
+    payload.read_to_end(&mut data).unwrap();
+    assert_eq!(data, embedding);
+    let root = reader.root().unwrap();
+    let entries = reader.entries(root.as_object().unwrap()).unwrap();
+    assert_eq!(entries.len(), 2);
+
+    // A reference may only point backwards
+    let offset = first.data.as_ptr() as usize - bytes.as_ptr() as usize;
+    let forged = bytes
+        .windows(8)
+        .rposition(|w| w == (offset as u64).to_le_bytes())
+        .unwrap();
+    let end = bytes.len() as u64;
+    bytes[forged..forged + 8].copy_from_slice(&end.to_le_bytes());
+    let err = parse(&bytes).unwrap_err();
+    assert_eq!(err.kind, BlobfigError::InvalidReference(end));
+    assert_eq!(err.path, "slots/embedding");
+}
+
+#[test]
+fn references_resolve_to_their_target() {
+    let embeddings: Vec<u8> = (0..32).collect();
+    let value = Value::Object(vec![
+        (
+            "defaults".into(),
+            Value::Object(vec![(
+                "audio".into(),
+                Value::Object(vec![("sample_rate".into(), Value::Int(16000))]),
+            )]),
+        ),
+        (
+            "encoder".into(),
+            Value::Object(vec![(
+                "embeddings".into(),
+                Value::Array(Array::new(DType::U8, vec![32], embeddings.clone())),
+            )]),
+        ),
+        (
+            "decoder".into(),
+            Value::Object(vec![(
+                "embeddings".into(),
+                Value::Ref("encoder/embeddings".into()),
+            )]),
+        ),
+        ("audio".into(), Value::Ref("defaults/audio".into())),
+    ]);
+    let bytes = writer::to_bytes(value).unwrap();
+    let parsed = parse(&bytes).unwrap();
+
+    // Both paths are the same value
+    let encoder = parsed.array("encoder/embeddings").unwrap();
+    let decoder = parsed.array("decoder/embeddings").unwrap();
+    assert!(std::ptr::eq(encoder, decoder));
+    assert_eq!(decoder.data, embeddings.as_slice());
+
+    // References are followed in the middle of a path too
+    assert_eq!(parsed.int("audio/sample_rate"), Ok(16000));
+    assert_eq!(
+        parsed.int("audio/channels"),
+        Err(AccessError::NotFound {
+            path: "audio/channels".into()
+        })
+    );
+
+    // The entry itself still holds the reference
+    let decoder = parsed.get("decoder").unwrap().as_object().unwrap();
+    assert_eq!(decoder[0].1.as_ref_path(), Some("encoder/embeddings"));
+    let lazy = parse_lazy(&bytes).unwrap().get("audio").unwrap().unwrap();
+    assert_eq!(lazy.decode().unwrap().as_ref_path(), Some("defaults/audio"));
+    let mut reader = Reader::new(io::Cursor::new(&bytes)).unwrap();
+    let node = reader.get("audio").unwrap().unwrap();
+    assert_eq!(node.as_ref_path(), Some("defaults/audio"));
+}
+
+#[test]
+fn references_resolve_from_the_root() {
+    let value = Value::Object(vec![
+        (
+            "defaults".into(),
+            Value::Object(vec![("rate".into(), Value::Int(16000))]),
+        ),
+        (
+            "model".into(),
+            Value::Object(vec![
+                ("rate".into(), Value::Ref("defaults/rate".into())),
+                ("self".into(), Value::Ref("model".into())),
+            ]),
+        ),
+    ]);
+    let bytes = writer::to_bytes(value).unwrap();
+    let root = parse(&bytes).unwrap();
+
+    // Below a nested view, targets are found through the root
+    let model = root.get("model").unwrap();
+    assert_eq!(
+        model.resolve_in(&root, "rate").unwrap().as_int(),
+        Some(16000)
+    );
+    assert!(model.get_in(&root, "self/rate").is_some());
+    assert_eq!(
+        model.int("rate"),
+        Err(AccessError::NotFound {
+            path: "rate".into()
+        })
+    );
+
+    // A path may pass through the same alias repeatedly without a cycle,
+    // and each alias starts a new chain of references
+    let path = format!("model{}/rate", "/self".repeat(2 * MAX_REFERENCE_HOPS));
+    assert_eq!(root.int(&path), Ok(16000));
+    let path = format!("model{}/rate", "/self".repeat(MAX_REFERENCE_LOOKUPS));
+    assert!(matches!(
+        root.int(&path),
+        Err(AccessError::TooManyReferences { max, .. }) if max == MAX_REFERENCE_LOOKUPS
+    ));
+}
+
+#[test]
+fn invalid_references_are_rejected() {
+    let dangling = Value::Object(vec![("alias".into(), Value::Ref("missing".into()))]);
+    let err = writer::to_bytes(dangling).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("'alias'"));
+
+    // Views can be built by hand, so cycles are still detected on access
+    let cycle = ValueView::Object(vec![("a", ValueView::Ref("b")), ("b", ValueView::Ref("a"))]);
+    assert_eq!(
+        cycle.int("a"),
+        Err(AccessError::ReferenceCycle { path: "a".into() })
+    );
+    assert!(cycle.get("b").is_none());
+
+    let chain: Vec<(String, Value)> = (0..=MAX_REFERENCE_HOPS)
+        .map(|i| (format!("r{}", i), Value::Ref(format!("r{}", i + 1))))
+        .chain([(format!("r{}", MAX_REFERENCE_HOPS + 1), Value::Int(7))])
+        .collect();
+    let err = writer::to_bytes(Value::Object(chain)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn reader_streams_from_seekable_source() {
+    let model: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let value = Value::Object(vec![
+        (
+            "tokenizer".into(),
+            Value::File(File::from_bytes("application/json", b"{}".to_vec())),
+        ),
+        (
+            "model".into(),
+            Value::File(File::from_bytes("application/octet-stream", model.clone())),
+        ),
+        ("sample_rate".into(), Value::Int(16000)),
+    ]);
+    let bytes = writer::to_bytes(value).unwrap();
+
+    let mut reader = Reader::new(io::Cursor::new(bytes)).unwrap();
+    let rate = reader.get("sample_rate").unwrap().unwrap();
+    assert_eq!(rate.as_int(), Some(16000));
+
+    let node = reader.get("model").unwrap().unwrap();
+    let mut payload = reader.payload(&node).unwrap().unwrap();
+    assert_eq!(payload.len(), model.len() as u64);
+
+    // Copy out in small chunks as a streaming consumer would
+    let mut out = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = payload.read(&mut chunk).unwrap();
+        if n == 0 {
+            break;
+        }
+        out.extend_from_slice(&chunk[..n]);
+    }
+    assert_eq!(out, model);
+}
+
+#[test]
+fn parse_error_outlives_buffer() {
+    fn load() -> Result<i64, Box<dyn std::error::Error>> {
+        let value = Value::Object(vec![(
+            "models".into(),
+            Value::Object(vec![(
+                "encoder".into(),
+                Value::Object(vec![(
+                    "weights".into(),
+                    Value::Array(Array::new(DType::U8, vec![2], vec![1, 2])),
+                )]),
+            )]),
+        )]);
+        let mut bytes = writer::to_bytes(value).unwrap();
+        let dtype = bytes
+            .windows(3)
+            .position(|w| w == [ValueTag::Array as u8, DType::U8 as u8, 1])
+            .unwrap()
+            + 1;
+        bytes[dtype] = 0xEE;
+
+        let parsed = parse(&bytes)?;
+        Ok(parsed
+            .get("models/encoder/weights")
+            .unwrap()
+            .as_int()
+            .unwrap())
+    }
+
+    let err = load().unwrap_err();
+    let err = err.downcast::<ParseError>().unwrap();
+    assert_eq!(err.kind, BlobfigError::InvalidDType(0xEE));
+    assert_eq!(err.path, "models/encoder/weights");
+    assert!(err.to_string().contains("models/encoder/weights"));
+}
+
+#[test]
+fn inconsistent_arrays_are_rejected() {
+    assert!(Array::try_new(DType::I32, vec![2, 3], vec![0u8; 23]).is_err());
+
+    let value = Value::Object(vec![(
+        "weights".into(),
+        Value::Array(Array::new(DType::I32, vec![2, 3], vec![0u8; 23])),
+    )]);
+    let err = writer::to_bytes(value).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn bool_arrays_hold_zero_or_one() {
+    let mask = Value::Object(vec![(
+        "mask".into(),
+        Value::Array(Array::new(DType::Bool, vec![3], vec![1, 0, 1])),
+    )]);
+    let mut bytes = writer::to_bytes_with(mask, writer::WriteOptions::unaligned()).unwrap();
+    let parsed = parse(&bytes).unwrap();
+    assert_eq!(parsed.array("mask").unwrap().data, &[1, 0, 1]);
+
+    // Corrupt the last element
+    let last = bytes.len() - 1;
+    bytes[last] = 2;
+    let err = parse(&bytes).unwrap_err();
+    assert_eq!(err.kind, BlobfigError::InvalidBool(2));
+    assert_eq!(err.offset, last);
+    assert_eq!(err.path, "mask");
+
+    let value = Value::Array(Array::new(DType::Bool, vec![1], vec![0xFF]));
+    let err = writer::to_bytes(value).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+// =============================================================================
+// Untrusted input
+// =============================================================================
+
+#[test]
+fn crafted_deep_nesting_hits_depth_limit() {
+    // Version 1 lists without length prefixes, nested far beyond any real file
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    for _ in 0..100_000 {
+        bytes.push(ValueTag::List as u8);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+    }
+    bytes.push(ValueTag::Bool as u8);
+    bytes.push(1);
+
+    let err = parse(&bytes).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        BlobfigError::LimitExceeded {
+            limit: Limit::Depth,
+            ..
+        }
+    ));
+
+    let lazy = parse_lazy(&bytes).unwrap();
+    let list = lazy.decode().unwrap();
+    assert!(list.as_list().unwrap().get(0).is_err());
+
+    let mut reader = Reader::new(io::Cursor::new(bytes)).unwrap();
+    let root = reader.root().unwrap();
+    assert!(reader.items(root.as_list().unwrap()).is_err());
+}
+
+#[test]
+fn forged_counts_do_not_allocate() {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.push(ValueTag::List as u8);
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+    bytes.push(ValueTag::Bool as u8);
+    bytes.push(1);
+
+    let err = parse(&bytes).unwrap_err();
+    assert_eq!(err.kind, BlobfigError::UnexpectedEof);
+
+    let options = ParseOptions {
+        max_container_len: 1_000,
+        ..Default::default()
+    };
+    let err = parse_with(&bytes, options).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        BlobfigError::LimitExceeded {
+            limit: Limit::ContainerLen,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn huge_payload_sizes_are_truncated_input() {
+    let mut header = Vec::new();
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&1u32.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+
+    // A File claiming u64::MAX bytes of data
+    let mut file = header.clone();
+    file.push(ValueTag::File as u8);
+    file.extend_from_slice(&0u16.to_le_bytes());
+    file.extend_from_slice(&u64::MAX.to_le_bytes());
+
+    // A chunked array whose offset table would span u64::MAX bytes
+    let mut chunked = header;
+    chunked.push(ValueTag::ChunkedArray as u8);
+    chunked.push(DType::U8 as u8);
+    chunked.push(1);
+    chunked.extend_from_slice(&(1u64 << 62).to_le_bytes());
+    chunked.extend_from_slice(&1u64.to_le_bytes());
+
+    for bytes in [file, chunked] {
+        let err = parse(&bytes).unwrap_err();
+        assert_eq!(err.kind, BlobfigError::UnexpectedEof);
+        let err = parse_lazy(&bytes).unwrap().decode().unwrap_err();
+        assert_eq!(err.kind, BlobfigError::UnexpectedEof);
+    }
+}
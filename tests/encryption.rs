@@ -0,0 +1,148 @@
+//! Integration tests for encrypted payloads
+
+#![cfg(feature = "encryption")]
+
+use blobfig::{
+    Array, BlobfigError, Cipher, DType, File, HEADER_SIZE, KEY_SIZE, Order, Reader, Value, parse,
+    parse_verified, writer,
+};
+use std::collections::HashMap;
+use std::io;
+
+const MODEL: &[u8] = b"proprietary model weights";
+
+fn keys() -> HashMap<String, [u8; KEY_SIZE]> {
+    HashMap::from([("customer-a".to_string(), [0x42; KEY_SIZE])])
+}
+
+fn artifact(cipher: Cipher) -> Vec<u8> {
+    let model = File::from_bytes("application/x-tflite", MODEL.to_vec())
+        .encrypt(cipher, "customer-a", &keys())
+        .unwrap();
+    let bias = Array::new(DType::U8, vec![3], vec![1, 2, 3])
+        .encrypt(cipher, "customer-a", &keys())
+        .unwrap();
+    let value = Value::Object(vec![
+        ("name".into(), Value::String("encoder".into())),
+        ("model".into(), Value::File(model)),
+        ("bias".into(), Value::Array(bias)),
+    ]);
+    let options = writer::WriteOptions::default().with_checksums();
+    writer::to_bytes_with(value, options).unwrap()
+}
+
+#[test]
+fn config_stays_readable_without_keys() {
+    for cipher in [Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305] {
+        let bytes = artifact(cipher);
+        assert!(!bytes.windows(MODEL.len()).any(|w| w == MODEL));
+
+        let parsed = parse_verified(&bytes).unwrap();
+        assert_eq!(parsed.get("name").unwrap().as_str(), Some("encoder"));
+
+        let model = parsed.get("model").unwrap().as_file().unwrap();
+        assert_eq!(model.mimetype, "application/x-tflite");
+        assert_eq!(model.encryption.as_ref().unwrap().cipher, cipher);
+        assert_eq!(model.decompressed().unwrap_err(), BlobfigError::Encrypted);
+        assert_eq!(model.decrypt(&keys()).unwrap(), MODEL);
+
+        let bias = parsed.get("bias").unwrap().as_array().unwrap();
+        assert_eq!(bias.shape, vec![3]);
+        assert_eq!(bias.decrypt(&keys()).unwrap().as_ref(), [1, 2, 3]);
+
+        // Encrypted payloads survive a parse and rewrite untouched
+        let rewritten = writer::to_bytes_with(
+            parsed.to_owned(),
+            writer::WriteOptions::default().with_checksums(),
+        )
+        .unwrap();
+        assert_eq!(rewritten, bytes);
+    }
+}
+
+#[test]
+fn wrong_or_missing_key_fails_cleanly() {
+    let bytes = artifact(Cipher::Aes256Gcm);
+    let parsed = parse(&bytes).unwrap();
+    let model = parsed.get("model").unwrap().as_file().unwrap();
+
+    let wrong = HashMap::from([("customer-a".to_string(), [0x43; KEY_SIZE])]);
+    assert_eq!(
+        model.decrypt(&wrong).unwrap_err(),
+        BlobfigError::DecryptionFailed
+    );
+
+    let other: HashMap<String, [u8; KEY_SIZE]> = HashMap::new();
+    assert_eq!(
+        model.decrypt(&other).unwrap_err(),
+        BlobfigError::UnknownKey("customer-a".into())
+    );
+}
+
+#[test]
+fn tampering_is_detected() {
+    let bytes = artifact(Cipher::ChaCha20Poly1305);
+
+    // Flip a ciphertext bit
+    let parsed = parse(&bytes).unwrap();
+    let model = parsed.get("model").unwrap().as_file().unwrap();
+    let offset = model.data.as_ptr() as usize - bytes.as_ptr() as usize;
+    let mut tampered = bytes.clone();
+    tampered[offset] ^= 0x01;
+    let parsed = parse(&tampered).unwrap();
+    let model = parsed.get("model").unwrap().as_file().unwrap();
+    assert_eq!(
+        model.decrypt(&keys()).unwrap_err(),
+        BlobfigError::DecryptionFailed
+    );
+
+    // Swap the plaintext mimetype
+    let at = bytes.windows(6).position(|w| w == b"tflite").unwrap();
+    let mut tampered = bytes.clone();
+    tampered[at..at + 6].copy_from_slice(b"onnxrt");
+    let parsed = parse(&tampered).unwrap();
+    let model = parsed.get("model").unwrap().as_file().unwrap();
+    assert_eq!(
+        model.decrypt(&keys()).unwrap_err(),
+        BlobfigError::DecryptionFailed
+    );
+}
+
+#[test]
+fn array_layout_is_authenticated() {
+    let columns = Array::new(DType::U8, vec![2, 3], vec![0, 3, 1, 4, 2, 5])
+        .with_order(Order::F)
+        .encrypt(Cipher::Aes256Gcm, "customer-a", &keys())
+        .unwrap();
+    let bytes = writer::to_bytes(Value::Array(columns)).unwrap();
+    let parsed = parse(&bytes).unwrap();
+    assert_eq!(
+        parsed.as_array().unwrap().decrypt(&keys()).unwrap().len(),
+        6
+    );
+
+    // Clear the Fortran flag in the layout byte following the tag, dtype,
+    // dimension count and shape
+    let at = HEADER_SIZE + 3 + 2 * 8;
+    assert_eq!(bytes[at], 1);
+    let mut tampered = bytes.clone();
+    tampered[at] = 0;
+    let parsed = parse(&tampered).unwrap();
+    let columns = parsed.as_array().unwrap();
+    assert_eq!(columns.layout.order, Order::C);
+    assert_eq!(
+        columns.decrypt(&keys()).unwrap_err(),
+        BlobfigError::DecryptionFailed
+    );
+}
+
+#[test]
+fn reader_exposes_cipher_fields() {
+    let bytes = artifact(Cipher::Aes256Gcm);
+    let mut reader = Reader::new(io::Cursor::new(&bytes)).unwrap();
+    let node = reader.get("model").unwrap().unwrap();
+    let node = node.as_file().unwrap();
+    let encrypted = node.encryption.as_ref().unwrap();
+    assert_eq!(encrypted.key_id, "customer-a");
+    assert_eq!(node.size as usize, MODEL.len() + blobfig::TAG_SIZE);
+}
@@ -0,0 +1,52 @@
+//! Integration tests for memory-mapped documents
+
+#![cfg(feature = "mmap")]
+
+use blobfig::{Array, BlobfigFile, DType, Value, writer};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn shared_between_threads() {
+    let path = std::env::temp_dir().join(format!("blobfig-{}-shared.bin", std::process::id()));
+    let weights: Vec<u8> = (0..64u8).collect();
+    let value = Value::Object(vec![
+        ("version".into(), Value::Int(3)),
+        (
+            "weights".into(),
+            Value::Array(Array::new(DType::U8, vec![8, 8], weights.clone())),
+        ),
+    ]);
+    std::fs::write(&path, writer::to_bytes(value).unwrap()).unwrap();
+
+    let file = Arc::new(BlobfigFile::open(&path).unwrap());
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let file = Arc::clone(&file);
+            thread::spawn(move || {
+                let weights = file.get("weights").unwrap().unwrap();
+                let weights = weights.as_array().unwrap();
+                // Payloads are aligned relative to the page-aligned mapping
+                assert_eq!(weights.data.as_ptr() as usize % 64, 0);
+                weights.data.to_vec()
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), weights);
+    }
+    assert_eq!(
+        file.lazy()
+            .get("version")
+            .unwrap()
+            .unwrap()
+            .decode()
+            .unwrap()
+            .as_int(),
+        Some(3)
+    );
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -5,7 +5,7 @@
 #![cfg(feature = "ndarray")]
 
 use blobfig::{Array, File, Value, parse, writer};
-use ndarray::{ArrayD, IxDyn, array, s};
+use ndarray::{ArrayD, IxDyn, ShapeBuilder, array, s};
 
 // =============================================================================
 // Basic ndarray roundtrips
@@ -392,3 +392,231 @@ fn tokenizer_with_embeddings() {
     let token_42_embedding = emb.slice(s![42, ..]);
     assert_eq!(token_42_embedding.len(), 32);
 }
+
+// =============================================================================
+// Half-precision checkpoints
+// =============================================================================
+
+#[test]
+fn half_precision_checkpoint() {
+    use blobfig::DType;
+    use half::{bf16, f16};
+
+    let weights = ArrayD::from_shape_fn(IxDyn(&[4, 8]), |i| (i[0] * 8 + i[1]) as f32 * 0.25);
+    let config = Value::Object(vec![
+        (
+            "fp16".into(),
+            Value::Array(Array::from_ndarray(weights.mapv(f16::from_f32)).unwrap()),
+        ),
+        (
+            "bf16".into(),
+            Value::Array(Array::from_ndarray(weights.mapv(bf16::from_f32)).unwrap()),
+        ),
+    ]);
+
+    let bytes = writer::to_bytes(config).unwrap();
+    let parsed = parse(&bytes).unwrap();
+
+    let fp16 = parsed.get("fp16").unwrap().as_array().unwrap();
+    assert_eq!(fp16.dtype, DType::F16);
+    assert_eq!(fp16.data.len(), 4 * 8 * 2);
+    assert_eq!(fp16.to_ndarray_f32().unwrap(), weights);
+
+    // Multiples of 0.25 up to 7.75 need at most 5 significant bits
+    let bf16 = parsed.get("bf16").unwrap().as_array().unwrap();
+    assert_eq!(bf16.dtype, DType::BF16);
+    assert_eq!(bf16.to_ndarray_f32().unwrap(), weights);
+}
+
+#[test]
+fn q8_0_weights_dequantize_to_ndarray() {
+    use blobfig::DType;
+
+    // Two rows of 64 elements, each block scaled by 0.25 (f16 0x3400)
+    let weights = ArrayD::from_shape_fn(IxDyn(&[2, 64]), |i| (i[1] as f32 - 32.0) * 0.25);
+    let mut data = Vec::new();
+    for block in weights.as_slice().unwrap().chunks(32) {
+        data.extend([0x00, 0x34]);
+        data.extend(block.iter().map(|w| (w / 0.25) as i8 as u8));
+    }
+    let quantized = Array::try_new(DType::Q8_0, vec![2, 64], data).unwrap();
+    assert_eq!(quantized.data.len(), 4 * 34);
+
+    let bytes = writer::to_bytes(Value::Object(vec![("w".into(), quantized.into())])).unwrap();
+    let parsed = parse(&bytes).unwrap();
+    let w = parsed.get("w").unwrap().as_array().unwrap();
+    assert_eq!(w.to_ndarray_f32().unwrap(), weights);
+    assert!(w.to_ndarray::<i8>().is_err());
+}
+
+// =============================================================================
+// Masks and filter banks
+// =============================================================================
+
+#[test]
+fn bool_masks_and_complex_filter_banks() {
+    use blobfig::DType;
+    use num_complex::Complex;
+
+    let mask = ArrayD::from_shape_fn(IxDyn(&[4, 4]), |i| i[1] <= i[0]);
+    let bank = ArrayD::from_shape_fn(IxDyn(&[2, 8]), |i| {
+        let phase = (i[0] * 8 + i[1]) as f64 * 0.1;
+        Complex::new(phase.cos(), phase.sin())
+    });
+    let config = Value::Object(vec![
+        (
+            "causal_mask".into(),
+            Value::Array(Array::from_ndarray(mask.clone()).unwrap()),
+        ),
+        (
+            "filter_bank".into(),
+            Value::Array(Array::from_ndarray(bank.clone()).unwrap()),
+        ),
+    ]);
+
+    let bytes = writer::to_bytes(config).unwrap();
+    let parsed = parse(&bytes).unwrap();
+
+    let view = parsed.get("causal_mask").unwrap().as_array().unwrap();
+    assert_eq!(view.dtype, DType::Bool);
+    assert_eq!(view.to_ndarray::<bool>().unwrap(), mask);
+
+    let view = parsed.get("filter_bank").unwrap().as_array().unwrap();
+    assert_eq!(view.dtype, DType::C128);
+    assert_eq!(view.data.len(), 2 * 8 * 16);
+    assert_eq!(view.to_ndarray::<Complex<f64>>().unwrap(), bank);
+}
+
+// =============================================================================
+// Zero-copy views over aligned payloads
+// =============================================================================
+
+#[test]
+fn zero_copy_view_after_roundtrip() {
+    let weights = ArrayD::<f64>::from_shape_fn(IxDyn(&[3, 5]), |idx| (idx[0] * 5 + idx[1]) as f64);
+    let expected = weights.clone();
+
+    // Odd-length entries before the tensor would misalign an unpadded payload
+    let config = Value::Object(vec![
+        ("name".into(), Value::String("odd".into())),
+        ("flag".into(), Value::Bool(true)),
+        (
+            "weights".into(),
+            Value::Array(Array::from_ndarray(weights).unwrap()),
+        ),
+    ]);
+
+    let bytes = writer::to_bytes(config).unwrap();
+
+    // Copy into a u64-backed buffer so the file start itself is 8-byte aligned
+    let mut storage = vec![0u64; bytes.len().div_ceil(8)];
+    // SAFETY: storage holds at least bytes.len() initialized bytes and outlives the slice
+    let aligned: &mut [u8] =
+        unsafe { std::slice::from_raw_parts_mut(storage.as_mut_ptr() as *mut u8, bytes.len()) };
+    aligned.copy_from_slice(&bytes);
+
+    let parsed = parse(aligned).unwrap();
+    let view = parsed
+        .get("weights")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .try_as_ndarray::<f64>()
+        .unwrap();
+    assert_eq!(view, expected.view());
+}
+
+// =============================================================================
+// Column-major arrays
+// =============================================================================
+
+#[test]
+fn fortran_order_roundtrip() {
+    use blobfig::{DType, Order};
+
+    // Element [i, j] is i + 3 * j, stored column by column
+    let columns = ArrayD::from_shape_vec(IxDyn(&[3, 4]).f(), (0..12u8).collect()).unwrap();
+    let expected = columns.clone();
+
+    let arr = Array::from_ndarray(columns).unwrap();
+    assert_eq!(arr.layout.order, Order::F);
+    assert_eq!(arr.data, (0..12).collect::<Vec<u8>>());
+    let arr = arr.with_axis_names(["channel", "sample"]);
+
+    let bytes = writer::to_bytes(Value::Array(arr)).unwrap();
+    let parsed = parse(&bytes).unwrap();
+    let parsed = parsed.as_array().unwrap();
+    assert_eq!(parsed.axis_index("sample"), Some(1));
+
+    let back: ArrayD<u8> = parsed.to_ndarray().unwrap();
+    assert_eq!(back, expected);
+    assert_eq!(back[[2, 1]], 5);
+    let view = parsed.try_as_ndarray::<u8>().unwrap();
+    assert_eq!(view, expected.view());
+    assert_eq!(view.strides(), [1, 3]);
+
+    // A zero stride repeats the elements along its axis
+    let repeated = Array::new(DType::U8, vec![2, 2], vec![1, 2, 0, 0]).with_strides(vec![0, 1]);
+    let back: ArrayD<u8> = repeated.to_ndarray().unwrap();
+    assert_eq!(back, array![[1u8, 2], [1, 2]].into_dyn());
+}
+
+// =============================================================================
+// Filtered compression
+// =============================================================================
+
+#[cfg(feature = "zstd")]
+#[test]
+fn shuffled_floats_compress_and_roundtrip() {
+    use blobfig::{Compression, Filters, NdarrayError};
+
+    // Smooth activation statistics: neighbouring values share exponent bytes
+    let stats = ArrayD::from_shape_fn(IxDyn(&[64, 64]), |i| {
+        ((i[0] * 64 + i[1]) as f32 * 0.001).sin()
+    });
+    let plain = Array::from_ndarray(stats.clone())
+        .unwrap()
+        .compress_with(Compression::Zstd, Filters::NONE)
+        .unwrap();
+    let shuffled = Array::from_ndarray(stats.clone())
+        .unwrap()
+        .compress(Compression::Zstd)
+        .unwrap();
+    assert_eq!(shuffled.compression.unwrap().filters, Filters::SHUFFLE);
+    assert!(shuffled.data.len() < plain.data.len());
+
+    // The writer picks the filters from the dtype
+    let options = writer::WriteOptions::default().with_compression(Compression::Zstd, 0);
+    let value = Value::Object(vec![
+        (
+            "stats".into(),
+            Value::Array(Array::from_ndarray(stats.clone()).unwrap()),
+        ),
+        (
+            "offsets".into(),
+            Value::Array(
+                Array::from_ndarray(ArrayD::from_shape_fn(IxDyn(&[1000]), |i| i[0] as i64 * 3))
+                    .unwrap(),
+            ),
+        ),
+    ]);
+    let bytes = writer::to_bytes_with(value, options).unwrap();
+    let parsed = parse(&bytes).unwrap();
+
+    let view = parsed.get("stats").unwrap().as_array().unwrap();
+    assert_eq!(view.compression.unwrap().filters, Filters::SHUFFLE);
+    assert_eq!(view.to_ndarray::<f32>().unwrap(), stats);
+    assert_eq!(
+        view.try_as_ndarray::<f32>().unwrap_err(),
+        NdarrayError::Compressed
+    );
+
+    let offsets = parsed.get("offsets").unwrap().as_array().unwrap();
+    assert_eq!(
+        offsets.compression.unwrap().filters,
+        Filters::DELTA | Filters::SHUFFLE
+    );
+    assert!(offsets.data.len() < 100);
+    let back: ArrayD<i64> = offsets.to_ndarray().unwrap();
+    assert_eq!(back[[999]], 2997);
+}
@@ -0,0 +1,63 @@
+//! Property tests for blobfig roundtrips
+//!
+//! The writer encodes every field of a value (including float bit patterns and
+//! entry order), so two values are equal exactly when their encodings are.
+
+#![cfg(feature = "proptest")]
+
+use blobfig::proptest_ext::arb_value;
+use blobfig::writer::{self, WriteOptions};
+use blobfig::{parse, parse_lazy, parse_verified};
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn write_parse_to_owned_is_identity(value in arb_value()) {
+        let bytes = writer::to_bytes(value).unwrap();
+        let owned = parse(&bytes).unwrap().to_owned();
+        prop_assert_eq!(writer::to_bytes(owned).unwrap(), bytes);
+    }
+
+    #[test]
+    fn identity_for_every_layout(
+        value in arb_value(),
+        alignment in 0u32..=12,
+        index: bool,
+        checksums: bool,
+    ) {
+        let mut options = match alignment {
+            0 => WriteOptions::unaligned(),
+            n => WriteOptions::aligned(1 << n),
+        };
+        options.path_index = index;
+        options.checksums = checksums;
+
+        let bytes = writer::to_bytes_with(value, options).unwrap();
+        if checksums {
+            prop_assert!(parse_verified(&bytes).is_ok());
+        }
+        let owned = parse(&bytes).unwrap().to_owned();
+        prop_assert_eq!(writer::to_bytes_with(owned, options).unwrap(), bytes);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn identity_with_compression(value in arb_value(), min_size in 0u64..64) {
+        let options = WriteOptions::unaligned()
+            .with_checksums()
+            .with_compression(blobfig::Compression::Lz4, min_size);
+        let bytes = writer::to_bytes_with(value, options).unwrap();
+        let parsed = parse_verified(&bytes).unwrap();
+        // Already compressed payloads are written as they are
+        let owned = parsed.to_owned();
+        prop_assert_eq!(writer::to_bytes_with(owned, options).unwrap(), bytes);
+    }
+
+    #[test]
+    fn lazy_view_matches_eager(value in arb_value()) {
+        let bytes = writer::to_bytes(value).unwrap();
+        let lazy = parse_lazy(&bytes).unwrap().to_view().unwrap().to_owned();
+        let eager = parse(&bytes).unwrap().to_owned();
+        prop_assert_eq!(writer::to_bytes(lazy).unwrap(), writer::to_bytes(eager).unwrap());
+    }
+}
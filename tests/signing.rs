@@ -0,0 +1,51 @@
+//! Integration tests for signed artifacts
+
+#![cfg(feature = "signing")]
+
+use blobfig::parser::parse_signed_detached;
+use blobfig::signing::DetachedSignature;
+use blobfig::{BlobfigError, KeyId, Value, parse_signed, writer};
+use ed25519_dalek::SigningKey;
+
+fn artifact() -> Vec<u8> {
+    writer::to_bytes(Value::Object(vec![
+        ("name".into(), Value::String("encoder".into())),
+        ("version".into(), Value::Int(7)),
+    ]))
+    .unwrap()
+}
+
+#[test]
+fn key_rotation() {
+    let old = SigningKey::from_bytes(&[1; 32]);
+    let new = SigningKey::from_bytes(&[2; 32]);
+
+    let mut signed_old = artifact();
+    writer::sign(&mut signed_old, &old);
+    let mut signed_new = artifact();
+    writer::sign(&mut signed_new, &new);
+
+    // During rotation devices trust both keys
+    let both = [old.verifying_key(), new.verifying_key()];
+    assert!(parse_signed(&signed_old, &both).is_ok());
+    assert!(parse_signed(&signed_new, &both).is_ok());
+
+    // Once the old key is retired its artifacts are refused
+    let err = parse_signed(&signed_old, &[new.verifying_key()]).unwrap_err();
+    assert_eq!(
+        err.kind,
+        BlobfigError::UntrustedKey(KeyId::of(&old.verifying_key()))
+    );
+}
+
+#[test]
+fn detached_signature_file() {
+    let key = SigningKey::from_bytes(&[3; 32]);
+    let bytes = artifact();
+    let signature = writer::sign_detached(&bytes, &key).to_bytes();
+
+    // Shipped as a separate file next to the artifact
+    let signature = DetachedSignature::from_bytes(&signature);
+    let parsed = parse_signed_detached(&bytes, &signature, &[key.verifying_key()]).unwrap();
+    assert_eq!(parsed.get("version").unwrap().as_int(), Some(7));
+}